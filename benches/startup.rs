@@ -0,0 +1,78 @@
+//! Time taken to build a CLI with 1500 leaves and to parse and run one of them,
+//! run with `cargo bench`
+
+extern crate clap;
+extern crate clap_nested;
+
+use std::time::Instant;
+
+use clap::Arg;
+use clap_nested::{Command, Commander};
+
+const GROUPS: usize = 50;
+const LEAVES: usize = 30;
+const RUNS: u32 = 200;
+
+fn cli<'a>(groups: &'a [String], leaves: &'a [String], trimmed: bool) -> Commander<'a, (), ()> {
+    groups.iter().fold(
+        Commander::new()
+            .options(|app| app.name("program"))
+            .trimmed_help(trimmed),
+        |commander, group| {
+            let inner = leaves.iter().fold(Commander::new(), |inner, leaf| {
+                inner.add_cmd(
+                    Command::new(leaf.as_str())
+                        .description("A leaf command")
+                        .options(|app| {
+                            app.arg(Arg::with_name("count").long("count").takes_value(true))
+                                .arg(Arg::with_name("force").long("force"))
+                        })
+                        .runner(|_args, _matches| Ok(())),
+                )
+            });
+
+            commander.add_cmd(
+                inner
+                    .trimmed_help(trimmed)
+                    .into_cmd(group.as_str())
+                    .description("A group of commands"),
+            )
+        },
+    )
+}
+
+fn main() {
+    let groups: Vec<String> = (0..GROUPS).map(|group| format!("group{}", group)).collect();
+    let leaves: Vec<String> = (0..LEAVES).map(|leaf| format!("leaf{}", leaf)).collect();
+
+    let runs: &[(&str, &[&str])] = &[
+        ("leaf", &["program", "group7", "leaf3", "--count", "2"]),
+        ("help", &["program", "group7", "--help"]),
+        ("typo", &["program", "group7", "leaf"]),
+    ];
+
+    for &trimmed in &[false, true] {
+        let start = Instant::now();
+        let commander = cli(&groups, &leaves, trimmed);
+
+        println!(
+            "{}: built in {:.3}ms",
+            if trimmed { "trimmed help" } else { "default" },
+            start.elapsed().as_secs_f64() * 1000.0
+        );
+
+        for (name, args) in runs {
+            let start = Instant::now();
+
+            for _ in 0..RUNS {
+                let _ = commander.run_with_args_result(args.iter());
+            }
+
+            println!(
+                "{:>16}: {:.3}ms per run",
+                name,
+                start.elapsed().as_secs_f64() * 1000.0 / f64::from(RUNS)
+            );
+        }
+    }
+}
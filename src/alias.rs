@@ -0,0 +1,158 @@
+//! Git-style aliases, expanded before arguments are parsed
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::result::Result as StdResult;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// Reads the `[alias]` table of a config file, e.g. `st = "show --env prod foo"`,
+/// where a missing file has no aliases
+pub(crate) fn load(path: &Path) -> StdResult<Vec<(String, String)>, ClapError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(ClapError::with_description(
+                &format!("Failed to read {}: {}", path.display(), err),
+                ClapErrorKind::Io,
+            ))
+        }
+    };
+
+    parse(&contents).map_err(|err| {
+        ClapError::with_description(
+            &format!("Invalid aliases in {}: {}", path.display(), err),
+            ClapErrorKind::InvalidValue,
+        )
+    })
+}
+
+fn parse(contents: &str) -> StdResult<Vec<(String, String)>, String> {
+    let mut aliases = Vec::new();
+    let mut in_alias = false;
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            in_alias = line[1..line.len() - 1].trim() == "alias";
+            continue;
+        }
+
+        if !in_alias {
+            continue;
+        }
+
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => {
+                return Err(format!(
+                    "line {}: expected `name = \"expansion\"`",
+                    index + 1
+                ))
+            }
+        };
+
+        let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+            None => value.to_owned(),
+        };
+
+        aliases.push((name.to_owned(), value));
+    }
+
+    Ok(aliases)
+}
+
+/// Splits an expansion, or a line of the REPL, into words honoring quotes and backslash escapes
+pub(crate) fn split(expansion: &str) -> StdResult<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut chars = expansion.chars();
+    let mut quote = None;
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_owned()),
+            },
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_owned());
+    }
+
+    words.extend(word);
+    Ok(words)
+}
+
+/// Expands the alias in command position (right after the binary name) until it no longer
+/// names an alias, or names one of the commands which always win over aliases
+pub(crate) fn expand(
+    args: &mut Vec<OsString>,
+    aliases: &[(String, String)],
+    is_command: impl Fn(&str) -> bool,
+) -> StdResult<(), ClapError> {
+    let mut seen: Vec<String> = Vec::new();
+
+    loop {
+        let name = match args.get(1).and_then(|arg| arg.to_str()) {
+            Some(name) if !is_command(name) => name.to_owned(),
+            _ => return Ok(()),
+        };
+
+        let expansion = match aliases.iter().find(|(alias, _)| *alias == name) {
+            Some((_, expansion)) => expansion,
+            None => return Ok(()),
+        };
+
+        if seen.contains(&name) {
+            seen.push(name);
+
+            return Err(ClapError::with_description(
+                &format!("Alias expands recursively: {}", seen.join(" -> ")),
+                ClapErrorKind::InvalidSubcommand,
+            ));
+        }
+
+        let words = split(expansion).map_err(|err| {
+            ClapError::with_description(
+                &format!("Invalid expansion of alias `{}`: {}", name, err),
+                ClapErrorKind::InvalidValue,
+            )
+        })?;
+
+        seen.push(name);
+        args.splice(1..2, words.into_iter().map(OsString::from));
+    }
+}
+
+#[test]
+fn parse_and_split() {
+    let aliases = parse(
+        "# comment\n[core]\nst = nope\n[alias]\nst = \"show --env prod foo\"\nq = say 'a b' \\\"c\n",
+    )
+    .unwrap();
+
+    assert_eq!(aliases.len(), 2);
+    assert_eq!(aliases[0].1, "show --env prod foo");
+    assert_eq!(split(&aliases[1].1).unwrap(), ["say", "a b", "\"c"]);
+    assert_eq!(split("a ''").unwrap(), ["a", ""]);
+    assert!(split("'open").is_err());
+}
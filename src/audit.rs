@@ -0,0 +1,144 @@
+//! A local audit log of invocations, one JSON object per line
+
+use std::ffi::OsString;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use capture;
+use redact;
+use report;
+use CommandPath;
+
+/// Where and how [`audit_log`](struct.Commander.html#method.audit_log) records invocations,
+/// e.g. `{"timestamp":"2019-10-14T12:00:00Z","path":["show","foo"],"args":["show","foo"],"exit":0}`
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    redact: Vec<String>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            redact: Vec::new(),
+        }
+    }
+
+    /// Hides the value of a sensitive option, e.g. `--password` or `-p`
+    pub fn redact(mut self, option: impl Into<String>) -> Self {
+        self.redact.push(option.into());
+        self
+    }
+
+    pub(crate) fn record(&self, args: &[OsString], path: &CommandPath, exit: i32) {
+        let args: Vec<_> = self
+            .sanitize(args)
+            .iter()
+            .map(|arg| report::string(arg))
+            .collect();
+        let path: Vec<_> = path
+            .segments()
+            .iter()
+            .map(|segment| report::string(segment))
+            .collect();
+
+        let line = format!(
+            "{{\"timestamp\":\"{}\",\"path\":[{}],\"args\":[{}],\"exit\":{}}}\n",
+            timestamp(SystemTime::now()),
+            path.join(","),
+            args.join(","),
+            exit
+        );
+
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+
+        // Failing to audit shouldn't fail the command itself
+        if let Err(err) = written {
+            let _ = writeln!(
+                capture::stderr(),
+                "warning: failed to write the audit log {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+
+    /// The arguments without the binary name, with the values of sensitive options redacted
+    fn sanitize(&self, args: &[OsString]) -> Vec<String> {
+        redact::sanitize(&self.redact, args)
+    }
+}
+
+/// Formats a time as RFC 3339 in UTC
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // The civil-from-days algorithm of Howard Hinnant
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[test]
+fn sanitize_and_timestamp() {
+    let audit = AuditLog::new("audit.log").redact("--password").redact("-p");
+    let args: Vec<OsString> = [
+        "program",
+        "login",
+        "--password",
+        "a",
+        "--password=b",
+        "-pc",
+        "-p",
+        "d",
+    ]
+    .iter()
+    .map(OsString::from)
+    .collect();
+
+    assert_eq!(
+        audit.sanitize(&args),
+        [
+            "login",
+            "--password",
+            "***",
+            "--password=***",
+            "-p***",
+            "-p",
+            "***"
+        ]
+    );
+    assert_eq!(
+        timestamp(UNIX_EPOCH + std::time::Duration::from_secs(1_571_054_400)),
+        "2019-10-14T12:00:00Z"
+    );
+}
@@ -0,0 +1,130 @@
+//! What's printed by the framework and the runners writing to `Context::stdout`
+//! and `Context::stderr`, captured by `Commander::run_capture`, which also gives
+//! `Context::read_stdin` its input
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::mem;
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Buffers>> = const { RefCell::new(None) };
+}
+
+#[derive(Default)]
+struct Buffers {
+    stdin: Vec<u8>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// What an invocation through [`run_capture`](struct.Commander.html#method.run_capture)
+/// printed, with its exit code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedRun {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: i32,
+}
+
+/// Runs `run` capturing what's written to the writers of this thread, reading `stdin`
+pub(crate) fn run(stdin: Vec<u8>, run: impl FnOnce() -> i32) -> CapturedRun {
+    let buffers = Buffers {
+        stdin,
+        ..Buffers::default()
+    };
+    let outer = CAPTURED.with(|captured| captured.replace(Some(buffers)));
+    let status = run();
+    let buffers = CAPTURED
+        .with(|captured| mem::replace(&mut *captured.borrow_mut(), outer))
+        .unwrap_or_default();
+
+    CapturedRun {
+        stdout: String::from_utf8_lossy(&buffers.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&buffers.stderr).into_owned(),
+        status,
+    }
+}
+
+/// Whether the streams of this thread are captured, which are then no terminals
+pub(crate) fn is_captured() -> bool {
+    CAPTURED.with(|captured| captured.borrow().is_some())
+}
+
+/// The input given to the captured run, `None` when this thread isn't captured
+pub(crate) fn take_stdin() -> Option<Vec<u8>> {
+    CAPTURED.with(|captured| {
+        captured
+            .borrow_mut()
+            .as_mut()
+            .map(|buffers| mem::take(&mut buffers.stdin))
+    })
+}
+
+/// The standard output or error, unless it's being captured on this thread
+///
+/// Only the thread calling `run_capture` is captured, the writers moved to the threads
+/// a runner spawns write to the real streams.
+#[derive(Clone, Copy, Debug)]
+pub struct Writer {
+    stderr: bool,
+}
+
+pub(crate) fn stdout() -> Writer {
+    Writer { stderr: false }
+}
+
+pub(crate) fn stderr() -> Writer {
+    Writer { stderr: true }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let captured = CAPTURED.with(|captured| match &mut *captured.borrow_mut() {
+            Some(buffers) => {
+                let buffer = if self.stderr {
+                    &mut buffers.stderr
+                } else {
+                    &mut buffers.stdout
+                };
+
+                buffer.extend_from_slice(buf);
+                true
+            }
+            None => false,
+        });
+
+        if captured {
+            Ok(buf.len())
+        } else if self.stderr {
+            io::stderr().lock().write(buf)
+        } else {
+            io::stdout().lock().write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.stderr {
+            io::stderr().flush()
+        } else {
+            io::stdout().flush()
+        }
+    }
+}
+
+#[test]
+fn captured() {
+    let run = run(Vec::new(), || {
+        let _ = write!(stdout(), "out");
+        let _ = writeln!(stderr(), "err");
+        2
+    });
+
+    assert_eq!(
+        run,
+        CapturedRun {
+            stdout: "out".to_owned(),
+            stderr: "err\n".to_owned(),
+            status: 2,
+        }
+    );
+}
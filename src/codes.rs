@@ -0,0 +1,89 @@
+//! Stable codes of errors, see `ErrorExt`, with their long-form explanations
+//! shown by the `explain` subcommand, see `Commander::explain`
+
+use clap::{App, Arg, ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind, SubCommand};
+
+use report;
+
+/// The name of the subcommand
+pub(crate) const NAME: &str = "explain";
+
+/// Errors carrying a stable code users can refer to, e.g. `error[E042]: Cannot reach the API`
+pub trait ErrorExt {
+    /// An error like `Error::with_description`, printed with `code` after `error`
+    fn with_code(code: &str, description: &str, kind: ClapErrorKind) -> Self;
+
+    /// The code of an error made with [`with_code`](#tymethod.with_code)
+    fn code(&self) -> Option<String>;
+}
+
+impl ErrorExt for ClapError {
+    fn with_code(code: &str, description: &str, kind: ClapErrorKind) -> Self {
+        let mut err = ClapError::with_description(description, kind);
+
+        err.message = err
+            .message
+            .replacen("error:", &format!("error[{}]:", code), 1);
+        err
+    }
+
+    fn code(&self) -> Option<String> {
+        let message = report::strip_ansi(&self.message);
+        let rest = message.strip_prefix("error[")?;
+
+        rest.find("]:").map(|end| rest[..end].to_owned())
+    }
+}
+
+/// The `explain <CODE>` subcommand
+pub(crate) fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Explains an error code in detail")
+        .arg(
+            Arg::with_name("code")
+                .required(true)
+                .help("The code of the error, e.g. E042"),
+        )
+}
+
+/// The explanation of the code given in the matches
+pub(crate) fn explain<'e>(
+    explanations: &[(&str, &'e str)],
+    matches: &ArgMatches<'_>,
+) -> Result<&'e str, ClapError> {
+    let code = matches.value_of("code").unwrap_or_default();
+
+    explanations
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+        .ok_or_else(|| {
+            ClapError::with_description(
+                &format!("No explanation for the error code '{}'", code),
+                ClapErrorKind::InvalidValue,
+            )
+        })
+}
+
+#[test]
+fn coded() {
+    let err = ClapError::with_code("E042", "Cannot reach the API", ClapErrorKind::Io);
+
+    assert!(report::strip_ansi(&err.message).starts_with("error[E042]: Cannot reach the API"));
+    assert_eq!(err.code().as_deref(), Some("E042"));
+    assert_eq!(
+        ClapError::with_description("Plain", ClapErrorKind::Io).code(),
+        None
+    );
+
+    let explanations = [("E042", "The API is down.\n")];
+    let matches = subcommand().get_matches_from(vec!["explain", "e042"]);
+
+    assert_eq!(
+        explain(&explanations, &matches).unwrap(),
+        "The API is down.\n"
+    );
+
+    let matches = subcommand().get_matches_from(vec!["explain", "E001"]);
+    assert!(explain(&explanations, &matches).is_err());
+}
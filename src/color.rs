@@ -0,0 +1,50 @@
+use std::io::IsTerminal;
+
+use clap::AppSettings;
+
+/// Whether output should be colored, from `--color <auto|always|never>`,
+/// see [`with_color`](struct.Commander.html#method.with_color)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub(crate) const VALUES: &'static [&'static str] = &["auto", "always", "never"];
+
+    /// An explicit choice wins, otherwise a non-empty `NO_COLOR` turns colors off
+    pub(crate) fn resolve(value: Option<&str>) -> Self {
+        match value {
+            Some("always") => ColorChoice::Always,
+            Some("never") => ColorChoice::Never,
+            Some(_) => ColorChoice::Auto,
+            None if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) => {
+                ColorChoice::Never
+            }
+            None => ColorChoice::Auto,
+        }
+    }
+
+    pub(crate) fn setting(self) -> AppSettings {
+        match self {
+            ColorChoice::Auto => AppSettings::ColorAuto,
+            ColorChoice::Always => AppSettings::ColorAlways,
+            ColorChoice::Never => AppSettings::ColorNever,
+        }
+    }
+
+    /// Whether to color the standard output, where `Auto`
+    /// colors terminals unless `TERM` is `dumb`
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+            }
+        }
+    }
+}
@@ -0,0 +1,694 @@
+//! Shell completion scripts, printed or installed, see `Commander::with_completions`
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, Write as IoWrite};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use clap::{
+    App, AppSettings, Arg, ArgMatches, ArgSettings, Error as ClapError, ErrorKind as ClapErrorKind,
+    Shell, SubCommand,
+};
+
+use capture;
+use dirs;
+use report;
+use Result;
+
+/// The name of the subcommand
+pub(crate) const NAME: &str = "completions";
+
+/// Generates the completion script of a shell for `completions <SHELL>`, on top of
+/// the built-in bash, zsh, fish, PowerShell, Elvish, Nushell and Fig ones,
+/// see [`Commander::completion_backend`](struct.Commander.html#method.completion_backend)
+pub trait CompletionBackend {
+    /// The name of the shell, e.g. `xonsh`, replacing the built-in one with that name
+    fn shell(&self) -> &'static str;
+
+    /// The script completing the whole tree of commands, invoked as `bin_name`
+    fn generate(&self, app: &App<'_, '_>, bin_name: &str) -> String;
+
+    /// Where `completions install` writes the script, with what to add to the shell rc,
+    /// `--path` being required when there's none
+    fn location(&self, _bin_name: &str) -> Option<(PathBuf, String)> {
+        None
+    }
+}
+
+struct Builtin(&'static str);
+
+static BUILTINS: &[Builtin] = &[
+    Builtin("bash"),
+    Builtin("zsh"),
+    Builtin("fish"),
+    Builtin("powershell"),
+    Builtin("elvish"),
+    Builtin("nushell"),
+    Builtin("fig"),
+];
+
+impl CompletionBackend for Builtin {
+    fn shell(&self) -> &'static str {
+        self.0
+    }
+
+    fn generate(&self, app: &App<'_, '_>, bin_name: &str) -> String {
+        match self.0 {
+            "fish" => fish(app, bin_name),
+            "nushell" => nushell(app, bin_name),
+            "fig" => format!("{}\n", fig_command(app, bin_name)),
+            shell => {
+                let mut buf = Vec::new();
+
+                if let Ok(shell) = shell.parse::<Shell>() {
+                    app.clone().gen_completions_to(bin_name, shell, &mut buf);
+                }
+
+                String::from_utf8_lossy(&buf).into_owned()
+            }
+        }
+    }
+
+    fn location(&self, bin_name: &str) -> Option<(PathBuf, String)> {
+        location(self.0, bin_name)
+    }
+}
+
+/// The built-in backends, but those replaced, followed by the registered ones
+pub(crate) fn backends<'b>(
+    custom: &'b [Rc<dyn CompletionBackend + 'b>],
+) -> Vec<&'b dyn CompletionBackend> {
+    let custom = custom
+        .iter()
+        .map(|backend| &**backend as &dyn CompletionBackend);
+
+    BUILTINS
+        .iter()
+        .filter(|builtin| custom.clone().all(|backend| backend.shell() != builtin.0))
+        .map(|builtin| builtin as &dyn CompletionBackend)
+        .chain(custom.clone())
+        .collect()
+}
+
+/// The `completions <SHELL>` and `completions install <SHELL> [--path <PATH>]` subcommands
+pub(crate) fn subcommand<'a, 'b>(backends: &[&dyn CompletionBackend]) -> App<'a, 'b> {
+    let shell = || {
+        backends.iter().fold(
+            Arg::with_name("shell")
+                .required(true)
+                .help("The shell to complete in"),
+            |arg, backend| arg.possible_value(backend.shell()),
+        )
+    };
+
+    SubCommand::with_name(NAME)
+        .about("Prints the shell completion script")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(shell())
+        .subcommand(
+            SubCommand::with_name("install")
+                .about("Writes the shell completion script where the shell finds it")
+                .arg(shell())
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .takes_value(true)
+                        .help("Writes the script there instead"),
+                ),
+        )
+}
+
+/// Prints or installs the script completing `app`
+pub(crate) fn run(
+    mut app: App<'_, '_>,
+    matches: &ArgMatches<'_>,
+    backends: &[&dyn CompletionBackend],
+) -> Result {
+    let bin_name = app
+        .p
+        .meta
+        .bin_name
+        .clone()
+        .unwrap_or_else(|| app.p.meta.name.clone());
+
+    prepare(&mut app, &bin_name);
+
+    let (matches, install) = match matches.subcommand() {
+        ("install", Some(install)) => (install, true),
+        _ => (matches, false),
+    };
+    let backend = backends
+        .iter()
+        .find(|backend| Some(backend.shell()) == matches.value_of("shell"))
+        .ok_or_else(|| {
+            ClapError::with_description("No completion backend for that shell", ClapErrorKind::Io)
+        })?;
+    let script = backend.generate(&app, &bin_name);
+
+    if !install {
+        let _ = write!(capture::stdout(), "{}", script);
+        return Ok(());
+    }
+
+    let shell = backend.shell();
+    let (path, hint) = match matches.value_of_os("path") {
+        Some(path) => (
+            PathBuf::from(path),
+            "Make sure your shell loads that file".to_owned(),
+        ),
+        None => backend.location(&bin_name).ok_or_else(|| {
+            ClapError::with_description(
+                &format!("Cannot find where {} completions go, try --path", shell),
+                ClapErrorKind::Io,
+            )
+        })?,
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, script));
+
+    written.map_err(|err| {
+        ClapError::with_description(
+            &format!("Cannot write {}: {}", path.display(), err),
+            ClapErrorKind::Io,
+        )
+    })?;
+
+    let _ = writeln!(
+        capture::stdout(),
+        "Installed the {} completions to {}\n{}",
+        shell,
+        path.display(),
+        hint
+    );
+    Ok(())
+}
+
+/// Builds the tree the way clap does before generating, e.g. adding the `--help` flags
+fn prepare(app: &mut App<'_, '_>, bin_name: &str) {
+    app.gen_completions_to(bin_name, Shell::Bash, &mut io::sink());
+}
+
+/// The fish script, where unlike clap's each command only completes its own options
+/// and subcommands, conditioned on the whole path leading to it, with their descriptions
+fn fish(app: &App<'_, '_>, bin_name: &str) -> String {
+    let mut out = String::new();
+
+    fish_command(&mut out, bin_name, &[], app);
+    out
+}
+
+fn fish_command(out: &mut String, bin_name: &str, path: &[&str], app: &App<'_, '_>) {
+    let p = &app.p;
+    let subcmds: Vec<_> = p
+        .subcommands
+        .iter()
+        .filter(|subcmd| !subcmd.p.is_set(AppSettings::Hidden))
+        .collect();
+
+    let mut condition = if path.is_empty() {
+        "__fish_use_subcommand".to_owned()
+    } else {
+        path.iter()
+            .map(|name| format!("__fish_seen_subcommand_from {}", name))
+            .collect::<Vec<_>>()
+            .join("; and ")
+    };
+
+    if !path.is_empty() && !subcmds.is_empty() {
+        let names: Vec<_> = subcmds.iter().map(|subcmd| &*subcmd.p.meta.name).collect();
+        let _ = write!(
+            condition,
+            "; and not __fish_seen_subcommand_from {}",
+            names.join(" ")
+        );
+    }
+
+    let mut line = |args: String, help: Option<&str>| {
+        let _ = write!(out, "complete -c {} -n \"{}\"{}", bin_name, condition, args);
+
+        if let Some(help) = help {
+            let _ = write!(out, " -d '{}'", fish_escape(help));
+        }
+
+        out.push('\n');
+    };
+    let switches = |short: Option<char>, long: Option<&str>| {
+        let short = short.map(|short| format!(" -s {}", short));
+        let long = long.map(|long| format!(" -l {}", long));
+
+        short.unwrap_or_default() + &long.unwrap_or_default()
+    };
+    let values = |values: &Option<Vec<&str>>| match values {
+        Some(values) => format!(" -f -a '{}'", fish_escape(&values.join(" "))),
+        None => String::new(),
+    };
+
+    for pos in p.positionals.values() {
+        if !pos.b.is_set(ArgSettings::Hidden) && pos.v.possible_vals.is_some() {
+            line(values(&pos.v.possible_vals), pos.b.help);
+        }
+    }
+
+    for opt in &p.opts {
+        if !opt.b.is_set(ArgSettings::Hidden) {
+            let args = switches(opt.s.short, opt.s.long) + " -r" + &values(&opt.v.possible_vals);
+            line(args, opt.b.help);
+        }
+    }
+
+    for flag in &p.flags {
+        if !flag.b.is_set(ArgSettings::Hidden) {
+            line(switches(flag.s.short, flag.s.long), flag.b.help);
+        }
+    }
+
+    for subcmd in &subcmds {
+        let args = format!(" -f -a '{}'", fish_escape(&subcmd.p.meta.name));
+        line(args, subcmd.p.meta.about);
+    }
+
+    for subcmd in subcmds {
+        let mut path = path.to_vec();
+
+        path.push(&subcmd.p.meta.name);
+        fish_command(out, bin_name, &path, subcmd);
+    }
+}
+
+/// The Nushell script, an `extern` per command with its arguments, their help
+/// and the completers of their possible values
+fn nushell(app: &App<'_, '_>, bin_name: &str) -> String {
+    let mut out = String::new();
+
+    nushell_command(&mut out, bin_name, app);
+    out
+}
+
+fn nushell_command(out: &mut String, path: &str, app: &App<'_, '_>) {
+    let p = &app.p;
+    let mut params = Vec::new();
+    let completer = |out: &mut String, name: &str, values: &Option<Vec<&str>>| {
+        let values = match values {
+            Some(values) => values,
+            None => return String::new(),
+        };
+        let name = format!("nu-complete {} {}", path, name);
+        let values: Vec<_> = values.iter().map(|value| nushell_string(value)).collect();
+
+        let _ = writeln!(
+            out,
+            "def {} [] {{\n    [{}]\n}}\n",
+            nushell_string(&name),
+            values.join(" ")
+        );
+        format!("@{}", nushell_string(&name))
+    };
+    let switches = |short: Option<char>, long: Option<&str>| match (short, long) {
+        (Some(short), Some(long)) => format!("--{}(-{})", long, short),
+        (None, Some(long)) => format!("--{}", long),
+        (Some(short), None) => format!("-{}", short),
+        (None, None) => String::new(),
+    };
+
+    for pos in p.positionals.values() {
+        let name = pos.b.name.replace('-', "_");
+        let complete = completer(out, pos.b.name, &pos.v.possible_vals);
+        let param = if pos.b.is_set(ArgSettings::Multiple) {
+            format!("...{}: string{}", name, complete)
+        } else if pos.b.is_set(ArgSettings::Required) {
+            format!("{}: string{}", name, complete)
+        } else {
+            format!("{}?: string{}", name, complete)
+        };
+
+        params.push((param, pos.b.help));
+    }
+
+    for opt in &p.opts {
+        let complete = completer(out, opt.b.name, &opt.v.possible_vals);
+        let param = format!("{}: string{}", switches(opt.s.short, opt.s.long), complete);
+
+        params.push((param, opt.b.help));
+    }
+
+    for flag in &p.flags {
+        params.push((switches(flag.s.short, flag.s.long), flag.b.help));
+    }
+
+    if let Some(about) = p.meta.about {
+        let _ = writeln!(out, "# {}", about.replace('\n', " "));
+    }
+
+    let _ = writeln!(out, "export extern {} [", nushell_string(path));
+
+    for (param, help) in params {
+        let _ = match help {
+            Some(help) => writeln!(out, "    {} # {}", param, help.replace('\n', " ")),
+            None => writeln!(out, "    {}", param),
+        };
+    }
+
+    out.push_str("]\n\n");
+
+    for subcmd in &p.subcommands {
+        if !subcmd.p.is_set(AppSettings::Hidden) {
+            let path = format!("{} {}", path, subcmd.p.meta.name);
+            nushell_command(out, &path, subcmd);
+        }
+    }
+}
+
+/// The [Fig](https://fig.io) spec of a command, with its options, arguments and subcommands
+fn fig_command(app: &App<'_, '_>, name: &str) -> String {
+    fn object(fields: Vec<(&str, String)>) -> String {
+        let fields: Vec<_> = fields
+            .into_iter()
+            .map(|(key, value)| format!("{}:{}", report::string(key), value))
+            .collect();
+
+        format!("{{{}}}", fields.join(","))
+    }
+
+    fn array(values: impl IntoIterator<Item = String>) -> String {
+        format!("[{}]", values.into_iter().collect::<Vec<_>>().join(","))
+    }
+
+    fn arg(
+        name: String,
+        help: Option<&str>,
+        values: &Option<Vec<&str>>,
+    ) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("name", report::string(&name))];
+
+        if let Some(help) = help {
+            fields.push(("description", report::string(help)));
+        }
+
+        if let Some(values) = values {
+            fields.push((
+                "suggestions",
+                array(values.iter().map(|value| report::string(value))),
+            ));
+        }
+
+        fields
+    }
+
+    let p = &app.p;
+    let mut fields = vec![("name", report::string(name))];
+
+    if let Some(about) = p.meta.about {
+        fields.push(("description", report::string(about)));
+    }
+
+    let subcmds: Vec<_> = p
+        .subcommands
+        .iter()
+        .filter(|subcmd| !subcmd.p.is_set(AppSettings::Hidden))
+        .map(|subcmd| fig_command(subcmd, &subcmd.p.meta.name))
+        .collect();
+    let switches = |short: Option<char>, long: Option<&str>| {
+        let short = short.map(|short| report::string(&format!("-{}", short)));
+        let long = long.map(|long| report::string(&format!("--{}", long)));
+
+        array(short.into_iter().chain(long))
+    };
+    let flags = p
+        .flags
+        .iter()
+        .filter(|flag| !flag.b.is_set(ArgSettings::Hidden))
+        .map(|flag| {
+            let mut fields = vec![("name", switches(flag.s.short, flag.s.long))];
+
+            if let Some(help) = flag.b.help {
+                fields.push(("description", report::string(help)));
+            }
+
+            if flag.b.is_set(ArgSettings::Multiple) {
+                fields.push(("isRepeatable", "true".to_owned()));
+            }
+
+            object(fields)
+        });
+    let opts = p
+        .opts
+        .iter()
+        .filter(|opt| !opt.b.is_set(ArgSettings::Hidden))
+        .map(|opt| {
+            let value = match opt
+                .v
+                .val_names
+                .as_ref()
+                .and_then(|names| names.values().next())
+            {
+                Some(value) => value.to_string(),
+                None => opt.b.name.to_uppercase(),
+            };
+            let mut fields = vec![("name", switches(opt.s.short, opt.s.long))];
+
+            if let Some(help) = opt.b.help {
+                fields.push(("description", report::string(help)));
+            }
+
+            if opt.b.is_set(ArgSettings::Multiple) {
+                fields.push(("isRepeatable", "true".to_owned()));
+            }
+
+            fields.push(("args", object(arg(value, None, &opt.v.possible_vals))));
+            object(fields)
+        });
+    let options: Vec<_> = flags.chain(opts).collect();
+    let args: Vec<_> = p
+        .positionals
+        .values()
+        .filter(|pos| !pos.b.is_set(ArgSettings::Hidden))
+        .map(|pos| {
+            let mut fields = arg(pos.b.name.to_owned(), pos.b.help, &pos.v.possible_vals);
+
+            if !pos.b.is_set(ArgSettings::Required) {
+                fields.push(("isOptional", "true".to_owned()));
+            }
+
+            if pos.b.is_set(ArgSettings::Multiple) {
+                fields.push(("isVariadic", "true".to_owned()));
+            }
+
+            object(fields)
+        })
+        .collect();
+
+    for (key, values) in [
+        ("subcommands", subcmds),
+        ("options", options),
+        ("args", args),
+    ] {
+        if !values.is_empty() {
+            fields.push((key, array(values)));
+        }
+    }
+
+    object(fields)
+}
+
+fn nushell_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escapes text inside single quotes
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// The conventional location of the script of a shell, with what to add to the shell rc
+fn location(shell: &str, bin_name: &str) -> Option<(PathBuf, String)> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+
+    Some(match shell {
+        "bash" => (
+            dirs::data_dir()?
+                .join("bash-completion/completions")
+                .join(bin_name),
+            "bash-completion loads it from there on its own".to_owned(),
+        ),
+        "zsh" => (
+            home()?.join(".zfunc").join(format!("_{}", bin_name)),
+            "Add `fpath=(~/.zfunc $fpath)` before `compinit` in ~/.zshrc".to_owned(),
+        ),
+        "fish" => (
+            dirs::config_dir()?
+                .join("fish/completions")
+                .join(format!("{}.fish", bin_name)),
+            "fish loads it from there on its own".to_owned(),
+        ),
+        "powershell" => {
+            let dir = if cfg!(windows) {
+                PathBuf::from(env::var_os("USERPROFILE")?).join("Documents/PowerShell")
+            } else {
+                dirs::config_dir()?.join("powershell")
+            };
+            let path = dir.join("completions").join(format!("{}.ps1", bin_name));
+            let hint = format!("Add `. {}` to your $PROFILE", path.display());
+
+            (path, hint)
+        }
+        "elvish" => (
+            dirs::config_dir()?
+                .join("elvish/lib")
+                .join(format!("{}.elv", bin_name)),
+            format!("Add `use {}` to ~/.config/elvish/rc.elv", bin_name),
+        ),
+        "nushell" => {
+            let path = dirs::config_dir()?
+                .join("nushell/completions")
+                .join(format!("{}.nu", bin_name));
+            let hint = format!("Add `use {} *` to your config.nu", path.display());
+
+            (path, hint)
+        }
+        _ => return None,
+    })
+}
+
+#[test]
+fn installed() {
+    let dir = env::temp_dir().join(format!("clap-nested-completions-{}", std::process::id()));
+    let path = dir.join("program.fish");
+    let app = App::new("program").subcommand(SubCommand::with_name("show"));
+    let completions = App::new("completions-test").subcommand(subcommand(&backends(&[])));
+    let matches = completions.get_matches_from(vec![
+        "completions-test",
+        "completions",
+        "install",
+        "fish",
+        "--path",
+        path.to_str().unwrap(),
+    ]);
+
+    let installed = capture::run(Vec::new(), || {
+        run(
+            app,
+            matches.subcommand_matches(NAME).unwrap(),
+            &backends(&[]),
+        )
+        .unwrap();
+        0
+    });
+
+    assert!(installed
+        .stdout
+        .starts_with("Installed the fish completions to"));
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .contains("complete -c program"));
+
+    let (zsh, hint) = location("zsh", "program").unwrap();
+    assert!(zsh.ends_with(".zfunc/_program"));
+    assert!(hint.contains("fpath"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fish_tree() {
+    let mut app = App::new("program").subcommand(
+        SubCommand::with_name("show")
+            .about("Shows things")
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .help("The format"),
+            )
+            .subcommand(SubCommand::with_name("foo").about("Shows foo's things")),
+    );
+
+    prepare(&mut app, "program");
+
+    let script = Builtin("fish").generate(&app, "program");
+    let lines: Vec<_> = script.lines().collect();
+    let show = "complete -c program -n \"__fish_seen_subcommand_from show; \
+                and not __fish_seen_subcommand_from foo help\"";
+
+    assert!(lines.contains(
+        &"complete -c program -n \"__fish_use_subcommand\" -f -a 'show' -d 'Shows things'"
+    ));
+    assert!(lines.contains(&&*format!(
+        "{} -l format -r -f -a 'text json' -d 'The format'",
+        show
+    )));
+    assert!(lines.contains(&&*format!("{} -f -a 'foo' -d 'Shows foo\\'s things'", show)));
+    assert!(lines.contains(
+        &"complete -c program -n \"__fish_seen_subcommand_from show; \
+          and __fish_seen_subcommand_from foo\" -s h -l help -d 'Prints help information'"
+    ));
+}
+
+#[test]
+fn nushell_tree() {
+    let mut app = App::new("program").subcommand(
+        SubCommand::with_name("show")
+            .about("Shows things")
+            .arg(
+                Arg::with_name("format")
+                    .short("f")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .help("The format"),
+            )
+            .arg(Arg::with_name("item-name").multiple(true)),
+    );
+
+    prepare(&mut app, "program");
+
+    let script = Builtin("nushell").generate(&app, "program");
+
+    assert!(script.contains(
+        "def \"nu-complete program show format\" [] {
+    [\"text\" \"json\"]
+}
+
+# Shows things
+export extern \"program show\" [
+    ...item_name: string
+    --format(-f): string@\"nu-complete program show format\" # The format
+    --help(-h) # Prints help information
+"
+    ));
+}
+
+#[test]
+fn fig_spec() {
+    let mut app = App::new("program").subcommand(
+        SubCommand::with_name("show")
+            .about("Shows things")
+            .arg(
+                Arg::with_name("format")
+                    .short("f")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .help("The format"),
+            )
+            .arg(Arg::with_name("item").multiple(true).help("The items")),
+    );
+
+    prepare(&mut app, "program");
+
+    let spec = Builtin("fig").generate(&app, "program");
+
+    assert!(spec.starts_with("{\"name\":\"program\",\"subcommands\":[{\"name\":\"show\",\"description\":\"Shows things\","));
+    assert!(spec.contains(
+        "{\"name\":[\"-f\",\"--format\"],\"description\":\"The format\",\
+         \"args\":{\"name\":\"FORMAT\",\"suggestions\":[\"text\",\"json\"]}}"
+    ));
+    assert!(spec.contains(
+        "\"args\":[{\"name\":\"item\",\"description\":\"The items\",\
+         \"isOptional\":true,\"isVariadic\":true}]"
+    ));
+}
@@ -0,0 +1,262 @@
+//! Layered configuration from files and environment variables
+//!
+//! Files are TOML (with the `toml` feature) or YAML (with the `yaml` feature),
+//! picked by extension. Nested tables are flattened into dotted keys,
+//! so `[show] env = "prod"` is available as `show.env`.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use dirs;
+
+/// Merged configuration values, where later layers win
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    values: BTreeMap<String, Vec<String>>,
+    env_prefix: Option<String>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets a value, preferring the `<PREFIX>_<KEY>` environment variable when
+    /// an environment prefix is set (dots and dashes in the key become underscores)
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_env(key).or_else(|| self.get_file(key))
+    }
+
+    pub(crate) fn get_env(&self, key: &str) -> Option<String> {
+        self.env_var(key).and_then(|value| value.into_string().ok())
+    }
+
+    pub(crate) fn get_file(&self, key: &str) -> Option<String> {
+        self.values
+            .get(key)
+            .and_then(|values| values.first())
+            .cloned()
+    }
+
+    /// Gets all values of a key from the files, e.g. of an array
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.values.get(key).map_or(&[], |values| &values[..])
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(|key| key.as_str())
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(key.into(), vec![value.into()]);
+    }
+
+    /// Takes all values of `other`, overriding the existing ones
+    pub fn merge(&mut self, other: Config) {
+        self.values.extend(other.values);
+
+        if other.env_prefix.is_some() {
+            self.env_prefix = other.env_prefix;
+        }
+    }
+
+    pub fn env_prefix(&self) -> Option<&str> {
+        self.env_prefix.as_deref()
+    }
+
+    pub fn set_env_prefix(&mut self, prefix: impl Into<String>) {
+        self.env_prefix = Some(prefix.into());
+    }
+
+    fn env_var(&self, key: &str) -> Option<OsString> {
+        let prefix = self.env_prefix.as_ref()?;
+        std::env::var_os(env_name(prefix, key))
+    }
+
+    /// Loads a file, picking the format by extension
+    #[cfg_attr(not(any(feature = "toml", feature = "yaml")), allow(unused_variables))]
+    pub fn from_file(path: &Path) -> StdResult<Self, ClapError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            ClapError::with_description(
+                &format!("Failed to read {}: {}", path.display(), err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+
+        let parsed = match extension {
+            #[cfg(feature = "toml")]
+            "toml" => Self::from_toml(&contents),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Self::from_yaml(&contents),
+            _ => Err(format!("unsupported format `{}`", extension)),
+        };
+
+        parsed.map_err(|err| {
+            ClapError::with_description(
+                &format!("Invalid config in {}: {}", path.display(), err),
+                ClapErrorKind::InvalidValue,
+            )
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(contents: &str) -> StdResult<Self, String> {
+        fn flatten(config: &mut Config, prefix: &str, value: &toml::Value) {
+            match value {
+                toml::Value::Table(table) => {
+                    for (key, value) in table {
+                        flatten(config, &join_key(prefix, key), value);
+                    }
+                }
+                toml::Value::Array(array) => {
+                    let values = array.iter().filter_map(toml_scalar).collect();
+                    config.values.insert(prefix.to_owned(), values);
+                }
+                value => {
+                    config
+                        .values
+                        .extend(toml_scalar(value).map(|v| (prefix.to_owned(), vec![v])));
+                }
+            }
+        }
+
+        fn toml_scalar(value: &toml::Value) -> Option<String> {
+            match value {
+                toml::Value::String(s) => Some(s.clone()),
+                toml::Value::Table(_) | toml::Value::Array(_) => None,
+                value => Some(value.to_string()),
+            }
+        }
+
+        let value: toml::Value = contents
+            .parse()
+            .map_err(|err: toml::de::Error| err.to_string())?;
+        let mut config = Self::new();
+
+        flatten(&mut config, "", &value);
+        Ok(config)
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(contents: &str) -> StdResult<Self, String> {
+        fn flatten(config: &mut Config, prefix: &str, value: &serde_yaml::Value) {
+            match value {
+                serde_yaml::Value::Mapping(mapping) => {
+                    for (key, value) in mapping {
+                        if let Some(key) = yaml_scalar(key) {
+                            flatten(config, &join_key(prefix, &key), value);
+                        }
+                    }
+                }
+                serde_yaml::Value::Sequence(sequence) => {
+                    let values = sequence.iter().filter_map(yaml_scalar).collect();
+                    config.values.insert(prefix.to_owned(), values);
+                }
+                value => {
+                    config
+                        .values
+                        .extend(yaml_scalar(value).map(|v| (prefix.to_owned(), vec![v])));
+                }
+            }
+        }
+
+        fn yaml_scalar(value: &serde_yaml::Value) -> Option<String> {
+            match value {
+                serde_yaml::Value::String(s) => Some(s.clone()),
+                serde_yaml::Value::Bool(b) => Some(b.to_string()),
+                serde_yaml::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            }
+        }
+
+        let value: serde_yaml::Value =
+            serde_yaml::from_str(contents).map_err(|err| err.to_string())?;
+        let mut config = Self::new();
+
+        flatten(&mut config, "", &value);
+        Ok(config)
+    }
+}
+
+#[allow(dead_code)]
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+pub(crate) fn env_name(prefix: &str, key: &str) -> String {
+    format!("{}_{}", prefix, key)
+        .to_uppercase()
+        .replace(['.', '-'], "_")
+}
+
+/// The extensions of the formats enabled by features
+fn extensions() -> &'static [&'static str] {
+    &[
+        #[cfg(feature = "toml")]
+        "toml",
+        #[cfg(feature = "yaml")]
+        "yaml",
+        #[cfg(feature = "yaml")]
+        "yml",
+    ]
+}
+
+/// The standard config file locations of an app, from the least to the most specific
+pub(crate) fn standard_paths(name: &str) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    if cfg!(unix) {
+        dirs.push(Path::new("/etc").join(name));
+    }
+
+    if let Some(dir) = dirs::config_dir() {
+        dirs.push(dir.join(name));
+    }
+
+    let mut paths = Vec::new();
+
+    for dir in dirs {
+        for ext in extensions() {
+            paths.push(dir.join(format!("config.{}", ext)));
+        }
+    }
+
+    paths
+}
+
+/// Loads the standard locations of `name` (if any) followed by the given files,
+/// only the latter must exist
+pub(crate) fn load(name: Option<&str>, files: &[PathBuf]) -> StdResult<Config, ClapError> {
+    let mut config = Config::new();
+
+    if let Some(name) = name {
+        for path in standard_paths(name) {
+            if path.is_file() {
+                config.merge(Config::from_file(&path)?);
+            }
+        }
+
+        config.set_env_prefix(name.to_uppercase().replace('-', "_"));
+    }
+
+    for path in files {
+        config.merge(Config::from_file(path)?);
+    }
+
+    Ok(config)
+}
+
+#[test]
+fn env_names() {
+    assert_eq!(env_name("MYAPP", "show.dry-run"), "MYAPP_SHOW_DRY_RUN");
+}
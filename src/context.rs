@@ -0,0 +1,419 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::ffi::OsString;
+#[cfg(feature = "serde")]
+use std::io::Write;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::result::Result as StdResult;
+
+use clap::{ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind};
+
+use capture::{self, Writer};
+use color::ColorChoice;
+use config::Config;
+use dirs;
+use editor;
+use help::Help;
+use interrupt::{CancellationToken, Cleanups};
+use output::OutputFormat;
+#[cfg(feature = "rpassword")]
+use prompt;
+use services::{Instances, Services};
+use table::Table;
+use Cause;
+use CommandPath;
+#[cfg(feature = "serde")]
+use Result;
+
+/// Information about the current invocation, given to runners
+/// registered with [`runner_with_context`](struct.Command.html#method.runner_with_context)
+pub struct Context<'c> {
+    /// The name of the app's directories
+    name: &'c str,
+    path: Vec<&'c str>,
+    matches: Vec<&'c ArgMatches<'c>>,
+    help: &'c Help<'c>,
+    config: &'c Config,
+    verbosity: i64,
+    color: ColorChoice,
+    output: OutputFormat,
+    cancellation: CancellationToken,
+    cleanups: Cleanups,
+    services: Instances<'c>,
+    /// Why the runner failed when the error alone doesn't tell, shared by the levels
+    cause: Rc<Cell<Option<Cause>>>,
+}
+
+impl<'c> Context<'c> {
+    pub(crate) fn new(
+        name: &'c str,
+        matches: &'c ArgMatches<'c>,
+        help: &'c Help<'c>,
+        config: &'c Config,
+        cancellation: CancellationToken,
+        cleanups: Cleanups,
+        services: &'c Services<'c>,
+    ) -> Self {
+        Self {
+            name,
+            path: Vec::new(),
+            matches: vec![matches],
+            help,
+            config,
+            verbosity: count(matches, "verbose") as i64 - count(matches, "quiet") as i64,
+            color: ColorChoice::resolve(None),
+            output: OutputFormat::Table,
+            cancellation,
+            cleanups,
+            services: Instances::new(services),
+            cause: Rc::default(),
+        }
+    }
+
+    /// The choice given with `--color` by the commander registering it
+    pub(crate) fn with_color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// The format given with `--output` by the commander registering it
+    pub(crate) fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub(crate) fn child<'x>(
+        &'x self,
+        name: &'x str,
+        matches: &'x ArgMatches<'x>,
+        help: &'x Help<'x>,
+    ) -> Context<'x> {
+        let mut path = self.path.clone();
+        let mut stack = self.matches.clone();
+
+        path.push(name);
+        stack.push(matches);
+
+        Context {
+            name: self.name,
+            path,
+            matches: stack,
+            help,
+            config: self.config,
+            verbosity: self.verbosity,
+            color: self.color,
+            output: self.output,
+            cancellation: self.cancellation.clone(),
+            cleanups: self.cleanups.clone(),
+            services: self.services.clone(),
+            cause: self.cause.clone(),
+        }
+    }
+
+    /// Records why the runner failed, for the commander to report it accordingly
+    pub(crate) fn fail(&self, cause: Cause) {
+        self.cause.set(Some(cause));
+    }
+
+    /// Why the runner failed, if recorded with [`fail`](#method.fail)
+    pub(crate) fn take_cause(&self) -> Option<Cause> {
+        self.cause.take()
+    }
+
+    /// The value of a global argument, which may be given at any level
+    pub(crate) fn global_value(&self, name: &str) -> Option<&'c str> {
+        deepest(self.matches[0], name)
+    }
+
+    /// Whether a global flag is given at any level
+    pub(crate) fn global_flag(&self, name: &str) -> bool {
+        count(self.matches[0], name) > 0
+    }
+
+    /// Whether `--yes` is given at any level, whichever the name of the flag
+    /// when the app defines its own
+    pub(crate) fn assumes_yes(&self) -> bool {
+        self.help
+            .root_arg("yes")
+            .is_some_and(|name| self.global_flag(name))
+    }
+
+    /// The names of all the subcommands matched, including the ones below the current command
+    pub(crate) fn matched_path(&self) -> CommandPath {
+        CommandPath::from(self.matches[0])
+    }
+
+    /// The names of the commands leading to the current one, e.g. `["show", "foo"]`
+    pub fn path(&self) -> &[&'c str] {
+        &self.path
+    }
+
+    /// The [`path`](#method.path) of the current command, e.g. to prefix log lines
+    /// with `show foo` the way the framework names commands in errors and hooks
+    pub fn command_path(&self) -> CommandPath {
+        CommandPath(
+            self.path
+                .iter()
+                .map(|segment| segment.to_string())
+                .collect(),
+        )
+    }
+
+    /// The matches of the command named `name` on the path, e.g. of `show` in the runner
+    /// of `show foo`, to read the arguments given to the commands above
+    pub fn matches_for(&self, name: &str) -> Option<&'c ArgMatches<'c>> {
+        let depth = self.path.iter().rposition(|segment| *segment == name)?;
+
+        self.matches.get(depth + 1).cloned()
+    }
+
+    /// The matches of the commands from the root down to the current one
+    pub fn matches_stack(&self) -> &[&'c ArgMatches<'c>] {
+        &self.matches
+    }
+
+    pub(crate) fn help(&self) -> &'c Help<'c> {
+        self.help
+    }
+
+    /// The service of type `V` given to [`provide`](struct.Commander.html#method.provide),
+    /// built the first time it is asked for during the invocation,
+    /// panicking when there is none
+    pub fn service<V: Any>(&self) -> Rc<V> {
+        self.services.get(self)
+    }
+
+    /// Whether `--dry-run` is given, see
+    /// [`with_dry_run`](struct.Commander.html#method.with_dry_run)
+    pub fn is_dry_run(&self) -> bool {
+        self.global_flag("dry-run")
+    }
+
+    /// The merged configuration from files and environment variables
+    pub fn config(&self) -> &'c Config {
+        self.config
+    }
+
+    /// The app's configuration directory, e.g. `~/.config/<name>`,
+    /// `~/Library/Application Support/<name>` on macOS or `%APPDATA%\<name>` on Windows,
+    /// named after the [`config`](struct.Commander.html#method.config) name or else the app,
+    /// `None` when the home directory is unknown
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(self.name))
+    }
+
+    /// The app's data directory, e.g. `~/.local/share/<name>`,
+    /// `~/Library/Application Support/<name>` on macOS or `%APPDATA%\<name>` on Windows
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(self.name))
+    }
+
+    /// The app's cache directory, e.g. `~/.cache/<name>`,
+    /// `~/Library/Caches/<name>` on macOS or `%LOCALAPPDATA%\<name>` on Windows
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join(self.name))
+    }
+
+    /// The number of `-v` minus the number of `-q` given anywhere on the command line,
+    /// see [`with_verbosity`](struct.Commander.html#method.with_verbosity)
+    pub fn verbosity(&self) -> i64 {
+        self.verbosity
+    }
+
+    /// The log level for the [`verbosity`](#method.verbosity), from `Warn` by default
+    /// up to `Trace` with `-vvv` and down to `Off` with `-qq`
+    #[cfg(feature = "log")]
+    pub fn log_level(&self) -> log::LevelFilter {
+        log_level(self.verbosity)
+    }
+
+    /// The resolved `--color` choice, see [`with_color`](struct.Commander.html#method.with_color)
+    pub fn color(&self) -> ColorChoice {
+        self.color
+    }
+
+    /// Whether the standard input is a terminal, i.e. nothing is piped in,
+    /// never for [`run_capture`](struct.Commander.html#method.run_capture)
+    pub fn stdin_is_tty(&self) -> bool {
+        !capture::is_captured() && std::io::stdin().is_terminal()
+    }
+
+    /// Whether the standard output is a terminal, e.g. to decide on colors or prompts,
+    /// never when it's captured
+    pub fn stdout_is_tty(&self) -> bool {
+        !capture::is_captured() && std::io::stdout().is_terminal()
+    }
+
+    /// Whether the standard error is a terminal, e.g. to decide on progress bars,
+    /// never when it's captured
+    pub fn stderr_is_tty(&self) -> bool {
+        !capture::is_captured() && std::io::stderr().is_terminal()
+    }
+
+    /// Reads everything piped to the standard input, `None` when it is a terminal,
+    /// or the input given to [`run_capture_with_stdin`](struct.Commander.html#method.run_capture_with_stdin)
+    pub fn read_stdin(&self) -> StdResult<Option<String>, ClapError> {
+        let failed = |err: &dyn std::fmt::Display| {
+            ClapError::with_description(
+                &format!("Failed to read the standard input: {}", err),
+                ClapErrorKind::Io,
+            )
+        };
+
+        if let Some(input) = capture::take_stdin() {
+            return String::from_utf8(input)
+                .map(Some)
+                .map_err(|err| failed(&err));
+        }
+
+        if self.stdin_is_tty() {
+            return Ok(None);
+        }
+
+        let mut input = String::new();
+
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|err| failed(&err))?;
+
+        Ok(Some(input))
+    }
+
+    /// Opens `$VISUAL` or `$EDITOR` on a temporary file containing `initial`,
+    /// e.g. for `config edit`, and returns its contents once the editor exits
+    pub fn edit(&self, initial: &str) -> StdResult<String, ClapError> {
+        editor::edit(initial)
+    }
+
+    /// Asks for a secret like `ctx.prompt_secret("Keystore password")` on the terminal
+    /// without echoing it, so it stays out of the shell history, logs and audit trail
+    #[cfg(feature = "rpassword")]
+    pub fn prompt_secret(&self, label: &str) -> StdResult<String, ClapError> {
+        prompt::secret(label)
+    }
+
+    /// The selected `--output` format, see [`with_output`](struct.Commander.html#method.with_output)
+    pub fn output_format(&self) -> OutputFormat {
+        self.output
+    }
+
+    /// Cancelled on Ctrl-C when [`on_interrupt`](struct.Commander.html#method.on_interrupt)
+    /// is set, for runners to clean up instead of being killed mid-write
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Registers a cleanup to run once the runner returns, even by panicking, or when
+    /// a signal stops the process after [`on_interrupt`](struct.Commander.html#method.on_interrupt),
+    /// latest registered first
+    pub fn defer(&self, cleanup: impl FnOnce() + Send + 'static) {
+        self.cleanups.defer(Box::new(cleanup));
+    }
+
+    /// Creates a [`Table`](struct.Table.html) fitting the terminal, the one used by `--output table`
+    pub fn table<S: Into<String>>(&self, headers: Vec<S>) -> Table {
+        Table::new(headers).fit_terminal()
+    }
+
+    /// The standard output, captured by
+    /// [`run_capture`](struct.Commander.html#method.run_capture) when written
+    /// from the thread running the runner
+    pub fn stdout(&self) -> Writer {
+        capture::stdout()
+    }
+
+    /// The standard error, captured by
+    /// [`run_capture`](struct.Commander.html#method.run_capture) when written
+    /// from the thread running the runner
+    pub fn stderr(&self) -> Writer {
+        capture::stderr()
+    }
+
+    /// Prints a value to the standard output in the selected format
+    #[cfg(feature = "serde")]
+    pub fn emit(&self, value: &impl serde::Serialize) -> Result {
+        let _ = write!(self.stdout(), "{}", self.output.render(value)?);
+        Ok(())
+    }
+
+    /// Gets the value of an argument, looking in order at:
+    ///
+    /// * the command line, from the current command up to the root
+    /// * the environment, then the config files, from the key scoped to the current
+    ///   command path (e.g. `show.foo.env`) up to the unscoped one (e.g. `env`)
+    /// * the default value of the argument
+    pub fn value_of(&self, key: &str) -> Option<String> {
+        self.value_of_os(key)
+            .map(|value| value.to_string_lossy().into_owned())
+    }
+
+    /// Like [`value_of`](#method.value_of), without losing values from the command line
+    /// which aren't valid UTF-8, see
+    /// [`allow_invalid_utf8`](struct.Commander.html#method.allow_invalid_utf8)
+    pub fn value_of_os(&self, key: &str) -> Option<OsString> {
+        for matches in self.matches.iter().rev() {
+            if matches.occurrences_of(key) > 0 {
+                return Some(
+                    matches
+                        .value_of_os(key)
+                        .unwrap_or("true".as_ref())
+                        .to_owned(),
+                );
+            }
+        }
+
+        let scoped: Vec<String> = (0..=self.path.len())
+            .rev()
+            .map(|depth| {
+                let mut scoped = self.path[..depth].join(".");
+
+                if !scoped.is_empty() {
+                    scoped.push('.');
+                }
+
+                scoped.push_str(key);
+                scoped
+            })
+            .collect();
+
+        let from_config = scoped
+            .iter()
+            .find_map(|key| self.config.get_env(key))
+            .or_else(|| scoped.iter().find_map(|key| self.config.get_file(key)));
+
+        if let Some(value) = from_config {
+            return Some(value.into());
+        }
+
+        self.matches
+            .iter()
+            .rev()
+            .find_map(|matches| matches.value_of_os(key))
+            .map(|value| value.to_owned())
+    }
+}
+
+// Global arguments may be given at any level, `clap` copies them down to subcommands
+fn count(matches: &ArgMatches<'_>, name: &str) -> u64 {
+    let inner = matches.subcommand().1.map_or(0, |sub| count(sub, name));
+    inner.max(matches.occurrences_of(name))
+}
+
+fn deepest<'m>(matches: &'m ArgMatches<'_>, name: &str) -> Option<&'m str> {
+    let inner = matches.subcommand().1.and_then(|sub| deepest(sub, name));
+    inner.or_else(|| matches.value_of(name))
+}
+
+#[cfg(feature = "log")]
+pub(crate) fn log_level(verbosity: i64) -> log::LevelFilter {
+    match verbosity {
+        i64::MIN..=-2 => log::LevelFilter::Off,
+        -1 => log::LevelFilter::Error,
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    }
+}
@@ -0,0 +1,74 @@
+use std::any::Any;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use CommandPath;
+
+/// What [`on_crash`](struct.Commander.html#method.on_crash) hooks receive
+/// when parsing fails, a runner returns an error or a runner panics
+#[derive(Debug)]
+pub struct CrashReport<'r> {
+    error: &'r ClapError,
+    path: &'r CommandPath,
+    panicked: bool,
+    version: Option<&'r str>,
+}
+
+impl<'r> CrashReport<'r> {
+    pub(crate) fn new(
+        error: &'r ClapError,
+        path: &'r CommandPath,
+        panicked: bool,
+        version: Option<&'r str>,
+    ) -> Self {
+        Self {
+            error,
+            path,
+            panicked,
+            version,
+        }
+    }
+
+    /// The error, whose message is the one of the panic if the runner panicked
+    pub fn error(&self) -> &'r ClapError {
+        self.error
+    }
+
+    /// The command which failed, empty when parsing failed before reaching any
+    pub fn path(&self) -> &'r CommandPath {
+        self.path
+    }
+
+    pub fn panicked(&self) -> bool {
+        self.panicked
+    }
+
+    /// The version of the app, if set with [`version`](struct.Commander.html#method.version)
+    pub fn version(&self) -> Option<&'r str> {
+        self.version
+    }
+
+    pub fn os(&self) -> &'static str {
+        std::env::consts::OS
+    }
+
+    pub fn arch(&self) -> &'static str {
+        std::env::consts::ARCH
+    }
+}
+
+/// Turns the payload of a panic into an error
+pub(crate) fn panic_error(payload: &(dyn Any + Send)) -> ClapError {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "Box<dyn Any>"
+    };
+
+    ClapError::with_description(
+        &format!("The command panicked: {}", message),
+        ClapErrorKind::Format,
+    )
+}
@@ -0,0 +1,50 @@
+//! The user's directories, following the conventions of each platform,
+//! see `Context::config_dir`
+
+use std::env;
+use std::path::PathBuf;
+
+/// Where configuration lives, e.g. `~/.config`
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    user_dir(
+        "APPDATA",
+        "XDG_CONFIG_HOME",
+        "Library/Application Support",
+        ".config",
+    )
+}
+
+/// Where data is kept, e.g. `~/.local/share`
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    user_dir(
+        "APPDATA",
+        "XDG_DATA_HOME",
+        "Library/Application Support",
+        ".local/share",
+    )
+}
+
+/// Where data which may be deleted is kept, e.g. `~/.cache`
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    user_dir("LOCALAPPDATA", "XDG_CACHE_HOME", "Library/Caches", ".cache")
+}
+
+/// The directory from `windows` on Windows, otherwise from `xdg` when set
+/// or else below the home directory
+fn user_dir(windows: &str, xdg: &str, macos: &str, unix: &str) -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os(windows).map(PathBuf::from);
+    }
+
+    if let Some(dir) = env::var_os(xdg).filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+
+    let below_home = if cfg!(target_os = "macos") {
+        macos
+    } else {
+        unix
+    };
+
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(below_home))
+}
@@ -0,0 +1,278 @@
+//! Reference docs of any command in Markdown, man or JSON, see `Commander::with_docs`
+
+use std::fmt::Write;
+
+use clap::{
+    App, AppSettings, Arg, ArgMatches, ArgSettings, Error as ClapError, ErrorKind as ClapErrorKind,
+    SubCommand,
+};
+
+use report;
+
+/// The name of the subcommand
+pub(crate) const NAME: &str = "docs";
+
+/// The `docs [PATH]... [--format <FORMAT>]` subcommand
+pub(crate) fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Prints the reference docs of a command")
+        .arg(
+            Arg::with_name("path")
+                .multiple(true)
+                .help("The names of the subcommands leading to the command"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["md", "man", "json"])
+                .default_value("md"),
+        )
+}
+
+/// The docs of the command at the path given in the matches, below `app`
+pub(crate) fn render(app: &App<'_, '_>, matches: &ArgMatches<'_>) -> Result<String, ClapError> {
+    let mut app = app;
+    let mut title = vec![app.p.meta.bin_name.as_deref().unwrap_or(&app.p.meta.name)];
+
+    for name in matches.values_of("path").into_iter().flatten() {
+        app = app
+            .p
+            .subcommands
+            .iter()
+            .find(|subcmd| subcmd.p.meta.name == name)
+            .ok_or_else(|| {
+                ClapError::with_description(
+                    &format!("No subcommand named '{}' in `{}`", name, title.join(" ")),
+                    ClapErrorKind::InvalidSubcommand,
+                )
+            })?;
+        title.push(name);
+    }
+
+    let page = Page::new(title.join(" "), app);
+
+    Ok(match matches.value_of("format") {
+        Some("man") => page.man(),
+        Some("json") => page.json(),
+        _ => page.markdown(),
+    })
+}
+
+struct Page<'p> {
+    title: String,
+    about: Option<&'p str>,
+    /// The usages of the arguments, e.g. `-p, --port <PORT>`, with their help
+    args: Vec<(String, Option<&'p str>)>,
+    subcommands: Vec<(String, Option<&'p str>)>,
+}
+
+impl<'p> Page<'p> {
+    fn new(title: String, app: &'p App<'_, '_>) -> Self {
+        fn switches(short: Option<char>, long: Option<&str>) -> String {
+            let short = short.map(|short| format!("-{}", short));
+            let long = long.map(|long| format!("--{}", long));
+
+            short.into_iter().chain(long).collect::<Vec<_>>().join(", ")
+        }
+
+        let p = &app.p;
+        let flags = p
+            .flags
+            .iter()
+            .filter(|flag| !flag.b.is_set(ArgSettings::Hidden))
+            .map(|flag| (switches(flag.s.short, flag.s.long), flag.b.help));
+        let opts = p
+            .opts
+            .iter()
+            .filter(|opt| !opt.b.is_set(ArgSettings::Hidden))
+            .map(|opt| {
+                let value = match opt
+                    .v
+                    .val_names
+                    .as_ref()
+                    .and_then(|names| names.values().next())
+                {
+                    Some(value) => value.to_string(),
+                    None => opt.b.name.to_uppercase(),
+                };
+
+                (
+                    format!("{} <{}>", switches(opt.s.short, opt.s.long), value),
+                    opt.b.help,
+                )
+            });
+        let positionals = p
+            .positionals
+            .values()
+            .filter(|pos| !pos.b.is_set(ArgSettings::Hidden))
+            .map(|pos| {
+                let dots = if pos.b.is_set(ArgSettings::Multiple) {
+                    "..."
+                } else {
+                    ""
+                };
+
+                (format!("<{}>{}", pos.b.name, dots), pos.b.help)
+            });
+
+        Self {
+            title,
+            about: p.meta.about,
+            args: positionals.chain(flags).chain(opts).collect(),
+            subcommands: p
+                .subcommands
+                .iter()
+                .filter(|subcmd| !subcmd.p.is_set(AppSettings::Hidden))
+                .map(|subcmd| (subcmd.p.meta.name.clone(), subcmd.p.meta.about))
+                .collect(),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = format!("# {}\n", self.title);
+
+        if let Some(about) = self.about {
+            let _ = write!(out, "\n{}\n", about);
+        }
+
+        if !self.args.is_empty() {
+            out.push_str("\n## Arguments\n\n");
+
+            for (usage, help) in &self.args {
+                let _ = writeln!(out, "- `{}`{}", usage, markdown_help(*help));
+            }
+        }
+
+        if !self.subcommands.is_empty() {
+            out.push_str("\n## Subcommands\n\n");
+
+            for (name, about) in &self.subcommands {
+                let _ = writeln!(out, "- `{}`{}", name, markdown_help(*about));
+            }
+        }
+
+        out
+    }
+
+    fn man(&self) -> String {
+        let mut out = format!(
+            ".TH {} 1\n.SH NAME\n{}",
+            roff(&self.title.to_uppercase().replace(' ', "-")),
+            roff(&self.title.replace(' ', "-"))
+        );
+
+        if let Some(about) = self.about {
+            let _ = write!(out, " \\- {}", roff(about));
+        }
+
+        out.push('\n');
+
+        for (section, entries) in [
+            ("ARGUMENTS", &self.args),
+            ("SUBCOMMANDS", &self.subcommands),
+        ] {
+            if entries.is_empty() {
+                continue;
+            }
+
+            let _ = writeln!(out, ".SH {}", section);
+
+            for (name, help) in entries {
+                let _ = writeln!(out, ".TP\n\\fB{}\\fR", roff(name));
+
+                if let Some(help) = help {
+                    let _ = writeln!(out, "{}", roff(help));
+                }
+            }
+        }
+
+        out
+    }
+
+    fn json(&self) -> String {
+        fn entries(key: &str, entries: &[(String, Option<&str>)]) -> String {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|(name, help)| {
+                    format!(
+                        "{{\"{}\":{},\"help\":{}}}",
+                        key,
+                        report::string(name),
+                        help.map_or("null".to_owned(), report::string)
+                    )
+                })
+                .collect();
+
+            entries.join(",")
+        }
+
+        format!(
+            "{{\"command\":{},\"about\":{},\"args\":[{}],\"subcommands\":[{}]}}\n",
+            report::string(&self.title),
+            self.about.map_or("null".to_owned(), report::string),
+            entries("usage", &self.args),
+            entries("name", &self.subcommands)
+        )
+    }
+}
+
+fn markdown_help(help: Option<&str>) -> String {
+    help.map(|help| format!(": {}", help)).unwrap_or_default()
+}
+
+/// Escapes the characters roff interprets
+fn roff(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('-', "\\-")
+}
+
+#[test]
+fn rendered() {
+    let app = App::new("program").subcommand(
+        SubCommand::with_name("show")
+            .about("Shows things")
+            .arg(
+                Arg::with_name("port")
+                    .short("p")
+                    .long("port")
+                    .takes_value(true)
+                    .help("The port"),
+            )
+            .arg(Arg::with_name("all").long("all"))
+            .subcommand(SubCommand::with_name("foo").about("Shows foo")),
+    );
+    let docs = subcommand();
+    let render_args = |args: &[&str]| {
+        let matches = docs
+            .clone()
+            .get_matches_from(std::iter::once("docs").chain(args.iter().cloned()));
+        render(&app, &matches)
+    };
+
+    assert_eq!(
+        render_args(&["show"]).unwrap(),
+        "# program show
+
+Shows things
+
+## Arguments
+
+- `--all`
+- `-p, --port <PORT>`: The port
+
+## Subcommands
+
+- `foo`: Shows foo
+"
+    );
+    assert_eq!(
+        render_args(&["show", "foo", "--format", "json"]).unwrap(),
+        "{\"command\":\"program show foo\",\"about\":\"Shows foo\",\"args\":[],\"subcommands\":[]}\n"
+    );
+    assert!(render_args(&["show", "--format", "man"])
+        .unwrap()
+        .starts_with(".TH PROGRAM\\-SHOW 1\n.SH NAME\nprogram\\-show \\- Shows things\n"));
+
+    let err = render_args(&["missing"]).unwrap_err();
+    assert_eq!(err.kind, ClapErrorKind::InvalidSubcommand);
+}
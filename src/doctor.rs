@@ -0,0 +1,170 @@
+//! Diagnostics of the environment run by the `doctor` subcommand,
+//! see `Commander::with_doctor`
+
+use std::fmt::{self, Write};
+use std::io::Write as IoWrite;
+use std::rc::Rc;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use capture;
+use Context;
+use Result;
+
+/// The name of the subcommand
+pub(crate) const NAME: &str = "doctor";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The outcome of a [`Check`](struct.Check.html), with a suggested fix for the problems
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnosis {
+    status: Status,
+    message: Option<String>,
+    fix: Option<String>,
+}
+
+impl Diagnosis {
+    pub fn pass() -> Self {
+        Self::new(Status::Pass, None)
+    }
+
+    /// A problem which doesn't stop the app from working, e.g. a deprecated setting
+    pub fn warn(message: impl Into<String>) -> Self {
+        Self::new(Status::Warn, Some(message.into()))
+    }
+
+    /// A problem which makes `doctor` fail, e.g. a missing tool
+    pub fn fail(message: impl Into<String>) -> Self {
+        Self::new(Status::Fail, Some(message.into()))
+    }
+
+    /// How to solve the problem, e.g. ``"Install it with `apt install git`"``
+    pub fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+
+    fn new(status: Status, message: Option<String>) -> Self {
+        Self {
+            status,
+            message,
+            fix: None,
+        }
+    }
+}
+
+/// A named check run by the `doctor` subcommand, e.g. whether a tool is installed
+#[derive(Clone)]
+pub struct Check<'a> {
+    name: &'a str,
+    run: Rc<dyn Fn(&Context<'_>) -> Diagnosis + 'a>,
+}
+
+impl<'a> Check<'a> {
+    pub fn new(name: impl Into<&'a str>, run: impl Fn(&Context<'_>) -> Diagnosis + 'a) -> Self {
+        Self {
+            name: name.into(),
+            run: Rc::new(run),
+        }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+}
+
+impl<'a> fmt::Debug for Check<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Check")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Runs every check, printing one line per check, and fails when any does
+pub(crate) fn run(checks: &[Check<'_>], ctx: &Context<'_>) -> Result {
+    let diagnoses: Vec<_> = checks
+        .iter()
+        .map(|check| (check.name, (check.run)(ctx)))
+        .collect();
+
+    let _ = write!(capture::stdout(), "{}", render(&diagnoses));
+
+    let failed = count(&diagnoses, Status::Fail);
+
+    if failed > 0 {
+        return Err(ClapError::with_description(
+            &format!("{} of {} checks failed", failed, diagnoses.len()),
+            ClapErrorKind::Io,
+        ));
+    }
+
+    Ok(())
+}
+
+/// The results, e.g. `[warn] config: No config file` followed by the fix and a summary
+fn render(diagnoses: &[(&str, Diagnosis)]) -> String {
+    let mut out = String::new();
+
+    for (name, diagnosis) in diagnoses {
+        let tag = match diagnosis.status {
+            Status::Pass => " ok ",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+
+        let _ = match &diagnosis.message {
+            Some(message) => writeln!(out, "[{}] {}: {}", tag, name, message),
+            None => writeln!(out, "[{}] {}", tag, name),
+        };
+
+        if let Some(fix) = &diagnosis.fix {
+            let _ = writeln!(out, "       fix: {}", fix);
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "\n{} passed, {} with warnings, {} failed",
+        count(diagnoses, Status::Pass),
+        count(diagnoses, Status::Warn),
+        count(diagnoses, Status::Fail)
+    );
+    out
+}
+
+fn count(diagnoses: &[(&str, Diagnosis)], status: Status) -> usize {
+    diagnoses
+        .iter()
+        .filter(|(_, diagnosis)| diagnosis.status == status)
+        .count()
+}
+
+#[test]
+fn rendered() {
+    let diagnoses = [
+        ("git", Diagnosis::pass()),
+        (
+            "config",
+            Diagnosis::warn("No config file").fix("Run `program init`"),
+        ),
+        ("network", Diagnosis::fail("Cannot reach the API")),
+    ];
+
+    assert_eq!(
+        render(&diagnoses),
+        "[ ok ] git
+[warn] config: No config file
+       fix: Run `program init`
+[FAIL] network: Cannot reach the API
+
+1 passed, 1 with warnings, 1 failed
+"
+    );
+}
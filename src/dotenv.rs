@@ -0,0 +1,117 @@
+//! `.env` files, loaded into the environment before arguments are parsed
+
+use std::path::Path;
+use std::result::Result as StdResult;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// Sets the variables of a `.env` file which aren't already set,
+/// where a missing file is only an error when `required`
+pub(crate) fn load(path: &Path, required: bool) -> StdResult<(), ClapError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref err) if !required && err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(ClapError::with_description(
+                &format!("Failed to read {}: {}", path.display(), err),
+                ClapErrorKind::Io,
+            ))
+        }
+    };
+
+    let vars = parse(&contents).map_err(|err| {
+        ClapError::with_description(
+            &format!("Invalid env file {}: {}", path.display(), err),
+            ClapErrorKind::InvalidValue,
+        )
+    })?;
+
+    for (name, value) in vars {
+        if std::env::var_os(&name).is_none() {
+            std::env::set_var(name, value);
+        }
+    }
+
+    Ok(())
+}
+
+fn parse(contents: &str) -> StdResult<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => (name.trim(), value.trim()),
+            _ => return Err(format!("line {}: expected `NAME=value`", index + 1)),
+        };
+
+        let value = if let Some(quoted) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+        {
+            unescape(quoted)
+        } else if let Some(quoted) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+            quoted.to_owned()
+        } else {
+            // Unquoted values may end with a comment
+            match value.find(" #") {
+                Some(index) => value[..index].trim_end().to_owned(),
+                None => value.to_owned(),
+            }
+        };
+
+        vars.push((name.to_owned(), value));
+    }
+
+    Ok(vars)
+}
+
+/// Unescapes `\n`, `\"` and `\\` in one pass, leaving other backslashes as they are
+fn unescape(quoted: &str) -> String {
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some(escaped @ '"') | Some(escaped @ '\\') => value.push(escaped),
+            Some(other) => {
+                value.push('\\');
+                value.push(other);
+            }
+            None => value.push('\\'),
+        }
+    }
+
+    value
+}
+
+#[test]
+fn parse_vars() {
+    let vars =
+        parse("# comment\nexport A=1\nB = \"two\\nlines\"\nC='$raw'\nD=value # comment\nE=\nF=\"a\\\\nb\\\"c\\t\"\n")
+            .unwrap();
+
+    assert_eq!(
+        vars,
+        [
+            ("A".to_owned(), "1".to_owned()),
+            ("B".to_owned(), "two\nlines".to_owned()),
+            ("C".to_owned(), "$raw".to_owned()),
+            ("D".to_owned(), "value".to_owned()),
+            ("E".to_owned(), "".to_owned()),
+            ("F".to_owned(), "a\\nb\"c\\t".to_owned()),
+        ]
+    );
+    assert!(parse("nope").is_err());
+}
@@ -0,0 +1,101 @@
+//! Editing text in the editor of the user, see `Context::edit`
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::process;
+use std::result::Result as StdResult;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use alias;
+
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// `$VISUAL`, then `$EDITOR`, like git does
+fn editor() -> OsString {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .find(|editor| !editor.is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.into())
+}
+
+/// Opens the editor of the user on a temporary file with `initial` in it
+pub(crate) fn edit(initial: &str) -> StdResult<String, ClapError> {
+    edit_with(&editor().to_string_lossy(), initial)
+}
+
+/// Runs `editor`, which may have arguments like `code --wait`, on a temporary file
+fn edit_with(editor: &str, initial: &str) -> StdResult<String, ClapError> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos());
+    let path = std::env::temp_dir().join(format!("edit-{}-{}.txt", std::process::id(), nanos));
+
+    let io_error = |action: &str, path: &Path, err: std::io::Error| {
+        ClapError::with_description(
+            &format!("Failed to {} {}: {}", action, path.display(), err),
+            ClapErrorKind::Io,
+        )
+    };
+
+    std::fs::write(&path, initial).map_err(|err| io_error("write", &path, err))?;
+
+    let result = run(editor, &path)
+        .and_then(|()| std::fs::read_to_string(&path).map_err(|err| io_error("read", &path, err)));
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+fn run(editor: &str, path: &Path) -> StdResult<(), ClapError> {
+    let words = alias::split(editor).map_err(|err| {
+        ClapError::with_description(
+            &format!("Invalid editor `{}`: {}", editor, err),
+            ClapErrorKind::InvalidValue,
+        )
+    })?;
+
+    let (program, args) = match words.split_first() {
+        Some(words) => words,
+        None => {
+            return Err(ClapError::with_description(
+                "No editor is set",
+                ClapErrorKind::InvalidValue,
+            ))
+        }
+    };
+
+    let status = process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .status()
+        .map_err(|err| {
+            ClapError::with_description(
+                &format!("Failed to run the editor `{}`: {}", editor, err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ClapError::with_description(
+            &format!("The editor `{}` failed with {}", editor, status),
+            ClapErrorKind::Io,
+        ))
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn edit_with_arguments() {
+    let edited = edit_with(r#"sh -c 'printf " world" >> "$0"'"#, "hello").unwrap();
+
+    assert_eq!(edited, "hello world");
+    assert!(edit_with("false", "hello").is_err());
+}
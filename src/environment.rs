@@ -0,0 +1,89 @@
+//! What [`Command::enabled`](../struct.Command.html#method.enabled) checks before parsing:
+//! the environment variables and the configuration of the top commander
+
+use std::cell::{OnceCell, RefCell};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use config::{self, Config};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<Environment>>> = const { RefCell::new(None) };
+}
+
+/// The environment a commander runs in, available before the arguments are parsed
+#[derive(Debug, Default)]
+pub struct Environment {
+    name: Option<String>,
+    files: Vec<PathBuf>,
+    env_prefix: Option<String>,
+    config: OnceCell<Config>,
+}
+
+impl Environment {
+    pub(crate) fn new(name: Option<&str>, files: &[PathBuf], env_prefix: Option<&str>) -> Self {
+        Self {
+            name: name.map(str::to_owned),
+            files: files.to_vec(),
+            env_prefix: env_prefix.map(str::to_owned),
+            config: OnceCell::new(),
+        }
+    }
+
+    /// The environment of a run, whose configuration is already loaded
+    pub(crate) fn loaded(config: Config) -> Self {
+        Self {
+            config: OnceCell::from(config),
+            ..Self::default()
+        }
+    }
+
+    /// The value of an environment variable, `None` when it is unset or not unicode
+    pub fn var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    /// The configuration of the commander, loaded on first use and empty when it fails to
+    pub fn config(&self) -> &Config {
+        self.config.get_or_init(|| {
+            let mut config = config::load(self.name.as_deref(), &self.files).unwrap_or_default();
+
+            if let Some(prefix) = &self.env_prefix {
+                config.set_env_prefix(prefix.clone());
+            }
+
+            config
+        })
+    }
+}
+
+/// Makes an environment the current one of this thread until dropped, unless one already is
+pub(crate) struct Scope(bool);
+
+impl Scope {
+    pub(crate) fn enter(environment: impl FnOnce() -> Environment) -> Self {
+        Scope(CURRENT.with(|current| {
+            let mut current = current.borrow_mut();
+            let entered = current.is_none();
+
+            if entered {
+                *current = Some(Rc::new(environment()));
+            }
+
+            entered
+        }))
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if self.0 {
+            CURRENT.with(|current| current.borrow_mut().take());
+        }
+    }
+}
+
+/// The current environment of this thread, an empty one outside of a commander
+pub(crate) fn current() -> Rc<Environment> {
+    CURRENT.with(|current| current.borrow().clone().unwrap_or_default())
+}
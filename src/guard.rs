@@ -0,0 +1,32 @@
+//! Refusing to run commands, see `Command::guard`
+
+use std::fmt;
+
+/// Why a [`guard`](struct.Command.html#method.guard) refused to run a command,
+/// e.g. `DenyReason::from("only admins can drop databases")`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenyReason(String);
+
+impl DenyReason {
+    pub fn new(reason: impl Into<String>) -> Self {
+        DenyReason(reason.into())
+    }
+}
+
+impl fmt::Display for DenyReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for DenyReason {
+    fn from(reason: &str) -> Self {
+        DenyReason::new(reason)
+    }
+}
+
+impl From<String> for DenyReason {
+    fn from(reason: String) -> Self {
+        DenyReason(reason)
+    }
+}
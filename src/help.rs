@@ -0,0 +1,185 @@
+//! The help of every command, written only when it is shown
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use clap::App;
+
+use subcommand_paths;
+
+/// The help of a command, written the first time it is shown, e.g. with `println!("{}", help)`,
+/// see [`runner_with_help`](struct.Command.html#method.runner_with_help)
+#[derive(Clone)]
+pub struct Help<'a> {
+    root: Rc<dyn Render + 'a>,
+    /// The names of the subcommands leading to the command
+    path: Vec<String>,
+}
+
+impl<'a> Help<'a> {
+    /// `renamed` is the name given to a subcommand and the one it has in the app
+    pub(crate) fn new(app: App<'a, 'a>, renamed: Option<(String, String)>) -> Self {
+        Self {
+            root: Rc::new(HelpRoot {
+                app,
+                renamed,
+                cache: RefCell::new(HashMap::new()),
+            }),
+            path: Vec::new(),
+        }
+    }
+
+    /// The help of a subcommand of the command
+    pub fn cmd(&self, name: &str) -> Option<Self> {
+        let mut path = self.path.clone();
+
+        path.push(name.to_owned());
+
+        if self.root.has(&path) {
+            Some(Self {
+                root: self.root.clone(),
+                path,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn data(&self) -> Rc<[u8]> {
+        self.root.write(&self.path)
+    }
+
+    /// The name of the root app
+    pub(crate) fn app_name(&self) -> &str {
+        self.root.name()
+    }
+
+    /// The paths of the subcommands named `name` below the root, e.g. `show foo` for `foo`,
+    /// among the ones built
+    pub(crate) fn paths_to(&self, name: &str) -> Vec<String> {
+        self.root.paths_to(name)
+    }
+
+    /// The name of the flag or option of the root given with `--<long>`
+    pub(crate) fn root_arg(&self, long: &str) -> Option<&str> {
+        self.root.arg(long)
+    }
+}
+
+impl<'a> fmt::Display for Help<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.data()))
+    }
+}
+
+impl<'a> fmt::Debug for Help<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Help")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Hides the lifetimes of the app, which `clap` makes invariant,
+/// from the contexts borrowing the help
+trait Render {
+    fn name(&self) -> &str;
+    fn has(&self, path: &[String]) -> bool;
+    fn write(&self, path: &[String]) -> Rc<[u8]>;
+    fn paths_to(&self, name: &str) -> Vec<String>;
+    fn arg(&self, long: &str) -> Option<&str>;
+}
+
+struct HelpRoot<'a> {
+    app: App<'a, 'a>,
+    renamed: Option<(String, String)>,
+    cache: RefCell<HashMap<Vec<String>, Rc<[u8]>>>,
+}
+
+impl<'a> HelpRoot<'a> {
+    fn find(&self, path: &[String]) -> Option<&App<'a, 'a>> {
+        let mut app = &self.app;
+
+        for (depth, name) in path.iter().enumerate() {
+            let name = match &self.renamed {
+                Some((to, from)) if depth == 0 && name == to => from,
+                _ => name,
+            };
+
+            app = app
+                .p
+                .subcommands
+                .iter()
+                .find(|subcmd| subcmd.p.meta.name == *name)?;
+        }
+
+        Some(app)
+    }
+}
+
+impl<'a> Render for HelpRoot<'a> {
+    fn name(&self) -> &str {
+        &self.app.p.meta.name
+    }
+
+    fn has(&self, path: &[String]) -> bool {
+        self.find(path).is_some()
+    }
+
+    fn write(&self, path: &[String]) -> Rc<[u8]> {
+        if let Some(data) = self.cache.borrow().get(path) {
+            return data.clone();
+        }
+
+        let mut data = Vec::new();
+
+        if let Some(app) = self.find(path) {
+            app.write_help(&mut data).unwrap();
+        }
+
+        let data: Rc<[u8]> = data.into();
+
+        self.cache.borrow_mut().insert(path.to_vec(), data.clone());
+        data
+    }
+
+    fn paths_to(&self, name: &str) -> Vec<String> {
+        let mut found = Vec::new();
+
+        subcommand_paths(&self.app, name, &mut Vec::new(), &mut found);
+        found
+    }
+
+    fn arg(&self, long: &str) -> Option<&str> {
+        let flags = self
+            .app
+            .p
+            .flags
+            .iter()
+            .map(|flag| (flag.b.name, flag.s.long));
+        let opts = self.app.p.opts.iter().map(|opt| (opt.b.name, opt.s.long));
+
+        flags
+            .chain(opts)
+            .find(|&(_, arg)| arg == Some(long))
+            .map(|(name, _)| name)
+    }
+}
+
+#[test]
+fn written_once() {
+    use clap::SubCommand;
+
+    let app = App::new("program").subcommand(SubCommand::with_name("show").about("Shows things"));
+    let help = Help::new(app, Some(("view".to_owned(), "show".to_owned())));
+
+    assert!(help.cmd("missing").is_none());
+
+    let view = help.cmd("view").unwrap();
+    let data = view.data();
+
+    assert!(String::from_utf8_lossy(&data).contains("Shows things"));
+    assert!(Rc::ptr_eq(&data, &help.cmd("view").unwrap().data()));
+}
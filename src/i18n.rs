@@ -0,0 +1,172 @@
+//! Translations of help and error text, see `Commander::translations`
+
+use std::collections::HashMap;
+use std::env;
+
+use clap::App;
+
+use report::strip_ansi;
+
+/// The headers of the sections of help and errors, as written by `clap`
+const HEADERS: &[&str] = &["USAGE:", "FLAGS:", "OPTIONS:", "ARGS:", "SUBCOMMANDS:"];
+
+/// Catalogs of translations gettext-style, keyed by the English text, e.g. `Shows things`
+/// or `USAGE:`, which is kept when the locale has no translation for it
+#[derive(Clone, Debug, Default)]
+pub struct Translations {
+    locale: Option<String>,
+    catalogs: HashMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Translates `english` for `locale`, e.g. `fr` or `pt_BR`
+    pub fn add(
+        mut self,
+        locale: impl Into<String>,
+        english: impl Into<String>,
+        translated: impl Into<String>,
+    ) -> Self {
+        self.catalogs
+            .entry(locale.into())
+            .or_default()
+            .insert(english.into(), translated.into());
+        self
+    }
+
+    /// Adds the translations of a gettext `.po` catalog for `locale`,
+    /// reading its single-line `msgid` and `msgstr` entries
+    pub fn po(mut self, locale: impl Into<String>, contents: &str) -> Self {
+        let catalog = self.catalogs.entry(locale.into()).or_default();
+        let mut msgid = None;
+
+        for line in contents.lines().map(str::trim) {
+            if let Some(id) = line.strip_prefix("msgid ") {
+                msgid = po_string(id);
+            } else if let Some(text) = line.strip_prefix("msgstr ") {
+                match (msgid.take(), po_string(text)) {
+                    (Some(id), Some(text)) if !id.is_empty() && !text.is_empty() => {
+                        catalog.insert(id, text);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Uses `locale` instead of the one of `LC_ALL`, `LC_MESSAGES` or `LANG`
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// The translation of `english` for the locale, `english` itself when there's none
+    pub fn get<'t>(&'t self, english: &'t str) -> &'t str {
+        self.catalog()
+            .and_then(|catalog| catalog.get(english))
+            .map_or(english, String::as_str)
+    }
+
+    /// The catalog of the locale, e.g. of `pt_BR` then `pt` for `pt_BR.UTF-8`
+    fn catalog(&self) -> Option<&HashMap<String, String>> {
+        let locale = self.locale.clone().or_else(|| {
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .filter_map(|var| env::var(var).ok())
+                .find(|locale| !locale.is_empty())
+        })?;
+        let locale = locale.split(['.', '@']).next().unwrap_or_default();
+        let language = locale.split(['_', '-']).next().unwrap_or_default();
+
+        self.catalogs
+            .get(locale)
+            .or_else(|| self.catalogs.get(language))
+    }
+
+    /// Translates the descriptions and help of the commands and arguments of the tree
+    pub(crate) fn app<'t>(&'t self, app: &mut App<'t, 't>) {
+        let p = &mut app.p;
+
+        p.meta.about = p.meta.about.map(|about| self.get(about));
+        p.meta.long_about = p.meta.long_about.map(|about| self.get(about));
+        p.help_message = Some(self.get(p.help_message.unwrap_or("Prints help information")));
+        p.version_message =
+            Some(self.get(p.version_message.unwrap_or("Prints version information")));
+
+        for flag in &mut p.flags {
+            flag.b.help = flag.b.help.map(|help| self.get(help));
+            flag.b.long_help = flag.b.long_help.map(|help| self.get(help));
+        }
+
+        for opt in &mut p.opts {
+            opt.b.help = opt.b.help.map(|help| self.get(help));
+            opt.b.long_help = opt.b.long_help.map(|help| self.get(help));
+        }
+
+        for pos in p.positionals.values_mut() {
+            pos.b.help = pos.b.help.map(|help| self.get(help));
+            pos.b.long_help = pos.b.long_help.map(|help| self.get(help));
+        }
+
+        for subcmd in &mut p.subcommands {
+            self.app(subcmd);
+        }
+    }
+
+    /// Translates the section headers and the `error:` prefix printed by `clap`
+    pub(crate) fn message(&self, message: &str) -> String {
+        let translated: Vec<_> = message
+            .split('\n')
+            .map(
+                |line| match HEADERS.iter().find(|header| strip_ansi(line) == **header) {
+                    Some(header) => line.replacen(header, self.get(header), 1),
+                    None => line.to_owned(),
+                },
+            )
+            .collect();
+
+        translated
+            .join("\n")
+            .replacen("error:", self.get("error:"), 1)
+    }
+}
+
+/// The text of a quoted `.po` string
+fn po_string(quoted: &str) -> Option<String> {
+    let text = quoted.strip_prefix('"')?.strip_suffix('"')?;
+
+    Some(text.replace("\\\"", "\"").replace("\\n", "\n"))
+}
+
+#[test]
+fn translated() {
+    let translations = Translations::new()
+        .po(
+            "fr",
+            r#"
+msgid "Shows things"
+msgstr "Affiche des choses"
+
+msgid "USAGE:"
+msgstr "UTILISATION :"
+"#,
+        )
+        .add("fr", "error:", "erreur :")
+        .locale("fr_FR.UTF-8");
+
+    assert_eq!(translations.get("Shows things"), "Affiche des choses");
+    assert_eq!(translations.get("Shows other things"), "Shows other things");
+    assert_eq!(
+        translations.message("error: Cannot run\n\nUSAGE:\n    program show"),
+        "erreur : Cannot run\n\nUTILISATION :\n    program show"
+    );
+    assert_eq!(
+        translations.clone().locale("de").get("Shows things"),
+        "Shows things"
+    );
+}
@@ -0,0 +1,63 @@
+//! Running one invocation at a time, see `Commander::single_instance`
+
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// An exclusive lock on the lock file of a name, released when dropped
+pub(crate) struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Locks `<temp dir>/<name>.lock`, failing right away when another invocation holds it
+    pub(crate) fn acquire(name: &str) -> Result<Self, ClapError> {
+        let path = path(name);
+        let error = |err: std::io::Error| {
+            ClapError::with_description(
+                &format!("Cannot lock '{}': {}", path.display(), err),
+                ClapErrorKind::Io,
+            )
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(error)?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Lock { _file: file }),
+            Err(std::fs::TryLockError::WouldBlock) => Err(ClapError::with_description(
+                &format!(
+                    "Another invocation of `{}` is running, try again once it's done \
+                     (lock file '{}')",
+                    name,
+                    path.display()
+                ),
+                ClapErrorKind::Io,
+            )),
+            Err(std::fs::TryLockError::Error(err)) => Err(error(err)),
+        }
+    }
+}
+
+fn path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("{}.lock", name))
+}
+
+#[test]
+fn exclusive() {
+    let name = format!("clap-nested-lock-{}", std::process::id());
+    let lock = Lock::acquire(&name).unwrap();
+
+    let err = Lock::acquire(&name).err().unwrap();
+    assert_eq!(err.kind, ClapErrorKind::Io);
+    assert!(err.message.contains("Another invocation of `"));
+
+    drop(lock);
+    drop(Lock::acquire(&name).unwrap());
+    std::fs::remove_file(path(&name)).unwrap();
+}
@@ -0,0 +1,70 @@
+//! Cancellation and cleanups of runners, triggered by signals with the `ctrlc` feature
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Tells long-running runners to wrap up, available from
+/// [`Context::cancellation`](struct.Context.html#method.cancellation)
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Whether the user asked to stop, e.g. by pressing Ctrl-C
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Asks to stop like Ctrl-C does, e.g. from another thread of the runner
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+type Cleanup = Box<dyn FnOnce() + Send>;
+
+/// The closures registered with [`Context::defer`](struct.Context.html#method.defer)
+#[derive(Clone, Default)]
+pub(crate) struct Cleanups(Arc<Mutex<Vec<Cleanup>>>);
+
+impl Cleanups {
+    pub(crate) fn defer(&self, cleanup: Cleanup) {
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(cleanup);
+    }
+
+    /// Runs the cleanups registered so far, the latest first, at most once each
+    pub(crate) fn run(&self) {
+        let cleanups = std::mem::take(&mut *self.0.lock().unwrap_or_else(|err| err.into_inner()));
+
+        for cleanup in cleanups.into_iter().rev() {
+            cleanup();
+        }
+    }
+}
+
+/// Runs the cleanups when the runner returns or unwinds
+pub(crate) struct CleanupGuard(pub(crate) Cleanups);
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        self.0.run();
+    }
+}
+
+#[test]
+fn cleanups() {
+    let ran = Arc::new(Mutex::new(Vec::new()));
+    let cleanups = Cleanups::default();
+
+    for i in 0..3 {
+        let ran = ran.clone();
+        cleanups.defer(Box::new(move || ran.lock().unwrap().push(i)));
+    }
+
+    drop(CleanupGuard(cleanups.clone()));
+    cleanups.run();
+
+    assert_eq!(*ran.lock().unwrap(), [2, 1, 0]);
+}
@@ -0,0 +1,106 @@
+//! Building only the subcommands given on the command line, see `CommandLike::app_for`
+
+use std::ffi::OsString;
+
+use clap::{App, ArgMatches};
+
+/// Which subcommands of a commander are fully built
+#[derive(Clone, Copy)]
+pub(crate) enum Build<'p> {
+    All,
+    /// The ones named in the arguments following the commander, the others are stubs
+    Along(&'p [OsString]),
+    /// None, for the stub of a nested commander
+    Shallow,
+}
+
+/// The subcommands fully built by [`app_for`](trait.CommandLike.html#method.app_for),
+/// the other ones are stubs only good for listing them
+#[derive(Default)]
+pub struct Built {
+    /// The subcommand being built
+    prefix: Vec<String>,
+    paths: Vec<Vec<String>>,
+    /// Whether a commander infers subcommands from prefixes, which may pick a stub
+    inferred: bool,
+}
+
+impl Built {
+    pub(crate) fn enter(&mut self, name: impl Into<String>) {
+        self.prefix.push(name.into());
+        self.paths.push(self.prefix.clone());
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.prefix.pop();
+    }
+
+    pub(crate) fn infer(&mut self) {
+        self.inferred = true;
+    }
+
+    pub(crate) fn inferred(&self) -> bool {
+        self.inferred
+    }
+
+    /// Whether all the subcommands in the matches are fully built
+    pub(crate) fn covers(&self, mut matches: &ArgMatches<'_>) -> bool {
+        let mut path = Vec::new();
+
+        while let (name, Some(sub)) = matches.subcommand() {
+            path.push(name.to_owned());
+
+            if !self.paths.contains(&path) {
+                return false;
+            }
+
+            matches = sub;
+        }
+
+        true
+    }
+}
+
+/// The positions of the words which aren't options, any of which may name the subcommand
+/// since the others may be values of options
+pub(crate) fn words(args: &[OsString]) -> impl Iterator<Item = usize> + '_ {
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .enumerate()
+        .filter(|(_, arg)| !arg.to_string_lossy().starts_with('-'))
+        .map(|(index, _)| index)
+}
+
+/// The name and aliases of a subcommand
+pub(crate) fn names<'p>(app: &'p App<'_, '_>) -> impl Iterator<Item = &'p str> {
+    let aliases = app.p.meta.aliases.iter().flatten();
+
+    std::iter::once(app.p.meta.name.as_str()).chain(aliases.map(|(alias, _)| *alias))
+}
+
+#[test]
+fn covered_subcommands() {
+    use clap::SubCommand;
+
+    let app = App::new("program")
+        .subcommand(SubCommand::with_name("show").subcommand(SubCommand::with_name("foo")));
+    let args: Vec<OsString> = ["--env", "prod", "show", "-v", "foo", "--", "bar"]
+        .iter()
+        .map(OsString::from)
+        .collect();
+
+    assert_eq!(words(&args).collect::<Vec<_>>(), [1, 2, 4]);
+
+    let matches = app.get_matches_from(["program", "show", "foo"]);
+    let mut built = Built::default();
+
+    built.enter("show");
+    assert!(!built.covers(&matches));
+
+    built.enter("foo");
+    built.leave();
+    built.leave();
+    built.enter("bar");
+    built.leave();
+    assert!(built.covers(&matches));
+}
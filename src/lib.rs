@@ -163,85 +163,664 @@
 //! [cargo]: https://github.com/rust-lang/cargo
 //! [clap]: https://github.com/clap-rs/clap
 
-use std::collections::HashMap;
-use std::ffi::OsString;
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::{OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::io::Write;
+use std::iter::FromIterator;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 extern crate clap;
+#[cfg(feature = "ctrlc")]
+extern crate ctrlc;
+#[cfg(feature = "env_logger")]
+extern crate env_logger;
+#[cfg(all(unix, feature = "ctrlc"))]
+extern crate libc;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "rpassword")]
+extern crate rpassword;
+#[cfg(feature = "rustyline")]
+extern crate rustyline;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+#[cfg(feature = "toml")]
+extern crate toml;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "tracing-subscriber")]
+extern crate tracing_subscriber;
+#[cfg(feature = "wild")]
+extern crate wild;
+#[cfg(all(windows, feature = "ctrlc"))]
+extern crate windows_sys;
 
 use clap::{
-    App, AppSettings, ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind, SubCommand,
+    App, AppSettings, Arg, ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind, SubCommand,
 };
 
 mod macros;
 
+mod alias;
+mod audit;
+mod capture;
+mod codes;
+mod color;
+mod completions;
+pub mod config;
+mod context;
+mod crash;
+mod dirs;
+mod docs;
+mod doctor;
+mod dotenv;
+mod editor;
+mod environment;
+mod guard;
+mod help;
+#[cfg(feature = "i18n")]
+mod i18n;
+mod instance;
+mod interrupt;
+mod lazy;
+mod logging;
+mod matches;
+mod output;
+mod panic;
+pub mod plugin;
+mod prompt;
+mod redact;
+mod repl;
+mod report;
+mod response;
+mod retry;
+mod runner;
+mod script;
+#[cfg(feature = "self-update")]
+mod self_update;
+mod services;
+#[cfg(feature = "ctrlc")]
+mod signal;
+mod spec;
+pub mod sysexits;
+mod table;
+pub mod testing;
+mod timeout;
+mod timings;
+#[cfg(feature = "serde")]
+mod typed;
+mod update;
+mod vars;
+mod workdir;
+
+pub use audit::AuditLog;
+pub use capture::{CapturedRun, Writer};
+pub use codes::ErrorExt;
+pub use color::ColorChoice;
+pub use completions::CompletionBackend;
+pub use config::Config;
+pub use context::Context;
+pub use crash::CrashReport;
+pub use doctor::{Check, Diagnosis};
+pub use environment::Environment;
+pub use guard::DenyReason;
+pub use help::Help;
+#[cfg(feature = "i18n")]
+pub use i18n::Translations;
+pub use interrupt::CancellationToken;
+pub use lazy::Built;
+pub use logging::LogConfig;
+pub use matches::MatchesExt;
+pub use output::OutputFormat;
+pub use panic::PanicMetadata;
+pub use retry::{Backoff, ExponentialBackoff};
+pub use runner::Runner;
+pub use script::ScriptMode;
+#[cfg(feature = "self-update")]
+pub use self_update::SelfUpdate;
+pub use spec::Spec;
+pub use table::{Align, Table};
+pub use update::UpdateCheck;
+
+use lazy::Build;
+use services::Services;
+use timings::Timings;
+
 type Result = StdResult<(), ClapError>;
 
-#[doc(hidden)]
-pub trait CommandLike<T: ?Sized> {
+/// The name of the argument collecting what follows `--` for `Command::trailing_args`
+const TRAILING_ARGS: &str = "trailing-args";
+
+type Options<'a> = Rc<dyn for<'x, 'y> Fn(App<'x, 'y>) -> App<'x, 'y> + 'a>;
+type NoCmdRunner<'a, T> = Box<dyn Fn(&T, &ArgMatches<'_>) -> Result + 'a>;
+type ContextRunner<'a, T> = Rc<dyn Fn(&T, &ArgMatches<'_>, &Context<'_>) -> Result + 'a>;
+/// Derives the arguments of the subcommands and runs them with it, so that they may be owned
+type Args<'a, S, T> =
+    Rc<dyn Fn(&S, &ArgMatches<'_>, &Context<'_>, &mut dyn FnMut(&T) -> Result) -> Result + 'a>;
+/// Runs with the arguments derived only when they are needed
+type WithArgs<'w, T> = &'w dyn Fn(&mut dyn FnMut(&T) -> Result) -> Result;
+type LeftoverRunner<'a, T> = Box<dyn Fn(&T, &[OsString], &ArgMatches<'_>) -> Result + 'a>;
+type ExitCodes<'a> = Rc<dyn Fn(&ClapError) -> Option<i32> + 'a>;
+type CrashHook<'a> = Rc<dyn Fn(&CrashReport<'_>) + 'a>;
+type Preprocess<'a> = Rc<dyn Fn(Vec<OsString>) -> Vec<OsString> + 'a>;
+type RetryPredicate<'a> = Rc<dyn Fn(&ClapError) -> bool + 'a>;
+type Predicate<'a> = Rc<dyn Fn(&Environment) -> bool + 'a>;
+type Guard<'a> = Rc<dyn Fn(&Context<'_>) -> StdResult<(), DenyReason> + 'a>;
+type Validator<'a> = Rc<dyn Fn(&Context<'_>, &ArgMatches<'_>) -> StdResult<(), String> + 'a>;
+type InvocationHook<'a> = Rc<dyn Fn(&CommandPath, &ArgMatches<'_>, &Result, Duration) + 'a>;
+
+/// What can be added to a [`Commander`](struct.Commander.html) as a subcommand,
+/// like [`Command`](struct.Command.html) and [`MultiCommand`](struct.MultiCommand.html)
+///
+/// Custom commands only need a name, an app and a way to run, the other methods
+/// are optimizations with defaults relying on `app`.
+///
+/// ```
+/// extern crate clap;
+/// extern crate clap_nested;
+///
+/// use clap::{App, Arg, ArgMatches, SubCommand};
+/// use clap_nested::{CommandLike, Commander, Context};
+///
+/// /// Forwards its arguments to a remote host
+/// #[derive(Debug)]
+/// struct Proxy {
+///     host: String,
+/// }
+///
+/// impl CommandLike<()> for Proxy {
+///     fn name(&self) -> &str {
+///         "remote"
+///     }
+///
+///     fn app(&self) -> App<'_, '_> {
+///         SubCommand::with_name("remote")
+///             .about("Runs a command on the remote host")
+///             .arg(Arg::with_name("command").multiple(true))
+///     }
+///
+///     fn run(
+///         &self,
+///         _args: &(),
+///         matches: &ArgMatches<'_>,
+///         _ctx: &Context<'_>,
+///     ) -> Result<(), clap::Error> {
+///         let command: Vec<_> = matches.values_of("command").into_iter().flatten().collect();
+///
+///         println!("ssh {} {}", self.host, command.join(" "));
+///         Ok(())
+///     }
+/// }
+///
+/// fn main() {
+///     let commander = Commander::new()
+///         .options(|app| app.name("program"))
+///         .add_cmd(Proxy { host: "example.com".to_owned() });
+///
+///     assert!(commander.run_with_args_result(["program", "remote", "uptime"]).is_ok());
+/// }
+/// ```
+pub trait CommandLike<T: ?Sized>: fmt::Debug {
+    /// The name of the subcommand, which must be the one of its app
     fn name(&self) -> &str;
-    fn app(&self) -> App;
-    fn run(&self, args: &T, matches: &ArgMatches<'_>, help: &Help) -> Result;
+    /// The app of the subcommand with its arguments and its own subcommands
+    fn app(&self) -> App<'_, '_>;
+    /// The app with only the subcommands named in the arguments following the command
+    /// fully built, adding their names to `built`
+    ///
+    /// Commands wrapping other commands pass `built` on to them.
+    fn app_for(&self, args: &[OsString], built: &mut Built) -> App<'_, '_> {
+        let _ = (args, built);
+        self.app()
+    }
+    /// The app without subcommands, enough to list the command
+    fn stub(&self) -> App<'_, '_> {
+        self.app()
+    }
+    /// The stub with only the name and description, see `Commander::trimmed_help`
+    fn trimmed_stub(&self) -> App<'_, '_> {
+        self.stub()
+    }
+    /// Whether the command asks for a confirmation, which needs a `--yes` flag
+    fn confirms(&self) -> bool {
+        false
+    }
+    /// Runs the command with the arguments derived by its commander
+    /// and the matches of its app
+    fn run(&self, args: &T, matches: &ArgMatches<'_>, ctx: &Context<'_>) -> Result;
 }
 
 /// Define a single-purpose command to be included
 /// in a [`Commander`](struct.Commander.html)
 pub struct Command<'a, T: ?Sized> {
-    name: &'a str,
-    desc: Option<&'a str>,
-    opts: Option<Box<dyn for<'x, 'y> Fn(App<'x, 'y>) -> App<'x, 'y> + 'a>>,
-    runner: Option<Box<dyn Fn(&T, &ArgMatches<'_>) -> Result + 'a>>,
+    name: Cow<'a, str>,
+    desc: Option<Cow<'a, str>>,
+    opts: Option<Options<'a>>,
+    runner: Option<ContextRunner<'a, T>>,
+    timeout: Option<Duration>,
+    env: Vec<(String, OsString)>,
+    retries: u32,
+    backoff: Option<Rc<dyn Backoff + 'a>>,
+    retry_if: Option<RetryPredicate<'a>>,
+    trailing_args: bool,
+    confirm: Option<&'a str>,
+    supports_dry_run: bool,
+    single_instance: Option<&'a str>,
+    validate: Option<Validator<'a>>,
+    enabled: Option<Predicate<'a>>,
+    guard: Option<Guard<'a>>,
 }
 
 impl<'a, T: ?Sized> Command<'a, T> {
-    pub fn new(name: impl Into<&'a str>) -> Self {
+    /// A command named `name`, borrowed like `"show"` or owned like `format!("{}-logs", app)`
+    pub fn new(name: impl Into<Cow<'a, str>>) -> Self {
         Self {
             name: name.into(),
             desc: None,
             opts: None,
             runner: None,
+            timeout: None,
+            env: Vec::new(),
+            retries: 0,
+            backoff: None,
+            retry_if: None,
+            trailing_args: false,
+            confirm: None,
+            supports_dry_run: true,
+            single_instance: None,
+            validate: None,
+            enabled: None,
+            guard: None,
         }
     }
 
-    pub fn description(mut self, desc: impl Into<&'a str>) -> Self {
+    pub fn description(mut self, desc: impl Into<Cow<'a, str>>) -> Self {
         self.desc = Some(desc.into());
         self
     }
 
     pub fn options(mut self, opts: impl for<'x, 'y> Fn(App<'x, 'y>) -> App<'x, 'y> + 'a) -> Self {
-        self.opts = Some(Box::new(opts));
+        self.opts = Some(Rc::new(opts));
         self
     }
 
     pub fn runner(mut self, run: impl Fn(&T, &ArgMatches<'_>) -> Result + 'a) -> Self {
-        self.runner = Some(Box::new(run));
+        self.runner = Some(Rc::new(move |args, matches, _ctx| run(args, matches)));
+        self
+    }
+
+    /// Like `runner`, with a closure which may mutate what it captures
+    pub fn runner_mut(self, run: impl FnMut(&T, &ArgMatches<'_>) -> Result + 'a) -> Self {
+        let name = self.name.clone();
+        let run = RefCell::new(run);
+
+        self.runner(move |args, matches| match run.try_borrow_mut() {
+            Ok(mut run) => run(args, matches),
+            Err(_) => Err(ClapError::with_description(
+                &format!("The command '{}' is already running", name),
+                ClapErrorKind::UnexpectedMultipleUsage,
+            )),
+        })
+    }
+
+    /// Like `runner`, with a closure which may only run once
+    pub fn runner_once(self, run: impl FnOnce(&T, &ArgMatches<'_>) -> Result + 'a) -> Self {
+        let name = self.name.clone();
+        let run = RefCell::new(Some(run));
+
+        self.runner(move |args, matches| {
+            let run = run.borrow_mut().take();
+
+            match run {
+                Some(run) => run(args, matches),
+                None => Err(ClapError::with_description(
+                    &format!("The command '{}' can only run once", name),
+                    ClapErrorKind::UnexpectedMultipleUsage,
+                )),
+            }
+        })
+    }
+
+    /// Like `runner`, also given the [`Help`](struct.Help.html) of the command
+    pub fn runner_with_help(
+        mut self,
+        run: impl Fn(&T, &ArgMatches<'_>, &Help<'_>) -> Result + 'a,
+    ) -> Self {
+        self.runner = Some(Rc::new(move |args, matches, ctx| {
+            run(args, matches, ctx.help())
+        }));
+        self
+    }
+
+    /// Like `runner`, with a [`Runner`](trait.Runner.html) keeping its state in a struct
+    pub fn runner_impl(mut self, runner: impl Runner<T> + 'a) -> Self {
+        self.runner = Some(Rc::new(move |args, matches, _ctx| {
+            runner.run(args, matches)
+        }));
+        self
+    }
+
+    /// Like `runner`, also given the [`Context`](struct.Context.html) of the invocation
+    pub fn runner_with_context(
+        mut self,
+        run: impl Fn(&T, &ArgMatches<'_>, &Context<'_>) -> Result + 'a,
+    ) -> Self {
+        self.runner = Some(Rc::new(run));
+        self
+    }
+
+    /// Like [`runner_with_context`](struct.Command.html#method.runner_with_context),
+    /// but receives the matches deserialized into `A`, whose `bool` fields are flags,
+    /// `Option` fields optional arguments and `Vec` fields multiple values
+    #[cfg(feature = "serde")]
+    pub fn runner_typed<A: serde::de::DeserializeOwned>(
+        mut self,
+        run: impl Fn(&Context<'_>, A) -> Result + 'a,
+    ) -> Self {
+        self.runner = Some(Rc::new(move |_args, matches, ctx| {
+            run(ctx, typed::from_matches(matches)?)
+        }));
+        self
+    }
+
+    /// Like `runner`, also given the arguments after `--` untouched
+    pub fn trailing_args(
+        mut self,
+        run: impl Fn(&T, &[OsString], &ArgMatches<'_>) -> Result + 'a,
+    ) -> Self {
+        self.trailing_args = true;
+        self.runner = Some(Rc::new(move |args, matches, _ctx| {
+            let trailing: Vec<OsString> = match matches.values_of_os(TRAILING_ARGS) {
+                Some(values) => values.map(OsString::from).collect(),
+                None => Vec::new(),
+            };
+
+            run(args, &trailing, matches)
+        }));
+        self
+    }
+
+    /// Cancels the runner after `timeout`, exiting with `124` like `timeout(1)`
+    ///
+    /// The deadline is cooperative: runners which never check their cancellation run on.
+    pub fn cancel_after(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets environment variables of the process while the runner runs
+    pub fn with_env<K: Into<String>, V: Into<OsString>>(
+        mut self,
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.env.extend(
+            vars.into_iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        self
+    }
+
+    /// Runs the runner again up to `retries` times while it fails
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Waits between retries, e.g. with an `ExponentialBackoff`
+    pub fn backoff(mut self, backoff: impl Backoff + 'a) -> Self {
+        self.backoff = Some(Rc::new(backoff));
+        self
+    }
+
+    /// Only retries the errors for which `retryable` returns `true`
+    pub fn retry_if(mut self, retryable: impl Fn(&ClapError) -> bool + 'a) -> Self {
+        self.retry_if = Some(Rc::new(retryable));
+        self
+    }
+
+    /// Hides and refuses the command unless `enabled`, checked before parsing
+    pub fn enabled(mut self, enabled: impl Fn(&Environment) -> bool + 'a) -> Self {
+        self.enabled = Some(Rc::new(enabled));
         self
     }
+
+    /// Refuses to run the command when `guard` denies it
+    pub fn guard(mut self, guard: impl Fn(&Context<'_>) -> StdResult<(), DenyReason> + 'a) -> Self {
+        self.guard = Some(Rc::new(guard));
+        self
+    }
+
+    /// Checks the matches before running, failing like parse errors do
+    pub fn validate(
+        mut self,
+        validate: impl Fn(&Context<'_>, &ArgMatches<'_>) -> StdResult<(), String> + 'a,
+    ) -> Self {
+        self.validate = Some(Rc::new(validate));
+        self
+    }
+
+    /// Asks `question` before running, unless `--yes` is given
+    pub fn confirm(mut self, question: impl Into<&'a str>) -> Self {
+        self.confirm = Some(question.into());
+        self
+    }
+
+    /// Whether the runner honors `--dry-run`, `true` by default, otherwise it is skipped
+    pub fn supports_dry_run(mut self, supported: bool) -> Self {
+        self.supports_dry_run = supported;
+        self
+    }
+
+    /// Fails when another invocation of the command is running
+    pub fn single_instance(mut self, name: impl Into<&'a str>) -> Self {
+        self.single_instance = Some(name.into());
+        self
+    }
+}
+
+impl<'a, T: ?Sized> Command<'a, T> {
+    fn is_enabled(&self) -> bool {
+        self.enabled
+            .as_ref()
+            .is_none_or(|enabled| enabled(&environment::current()))
+    }
+
+    /// Runs again after the failures the retry policy allows
+    fn run_with_retries(&self, run: impl Fn() -> Result) -> Result {
+        let mut retry = 0;
+
+        while let Err(err) = run() {
+            let retryable = self
+                .retry_if
+                .as_ref()
+                .is_none_or(|retryable| retryable(&err));
+
+            if retry >= self.retries || !retryable {
+                return Err(err);
+            }
+
+            if let Some(backoff) = &self.backoff {
+                std::thread::sleep(backoff.delay(retry));
+            }
+
+            retry += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// The clones share the closures, so that the same command can be added to several commanders
+impl<'a, T: ?Sized> Clone for Command<'a, T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            desc: self.desc.clone(),
+            opts: self.opts.clone(),
+            runner: self.runner.clone(),
+            timeout: self.timeout,
+            env: self.env.clone(),
+            retries: self.retries,
+            backoff: self.backoff.clone(),
+            retry_if: self.retry_if.clone(),
+            trailing_args: self.trailing_args,
+            confirm: self.confirm,
+            supports_dry_run: self.supports_dry_run,
+            single_instance: self.single_instance,
+            validate: self.validate.clone(),
+            enabled: self.enabled.clone(),
+            guard: self.guard.clone(),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> fmt::Debug for Command<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Command")
+            .field("name", &self.name)
+            .field("description", &self.desc)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<'a, T: ?Sized> CommandLike<T> for Command<'a, T> {
     fn name(&self) -> &str {
-        self.name
+        &self.name
     }
 
-    fn app(&self) -> App {
-        let mut app = SubCommand::with_name(self.name);
+    fn app(&self) -> App<'_, '_> {
+        let mut app = SubCommand::with_name(&self.name);
 
-        if let Some(desc) = self.desc {
-            app = app.about(desc);
+        if let Some(desc) = &self.desc {
+            app = app.about(&**desc);
         }
 
         if let Some(cmd) = &self.opts {
             app = cmd(app);
         }
 
+        if !self.is_enabled() {
+            app = app.setting(AppSettings::Hidden);
+        }
+
+        if self.trailing_args {
+            app = app.setting(AppSettings::TrailingVarArg).arg(
+                Arg::with_name(TRAILING_ARGS)
+                    .value_name("ARGS")
+                    .multiple(true)
+                    .last(true)
+                    .allow_hyphen_values(true),
+            );
+        }
+
+        app
+    }
+
+    fn trimmed_stub(&self) -> App<'_, '_> {
+        let mut app = SubCommand::with_name(&self.name);
+
+        if let Some(desc) = &self.desc {
+            app = app.about(&**desc);
+        }
+
+        if !self.is_enabled() {
+            app = app.setting(AppSettings::Hidden);
+        }
+
         app
     }
 
-    fn run(&self, args: &T, matches: &ArgMatches<'_>, _help: &Help) -> Result {
+    fn confirms(&self) -> bool {
+        self.confirm.is_some()
+    }
+
+    fn run(&self, args: &T, matches: &ArgMatches<'_>, ctx: &Context<'_>) -> Result {
+        if !self.is_enabled() {
+            return Err(ClapError::with_description(
+                &format!(
+                    "The command `{}` is not enabled in this build or environment",
+                    ctx.path().join(" ")
+                ),
+                ClapErrorKind::InvalidSubcommand,
+            ));
+        }
+
+        if let Some(Err(reason)) = self.guard.as_ref().map(|guard| guard(ctx)) {
+            return Err(ClapError::with_description(
+                &format!(
+                    "Permission denied to run `{}`: {}",
+                    ctx.path().join(" "),
+                    reason
+                ),
+                ClapErrorKind::InvalidSubcommand,
+            ));
+        }
+
+        if let Some(Err(msg)) = self
+            .validate
+            .as_ref()
+            .map(|validate| validate(ctx, matches))
+        {
+            // Reported like parse errors by the commander
+            ctx.fail(Cause::Invalid);
+            return Err(ClapError::with_description(
+                &msg,
+                ClapErrorKind::ValueValidation,
+            ));
+        }
+
         if let Some(runner) = &self.runner {
-            runner(args, matches)?;
+            if !self.supports_dry_run && ctx.is_dry_run() {
+                let _ = writeln!(
+                    capture::stderr(),
+                    "Skipping `{}`, which doesn't support --dry-run",
+                    ctx.path().join(" ")
+                );
+                return Ok(());
+            }
+
+            if let Some(question) = self.confirm.filter(|_| !ctx.assumes_yes()) {
+                prompt::confirm_on_terminal(question)?;
+            }
+
+            let _lock = self
+                .single_instance
+                .map(instance::Lock::acquire)
+                .transpose()?;
+            let watchdog = self
+                .timeout
+                .map(|timeout| timeout::Watchdog::start(ctx.cancellation().clone(), timeout));
+            let _vars = vars::Vars::set(&self.env);
+            let expired = || watchdog.as_ref().is_some_and(timeout::Watchdog::expired);
+            // Stops retrying once the deadline passed, reported below in place of the result
+            let result = self.run_with_retries(|| {
+                if expired() {
+                    Ok(())
+                } else {
+                    runner(args, matches, ctx)
+                }
+            });
+
+            if let (Some(timeout), true) = (self.timeout, expired()) {
+                ctx.fail(Cause::TimedOut);
+                return Err(timeout::error(&ctx.path().join(" "), timeout));
+            }
+
+            return result;
         }
 
         Ok(())
@@ -251,26 +830,194 @@ impl<'a, T: ?Sized> CommandLike<T> for Command<'a, T> {
 /// Define a group of subcommands to be run directly,
 /// or converted as a whole into a higher-order command
 pub struct Commander<'a, S: ?Sized, T: ?Sized> {
-    opts: Option<Box<dyn for<'x, 'y> Fn(App<'x, 'y>) -> App<'x, 'y> + 'a>>,
-    args: Box<dyn for<'x> Fn(&'x S, &'x ArgMatches<'_>) -> &'x T + 'a>,
-    cmds: Vec<Box<dyn CommandLike<T> + 'a>>,
-    no_cmd: Option<Box<dyn Fn(&T, &ArgMatches<'_>) -> Result + 'a>>,
+    opts: Option<Options<'a>>,
+    args: Args<'a, S, T>,
+    cmds: Vec<Rc<dyn CommandLike<T> + 'a>>,
+    /// The positions of the subcommands by name and alias
+    index: HashMap<String, usize>,
+    /// Rc by the clones since its runners are boxed
+    no_cmd: Rc<NoCmd<'a, T>>,
+    parsing: Parsing<'a>,
+    sources: Sources<'a>,
+    builtins: Builtins<'a>,
+    hooks: Hooks<'a>,
+    services: Services<'a>,
+    /// The plugins found by the first scan of their directories
+    discovered: OnceCell<Vec<plugin::Plugin>>,
+    /// The variables of `env_prefix` for all the arguments, borrowed by the apps parsing
+    env_names: OnceCell<HashSet<String>>,
+}
+
+/// How the command line is turned into matches
+#[derive(Clone)]
+struct Parsing<'a> {
+    subcommand_required: bool,
+    global_subcommand_required: bool,
+    lazy: bool,
+    trimmed_help: bool,
+    version: Option<&'a str>,
+    global_version: bool,
+    bin_name: Option<&'a str>,
+    multicall: Option<&'a str>,
+    cargo: Option<&'a str>,
+    plugins: Option<&'a str>,
+    plugin_dirs: Option<Vec<PathBuf>>,
+    aliases: Vec<(String, String)>,
+    alias_file: Option<PathBuf>,
+    chain: Option<&'a str>,
+    invalid_utf8: bool,
+    response_files: bool,
+    preprocess: Option<Preprocess<'a>>,
+    spec: Option<(&'a Spec, spec::Bindings<'a>)>,
+    required_args: Vec<&'a str>,
+    arg_groups: Vec<GlobalGroup<'a>>,
+    sensitive: Vec<&'a str>,
+}
+
+impl<'a> Default for Parsing<'a> {
+    fn default() -> Self {
+        Self {
+            subcommand_required: false,
+            global_subcommand_required: false,
+            lazy: true,
+            trimmed_help: false,
+            version: None,
+            global_version: true,
+            bin_name: None,
+            multicall: None,
+            cargo: None,
+            plugins: None,
+            plugin_dirs: None,
+            aliases: Vec::new(),
+            alias_file: None,
+            chain: None,
+            invalid_utf8: false,
+            response_files: false,
+            preprocess: None,
+            spec: None,
+            required_args: Vec::new(),
+            arg_groups: Vec::new(),
+            sensitive: Vec::new(),
+        }
+    }
+}
+
+/// Where values come from besides the command line
+#[derive(Clone, Default)]
+struct Sources<'a> {
+    config: Option<&'a str>,
+    config_files: Vec<PathBuf>,
+    env_prefix: Option<&'a str>,
+    env_file: Option<PathBuf>,
+}
+
+/// The global options and subcommands provided by the framework
+#[derive(Clone, Default)]
+struct Builtins<'a> {
+    verbosity: bool,
+    logging: Option<LogConfig>,
+    chdir: bool,
+    dry_run: bool,
+    doctor: bool,
+    checks: Vec<Check<'a>>,
+    docs: bool,
+    completions: bool,
+    completion_backends: Vec<Rc<dyn CompletionBackend + 'a>>,
+    explanations: Vec<(&'a str, &'a str)>,
+    color: bool,
+    output: bool,
+    json_errors: bool,
+    timings: bool,
+    script: bool,
+    repl_history: Option<PathBuf>,
+    update_check: Option<UpdateCheck>,
+    #[cfg(feature = "self-update")]
+    self_update: Option<SelfUpdate>,
+}
+
+/// What happens around the runs
+#[derive(Clone, Default)]
+struct Hooks<'a> {
+    exit_codes: Option<ExitCodes<'a>>,
+    single_instance: Option<&'a str>,
+    on_invocation: Option<InvocationHook<'a>>,
+    audit_log: Option<AuditLog>,
+    #[cfg(feature = "i18n")]
+    translations: Option<Translations>,
+    on_crash: Option<CrashHook<'a>>,
+    friendly_panics: Option<PanicMetadata>,
+    catch_panics: bool,
+    #[cfg(feature = "ctrlc")]
+    on_interrupt: Option<signal::InterruptHook>,
 }
 
 impl<'a, S: ?Sized> Commander<'a, S, S> {
     pub fn new() -> Self {
         Self {
             opts: None,
-            args: Box::new(|args, _matches| args),
+            args: Rc::new(|args, _matches, _ctx, run| run(args)),
             cmds: Vec::new(),
-            no_cmd: None,
+            index: HashMap::new(),
+            no_cmd: Rc::new(NoCmd::Help),
+            parsing: Parsing::default(),
+            sources: Sources::default(),
+            builtins: Builtins::default(),
+            hooks: Hooks::default(),
+            services: Services::default(),
+            discovered: OnceCell::new(),
+            env_names: OnceCell::new(),
+        }
+    }
+}
+
+impl<'a, S: ?Sized> Default for Commander<'a, S, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adds the commands like [`add_cmd`](struct.Commander.html#method.add_cmd)
+impl<'a, T: ?Sized> FromIterator<Box<dyn CommandLike<T> + 'a>> for Commander<'a, T, T> {
+    fn from_iter<I: IntoIterator<Item = Box<dyn CommandLike<T> + 'a>>>(cmds: I) -> Self {
+        cmds.into_iter().fold(Self::new(), |commander, cmd| {
+            commander.add_shared_cmd(Rc::from(cmd))
+        })
+    }
+}
+
+/// The clones share the closures and subcommands, so that the same subtree can be mounted
+/// under several parents, e.g. `prod` and `staging`
+impl<'a, S: ?Sized, T: ?Sized> Clone for Commander<'a, S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            opts: self.opts.clone(),
+            args: self.args.clone(),
+            cmds: self.cmds.clone(),
+            index: self.index.clone(),
+            no_cmd: self.no_cmd.clone(),
+            parsing: self.parsing.clone(),
+            sources: self.sources.clone(),
+            builtins: self.builtins.clone(),
+            hooks: self.hooks.clone(),
+            services: self.services.clone(),
+            discovered: OnceCell::new(),
+            env_names: OnceCell::new(),
         }
     }
 }
 
+impl<'a, S: ?Sized, T: ?Sized> fmt::Debug for Commander<'a, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Commander")
+            .field("cmds", &self.cmds)
+            .field("no_cmd", &self.no_cmd)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<'a, S: ?Sized, T: ?Sized> Commander<'a, S, T> {
     pub fn options(mut self, opts: impl for<'x, 'y> Fn(App<'x, 'y>) -> App<'x, 'y> + 'a) -> Self {
-        self.opts = Some(Box::new(opts));
+        self.opts = Some(Rc::new(opts));
         self
     }
 
@@ -278,181 +1025,2511 @@ impl<'a, S: ?Sized, T: ?Sized> Commander<'a, S, T> {
         self,
         args: impl for<'x> Fn(&'x S, &'x ArgMatches<'_>) -> &'x U + 'a,
     ) -> Commander<'a, S, U> {
+        self.with_args(Rc::new(move |data, matches, _ctx, run| {
+            run(args(data, matches))
+        }))
+    }
+
+    /// Like `args`, where deriving the arguments may fail
+    pub fn try_args<U: ?Sized>(
+        self,
+        args: impl for<'x> Fn(&'x S, &'x ArgMatches<'_>) -> StdResult<&'x U, ClapError> + 'a,
+    ) -> Commander<'a, S, U> {
+        self.with_args(Rc::new(move |data, matches, _ctx, run| {
+            run(args(data, matches)?)
+        }))
+    }
+
+    /// Like `try_args`, deriving arguments owned by the invocation
+    pub fn try_args_owned<U>(
+        self,
+        args: impl Fn(&S, &ArgMatches<'_>) -> StdResult<U, ClapError> + 'a,
+    ) -> Commander<'a, S, U> {
+        self.with_args(Rc::new(move |data, matches, _ctx, run| {
+            run(&args(data, matches)?)
+        }))
+    }
+
+    /// Like `args`, deriving the arguments from the path of the subcommands matched
+    pub fn context_for<U>(
+        self,
+        context: impl Fn(&CommandPath, &ArgMatches<'_>) -> U + 'a,
+    ) -> Commander<'a, S, U> {
+        self.with_args(Rc::new(move |_data, matches, ctx, run| {
+            run(&context(&ctx.matched_path(), matches))
+        }))
+    }
+
+    /// Registers a service built on first use by `Context::service`
+    pub fn provide<V: Any>(mut self, factory: impl Fn(&Context<'_>) -> V + 'a) -> Self {
+        self.services.provide(factory);
+        self
+    }
+
+    fn with_args<U: ?Sized>(self, args: Args<'a, S, U>) -> Commander<'a, S, U> {
         Commander {
             opts: self.opts,
-            args: Box::new(args),
+            args,
+            parsing: self.parsing,
+            sources: self.sources,
+            builtins: self.builtins,
+            hooks: self.hooks,
+            services: self.services,
+            discovered: OnceCell::new(),
+            env_names: OnceCell::new(),
             // All other settings are reset.
             cmds: Vec::new(),
-            no_cmd: None,
+            index: HashMap::new(),
+            no_cmd: Rc::new(NoCmd::Help),
         }
     }
 
-    pub fn add_cmd(mut self, cmd: impl CommandLike<T> + 'a) -> Self {
-        self.cmds.push(Box::new(cmd));
-        self
+    /// Adds a subcommand, panicking when its name or an alias is taken
+    pub fn add_cmd(self, cmd: impl CommandLike<T> + 'a) -> Self {
+        self.add_shared_cmd(Rc::new(cmd))
     }
 
-    pub fn no_cmd(mut self, no_cmd: impl Fn(&T, &ArgMatches<'_>) -> Result + 'a) -> Self {
-        self.no_cmd = Some(Box::new(no_cmd));
+    fn add_shared_cmd(mut self, cmd: Rc<dyn CommandLike<T> + 'a>) -> Self {
+        let names = cmd_names(&*cmd);
+
+        if let Some((existing, name)) = self.find_conflict(&names) {
+            panic!(
+                "The subcommands `{}` and `{}` are both named `{}`",
+                self.cmds[existing].name(),
+                cmd.name(),
+                name
+            );
+        }
+
+        self.push_cmd(cmd, names);
         self
     }
 
-    fn app(&self) -> App {
-        let mut app = App::new(clap::crate_name!())
-            .version(clap::crate_version!())
-            .about(clap::crate_description!())
-            .author(clap::crate_authors!());
-
-        if let Some(opts) = &self.opts {
-            app = opts(app);
+    fn push_cmd(&mut self, cmd: Rc<dyn CommandLike<T> + 'a>, names: Vec<String>) {
+        for name in names {
+            self.index.insert(name, self.cmds.len());
         }
 
-        self.cmds
-            .iter()
-            .fold(app, |app, cmd| app.subcommand(cmd.app()))
+        self.cmds.push(cmd);
     }
 
-    fn run_with_data(&self, args: &S, matches: &ArgMatches<'_>, help: &Help) -> Result {
-        let args = (self.args)(args, matches);
-
-        for cmd in &self.cmds {
-            if let Some(matches) = matches.subcommand_matches(cmd.name()) {
-                let help = help.cmds.get(cmd.name()).unwrap();
-                return cmd.run(args, matches, help);
-            }
-        }
+    /// Indexes the subcommands again after their positions or names changed
+    fn reindex(&mut self) {
+        self.index = self
+            .cmds
+            .iter()
+            .enumerate()
+            .flat_map(|(index, cmd)| cmd_names(&**cmd).into_iter().map(move |name| (name, index)))
+            .collect();
+    }
 
-        if let Some(no_cmd) = &self.no_cmd {
-            no_cmd(args, matches)
-        } else {
-            let mut buf = Vec::new();
+    /// The subcommand with the given name or alias
+    fn find_cmd(&self, name: &str) -> Option<&(dyn CommandLike<T> + 'a)> {
+        let index = *self.index.get(name)?;
 
-            self.write_help(&help, &[], &mut buf);
+        Some(&*self.cmds[index])
+    }
 
-            Err(ClapError::with_description(
-                &String::from_utf8(buf).unwrap(),
-                ClapErrorKind::HelpDisplayed,
-            ))
-        }
+    /// Whether a built-in subcommand is added, which a registered one overrides
+    fn has_builtin(&self, name: &str, enabled: bool) -> bool {
+        enabled && self.find_cmd(name).is_none()
     }
 
-    fn write_help(&self, mut help: &Help, path: &[&str], out: &mut impl Write) {
-        for &segment in path {
-            match help.cmds.get(segment) {
-                Some(inner) => help = inner,
-                None => unreachable!("Bad help structure (doesn't match with path)"),
-            }
+    /// Removes the subcommand named `name`, panicking when there is none
+    pub fn remove_cmd(mut self, name: &str) -> Self {
+        match self.cmds.iter().position(|cmd| cmd.name() == name) {
+            Some(index) => drop(self.cmds.remove(index)),
+            None => panic!("No subcommand named `{}` to remove", name),
         }
 
-        out.write(&help.data).unwrap();
+        self.reindex();
+        self
     }
 
-    pub fn into_cmd(self, name: &'a str) -> MultiCommand<'a, S, T> {
-        MultiCommand {
-            name,
-            desc: None,
-            cmd: self,
+    /// Replaces the subcommand of the same name, panicking when there is none
+    pub fn replace_cmd(mut self, cmd: impl CommandLike<T> + 'a) -> Self {
+        let index = match self
+            .cmds
+            .iter()
+            .position(|existing| existing.name() == cmd.name())
+        {
+            Some(index) => index,
+            None => panic!("No subcommand named `{}` to replace", cmd.name()),
+        };
+        let names = cmd_names(&cmd);
+
+        // Like `add_cmd`, apart from the names of the replaced subcommand
+        let conflict = names.iter().find_map(|name| {
+            let existing = *self.index.get(name)?;
+            (existing != index).then_some((existing, name))
+        });
+
+        if let Some((existing, name)) = conflict {
+            panic!(
+                "The subcommands `{}` and `{}` are both named `{}`",
+                self.cmds[existing].name(),
+                cmd.name(),
+                name
+            );
         }
+
+        self.cmds[index] = Rc::new(cmd);
+        self.reindex();
+        self
     }
-}
 
-impl<'a, T: ?Sized> Commander<'a, (), T> {
-    pub fn run(&self) {
-        self.run_result().unwrap_or_else(|error| error.exit())
+    /// The subcommand using one of the names, and the name
+    fn find_conflict<'n>(&self, names: &'n [String]) -> Option<(usize, &'n str)> {
+        names
+            .iter()
+            .find_map(|name| Some((*self.index.get(name)?, name.as_str())))
     }
 
-    pub fn run_with_args(&self, args: impl IntoIterator<Item = impl Into<OsString> + Clone>) {
-        self.run_with_args_result(args).unwrap_or_else(|error| error.exit())
+    /// Adds the subcommand only when `cond` holds, e.g. `cfg!(unix)`
+    pub fn add_cmd_if(self, cond: bool, cmd: impl CommandLike<T> + 'a) -> Self {
+        if cond {
+            self.add_cmd(cmd)
+        } else {
+            self
+        }
     }
 
-    pub fn run_result(&self) -> Result {
-        self.run_with_args_result(std::env::args_os())
+    /// Adds the subcommand only when `cond` returns `true`
+    pub fn add_cmd_when<C: CommandLike<T> + 'a>(
+        self,
+        cond: impl FnOnce() -> bool,
+        cmd: impl FnOnce() -> C,
+    ) -> Self {
+        if cond() {
+            self.add_cmd(cmd())
+        } else {
+            self
+        }
     }
 
-    pub fn run_with_args_result(
-        &self,
-        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
-    ) -> Result {
-        let mut args = args.into_iter().peekable();
-        let mut app = self.app();
+    /// Adds the subcommands in order
+    pub fn add_cmds(self, cmds: impl IntoIterator<Item = impl CommandLike<T> + 'a>) -> Self {
+        cmds.into_iter()
+            .fold(self, |commander, cmd| commander.add_cmd(cmd))
+    }
 
-        // Infer binary name
-        if let Some(name) = args.peek() {
-            let name = name.clone().into();
-            let path = std::path::Path::new(&name);
+    /// Takes the subcommands, options and `no_cmd` of another commander
+    pub fn extend(self, other: Commander<'a, S, T>) -> Self {
+        self.extend_with(other, Conflict::Panic)
+    }
 
-            if let Some(filename) = path.file_name() {
-                if let Some(binary_name) = filename.to_os_string().to_str() {
-                    if app.p.meta.bin_name.is_none() {
-                        app.p.meta.bin_name = Some(binary_name.to_owned());
-                    }
+    /// Like [`extend`](#method.extend), but resolving conflicts with the given policy
+    pub fn extend_with(mut self, other: Commander<'a, S, T>, conflict: Conflict) -> Self {
+        self.opts = match (self.opts.take(), other.opts) {
+            (Some(opts), Some(other)) => Some(Rc::new(move |app| other(opts(app)))),
+            (opts, other) => opts.or(other),
+        };
+
+        for cmd in other.cmds {
+            let names = cmd_names(&*cmd);
+
+            match (self.find_conflict(&names), conflict) {
+                (None, _) => self.push_cmd(cmd, names),
+                (Some((existing, name)), Conflict::Panic) => panic!(
+                    "The subcommands `{}` and `{}` are both named `{}`",
+                    self.cmds[existing].name(),
+                    cmd.name(),
+                    name
+                ),
+                (Some(_), Conflict::Keep) => {}
+                (Some((existing, _)), Conflict::Replace) => {
+                    self.cmds[existing] = cmd;
+                    self.reindex();
                 }
             }
         }
 
-        fn propagate_author<'a>(app: &mut App<'_, 'a>, author: &'a str) {
-            app.p.meta.author = Some(author);
-
-            for subcmd in &mut app.p.subcommands {
-                propagate_author(subcmd, author);
-            }
+        match (&*self.no_cmd, &*other.no_cmd, conflict) {
+            (_, NoCmd::Help, _) => {}
+            (NoCmd::Help, _, _) | (_, _, Conflict::Replace) => self.no_cmd = other.no_cmd,
+            (_, _, Conflict::Keep) => {}
+            (_, _, Conflict::Panic) => panic!("Both commanders have a `no_cmd`"),
         }
 
-        let mut tmp = Vec::new();
-        // This hack is used to propagate all needed information to subcommands.
-        app.p.set(AppSettings::GlobalVersion);
-        app.p.gen_completions_to(clap::Shell::Bash, &mut tmp);
-
-        // Also propagate author to subcommands since `clap` doesn't do it
-        if let Some(author) = app.p.meta.author {
-            propagate_author(&mut app, author);
-        }
+        self
+    }
 
-        let help = Help::from(&app);
+    pub fn no_cmd(mut self, no_cmd: impl Fn(&T, &ArgMatches<'_>) -> Result + 'a) -> Self {
+        self.no_cmd = Rc::new(NoCmd::Run(Box::new(no_cmd)));
+        self
+    }
 
-        match app.get_matches_from_safe(args) {
-            Ok(matches) => self.run_with_data(&(), &matches, &help),
-            Err(err) => match err.kind {
-                clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => Err(err),
-                _ => {
-                    let mut msg = err.message;
-                    let mut buf = Vec::new();
-                    let mut help_captured = false;
+    /// Like `no_cmd`, with a closure which may mutate what it captures
+    pub fn no_cmd_mut(self, no_cmd: impl FnMut(&T, &ArgMatches<'_>) -> Result + 'a) -> Self {
+        let no_cmd = RefCell::new(no_cmd);
+
+        self.no_cmd(move |args, matches| match no_cmd.try_borrow_mut() {
+            Ok(mut no_cmd) => no_cmd(args, matches),
+            Err(_) => Err(ClapError::with_description(
+                "The commander is already running without a subcommand",
+                ClapErrorKind::UnexpectedMultipleUsage,
+            )),
+        })
+    }
 
-                    if let Some(index) = msg.find("\nUSAGE") {
-                        let usage = msg.split_off(index);
-                        let mut lines = usage.lines();
+    pub fn no_cmd_policy(mut self, no_cmd: NoCmd<'a, T>) -> Self {
+        self.no_cmd = Rc::new(no_cmd);
+        self
+    }
 
-                        buf.extend_from_slice(msg.as_bytes());
-                        buf.push('\n' as u8);
+    /// Fails with the help when no subcommand is given
+    pub fn subcommand_required(mut self, required: bool) -> Self {
+        self.parsing.subcommand_required = required;
+        self
+    }
 
-                        lines.next();
-                        lines.next();
+    /// Like `subcommand_required`, for the nested commanders too
+    pub fn global_subcommand_required(mut self, required: bool) -> Self {
+        self.parsing.global_subcommand_required = required;
+        self
+    }
 
-                        if let Some(usage) = lines.next() {
-                            let mut usage = usage.to_owned();
+    /// Whether only the subcommands given are built before parsing, `true` by default
+    ///
+    /// Misplaced subcommands are only suggested when everything is built.
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.parsing.lazy = lazy;
+        self
+    }
 
-                            if let Some(index) = usage.find("[") {
-                                usage.truncate(index);
+    /// Lists the subcommands which aren't given with only their name and description
+    pub fn trimmed_help(mut self, trimmed: bool) -> Self {
+        self.parsing.trimmed_help = trimmed;
+        self
+    }
+
+    /// Overrides the version shown by `--version`, which defaults to the crate version
+    pub fn version(mut self, version: impl Into<&'a str>) -> Self {
+        self.parsing.version = Some(version.into());
+        self
+    }
+
+    /// Whether the subcommands get the version of the root, `true` by default
+    pub fn global_version(mut self, global: bool) -> Self {
+        self.parsing.global_version = global;
+        self
+    }
+
+    /// Sets the binary name shown in usages
+    pub fn bin_name(mut self, bin_name: impl Into<&'a str>) -> Self {
+        self.parsing.bin_name = Some(bin_name.into());
+        self
+    }
+
+    /// Lets a binary named `<prefix><name>` run the subcommand `name`, busybox-style
+    pub fn multicall(mut self, prefix: impl Into<&'a str>) -> Self {
+        self.parsing.multicall = Some(prefix.into());
+        self
+    }
+
+    /// Runs as the `cargo-<name>` binary of a cargo subcommand
+    pub fn cargo_subcommand(mut self, name: impl Into<&'a str>) -> Self {
+        self.parsing.cargo = Some(name.into());
+        self
+    }
+
+    /// Runs `<prefix><name>` executables in `$PATH` as extra subcommands
+    pub fn plugins(mut self, prefix: impl Into<&'a str>) -> Self {
+        self.parsing.plugins = Some(prefix.into());
+        self
+    }
+
+    /// Looks for the [`plugins`](#method.plugins) in the given directories instead of `$PATH`
+    pub fn plugin_dirs(mut self, dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.parsing.plugin_dirs = Some(dirs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Lists the plugins which don't clash with a subcommand, scanning once
+    pub fn discover_plugins(&self) -> &[plugin::Plugin] {
+        let prefix = match self.parsing.plugins {
+            Some(prefix) => prefix,
+            None => return &[],
+        };
+
+        self.discovered.get_or_init(|| {
+            let plugins = match &self.parsing.plugin_dirs {
+                Some(dirs) => plugin::discover_in(prefix, dirs.iter().cloned()),
+                None => plugin::discover(prefix),
+            };
+
+            plugins
+                .into_iter()
+                .filter(|plugin| self.cmds.iter().all(|cmd| cmd.name() != plugin.name()))
+                .collect()
+        })
+    }
+
+    /// The plugin named `name`, unless it clashes with a registered subcommand,
+    /// looked up without scanning the directories
+    fn find_plugin(&self, name: &str) -> Option<plugin::Plugin> {
+        let prefix = self.parsing.plugins?;
+
+        if self.index.contains_key(name) {
+            return None;
+        }
+
+        match &self.parsing.plugin_dirs {
+            Some(dirs) => plugin::find_in(prefix, name, dirs.iter().cloned()),
+            None => plugin::find(prefix, name),
+        }
+    }
+
+    /// Expands `name` into `expansion` in command position, e.g. `.alias("st", "show foo")`
+    pub fn alias(mut self, name: impl Into<String>, expansion: impl Into<String>) -> Self {
+        self.parsing.aliases.push((name.into(), expansion.into()));
+        self
+    }
+
+    /// Loads more aliases from the `[alias]` table of a config file
+    pub fn alias_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.parsing.alias_file = Some(path.into());
+        self
+    }
+
+    /// Loads the [`Config`](config/struct.Config.html) of the app `name`
+    pub fn config(mut self, name: &'a str) -> Self {
+        self.sources.config = Some(name);
+        self
+    }
+
+    /// Loads one more config file on top of the standard locations, it must exist
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.config_files.push(path.into());
+        self
+    }
+
+    /// Makes the arguments fall back to `<PREFIX>_<ARG>` environment variables
+    pub fn env_prefix(mut self, prefix: &'a str) -> Self {
+        self.sources.env_prefix = Some(prefix);
+        self
+    }
+
+    /// Loads a `.env` file, or the one given with `--env-file`, before parsing and any thread
+    pub fn env_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.env_file = Some(path.into());
+        self
+    }
+
+    /// Registers global `-v/--verbose` and `-q/--quiet` flags
+    pub fn with_verbosity(mut self) -> Self {
+        self.builtins.verbosity = true;
+        self
+    }
+
+    /// Sets up logging at the level given by the verbosity flags
+    pub fn init_logging(mut self, config: LogConfig) -> Self {
+        self.builtins.verbosity = true;
+        self.builtins.logging = Some(config);
+        self
+    }
+
+    /// Registers a global `-C <DIR>` option changing the process' working directory, like `git -C`
+    pub fn with_chdir_flag(mut self) -> Self {
+        self.builtins.chdir = true;
+        self
+    }
+
+    /// Registers a global `--dry-run` flag
+    pub fn with_dry_run(mut self) -> Self {
+        self.builtins.dry_run = true;
+        self
+    }
+
+    /// Adds a `doctor` subcommand running the checks
+    pub fn with_doctor(mut self) -> Self {
+        self.builtins.doctor = true;
+        self
+    }
+
+    /// Adds a `docs` subcommand printing the reference of a command
+    pub fn with_docs(mut self) -> Self {
+        self.builtins.docs = true;
+        self
+    }
+
+    /// Adds a `completions` subcommand printing or installing completion scripts
+    pub fn with_completions(mut self) -> Self {
+        self.builtins.completions = true;
+        self
+    }
+
+    /// Registers the completion script of another shell
+    pub fn completion_backend(mut self, backend: impl CompletionBackend + 'a) -> Self {
+        self.builtins.completion_backends.push(Rc::new(backend));
+        self
+    }
+
+    /// Registers the explanation of an error code, shown by an `explain` subcommand
+    pub fn explain(mut self, code: impl Into<&'a str>, explanation: impl Into<&'a str>) -> Self {
+        self.builtins
+            .explanations
+            .push((code.into(), explanation.into()));
+        self
+    }
+
+    /// Registers a check run by the [`doctor`](#method.with_doctor) subcommand
+    pub fn check(mut self, check: Check<'a>) -> Self {
+        self.builtins.checks.push(check);
+        self
+    }
+
+    /// Fails when another invocation holding the lock `name` is running
+    pub fn single_instance(mut self, name: impl Into<&'a str>) -> Self {
+        self.hooks.single_instance = Some(name.into());
+        self
+    }
+
+    /// Registers a global `--color <auto|always|never>` option
+    pub fn with_color(mut self) -> Self {
+        self.builtins.color = true;
+        self
+    }
+
+    /// Registers a global `--output <json|yaml|table>` option
+    pub fn with_output(mut self) -> Self {
+        self.builtins.output = true;
+        self
+    }
+
+    /// Registers a global `--json-errors` flag reporting errors as JSON
+    pub fn with_json_errors(mut self) -> Self {
+        self.builtins.json_errors = true;
+        self
+    }
+
+    /// Registers a hidden global `--timings` flag printing how long each phase took
+    pub fn with_timings(mut self) -> Self {
+        self.builtins.timings = true;
+        self
+    }
+
+    /// Calls `hook` after every execution, unless `--no-telemetry` or `DO_NOT_TRACK`
+    pub fn on_invocation(
+        mut self,
+        hook: impl Fn(&CommandPath, &ArgMatches<'_>, &Result, Duration) + 'a,
+    ) -> Self {
+        self.hooks.on_invocation = Some(Rc::new(hook));
+        self
+    }
+
+    /// Appends every invocation to an audit log
+    pub fn audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.hooks.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Prints a notice when a newer version is available
+    pub fn check_for_updates(mut self, update_check: UpdateCheck) -> Self {
+        self.builtins.update_check = Some(update_check);
+        self
+    }
+
+    /// Adds a `self update [VERSION]` subcommand downloading a release, checking it
+    /// and replacing the running binary with it, unless there's already a `self` subcommand
+    #[cfg(feature = "self-update")]
+    pub fn with_self_update(mut self, self_update: SelfUpdate) -> Self {
+        self.builtins.self_update = Some(self_update);
+        self
+    }
+
+    /// Translates the descriptions and help of the commands and arguments, the section
+    /// headers of help and the `error:` prefix of errors to the locale, with the `i18n` feature
+    #[cfg(feature = "i18n")]
+    pub fn translations(mut self, translations: Translations) -> Self {
+        self.hooks.translations = Some(translations);
+        self
+    }
+
+    /// Calls `hook` when parsing fails, a runner fails or a runner panics
+    pub fn on_crash(mut self, hook: impl Fn(&CrashReport<'_>) + 'a) -> Self {
+        self.hooks.on_crash = Some(Rc::new(hook));
+        self
+    }
+
+    /// Replaces the panic message with a note pointing to a report file
+    pub fn friendly_panics(mut self, metadata: PanicMetadata) -> Self {
+        self.hooks.friendly_panics = Some(metadata);
+        self
+    }
+
+    /// Turns the panics of runners into errors
+    pub fn catch_panics(mut self) -> Self {
+        self.hooks.catch_panics = true;
+        self
+    }
+
+    /// Calls `hook` with the running command on the first Ctrl-C, `SIGTERM` or `SIGHUP`
+    /// (Ctrl-Break on Windows, where closing the console runs the hook and cleanups at once),
+    /// which also cancels [`Context::cancellation`](struct.Context.html#method.cancellation),
+    /// a second one runs the [`Context::defer`](struct.Context.html#method.defer)red cleanups
+    /// and exits with `128` plus the signal number, e.g. `130` for Ctrl-C and `143` for `SIGTERM`;
+    /// signals are left alone by commanders without a hook
+    #[cfg(feature = "ctrlc")]
+    pub fn on_interrupt(mut self, hook: impl Fn(&CommandPath) + Send + Sync + 'static) -> Self {
+        self.hooks.on_interrupt = Some(std::sync::Arc::new(hook));
+        self
+    }
+
+    /// Registers `--script <PATH>` and `--keep-going` options running a script
+    pub fn with_script(mut self) -> Self {
+        self.builtins.script = true;
+        self
+    }
+
+    /// Runs several commands separated by `separator`, e.g. `build + deploy`
+    pub fn chain(mut self, separator: &'a str) -> Self {
+        assert!(
+            separator != "--",
+            "`--` can't separate chained commands, it ends their options"
+        );
+
+        self.parsing.chain = Some(separator);
+        self
+    }
+
+    /// Accepts arguments which aren't valid UTF-8 at all levels
+    pub fn allow_invalid_utf8(mut self) -> Self {
+        self.parsing.invalid_utf8 = true;
+        self
+    }
+
+    /// Replaces `@<path>` arguments with the arguments in the file
+    pub fn response_files(mut self) -> Self {
+        self.parsing.response_files = true;
+        self
+    }
+
+    /// Rewrites the arguments before anything else looks at them
+    pub fn preprocess(mut self, preprocess: impl Fn(Vec<OsString>) -> Vec<OsString> + 'a) -> Self {
+        self.parsing.preprocess = Some(Rc::new(preprocess));
+        self
+    }
+
+    /// Fails with the help when the global option `name` isn't given
+    pub fn require_arg(mut self, name: &'a str) -> Self {
+        self.parsing.required_args.push(name);
+        self
+    }
+
+    /// Fails when more than one of the global options `names` is given
+    pub fn exclusive_args(mut self, names: &'a [&'a str]) -> Self {
+        self.parsing.arg_groups.push(GlobalGroup::Exclusive(names));
+        self
+    }
+
+    /// Fails when some but not all of the global options `names` are given
+    pub fn args_together(mut self, names: &'a [&'a str]) -> Self {
+        self.parsing.arg_groups.push(GlobalGroup::Together(names));
+        self
+    }
+
+    /// Hides the values of the argument `name` in the help, errors and logs
+    pub fn sensitive(mut self, name: &'a str) -> Self {
+        self.parsing.sensitive.push(name);
+        self
+    }
+
+    /// Keeps the history of `run_repl` in a file
+    pub fn repl_history(mut self, path: impl Into<PathBuf>) -> Self {
+        self.builtins.repl_history = Some(path.into());
+        self
+    }
+
+    /// Maps errors to exit codes, falling back to the defaults on `None`
+    pub fn exit_codes(mut self, exit_codes: impl Fn(&ClapError) -> Option<i32> + 'a) -> Self {
+        self.hooks.exit_codes = Some(Rc::new(exit_codes));
+        self
+    }
+
+    /// The exit code of an error: from `exit_codes`, else `1` or `0` for help and version
+    pub fn exit_code_for(&self, err: &ClapError) -> i32 {
+        self.hooks
+            .exit_codes
+            .as_ref()
+            .and_then(|exit_codes| exit_codes(err))
+            .unwrap_or(if err.use_stderr() { 1 } else { 0 })
+    }
+
+    /// The exit code of a failure, see [`exit_code_for`](#method.exit_code_for)
+    fn exit_code(&self, err: &ClapError, cause: Cause) -> i32 {
+        match self
+            .hooks
+            .exit_codes
+            .as_ref()
+            .and_then(|exit_codes| exit_codes(err))
+        {
+            Some(code) => code,
+            None if cause == Cause::TimedOut => timeout::EXIT_CODE,
+            None => self.exit_code_for(err),
+        }
+    }
+
+    /// The paths of the commands whose name or description contains `term`
+    pub fn search(&self, term: &str) -> Vec<CommandPath> {
+        let mut found = Vec::new();
+
+        search_tree(
+            &self.app(),
+            &term.to_lowercase(),
+            &mut Vec::new(),
+            &mut found,
+        );
+        found.into_iter().map(|(path, _)| path).collect()
+    }
+
+    fn app(&self) -> App<'_, '_> {
+        self.root_app(Build::All, &mut Built::default())
+    }
+
+    /// The app of the top commander, with the global arguments which can't be defined again below
+    fn root_app(&self, build: Build<'_>, built: &mut Built) -> App<'_, '_> {
+        let mut app = self.build_app(build, built);
+
+        if self.cmds.iter().any(|cmd| cmd.confirms()) {
+            let args: Vec<_> = (app.p.flags.iter().map(|flag| (flag.b.name, &flag.s)))
+                .chain(app.p.opts.iter().map(|opt| (opt.b.name, &opt.s)))
+                .map(|(name, switch)| (name, switch.long, switch.short))
+                .collect();
+
+            // Left to the app when it has its own
+            if !args
+                .iter()
+                .any(|&(name, long, _)| name == "yes" || long == Some("yes"))
+            {
+                let yes = Arg::with_name("yes")
+                    .long("yes")
+                    .help("Answers yes to confirmations")
+                    .global(true);
+                let short = args.iter().all(|&(_, _, short)| short != Some('y'));
+
+                app = app.arg(if short { yes.short("y") } else { yes });
+            }
+        }
+
+        app
+    }
+
+    fn build_app(&self, build: Build<'_>, built: &mut Built) -> App<'_, '_> {
+        let _environment = environment::Scope::enter(|| {
+            Environment::new(
+                self.sources.config,
+                &self.sources.config_files,
+                self.sources.env_prefix,
+            )
+        });
+        let mut app = App::new(clap::crate_name!())
+            .version(self.parsing.version.unwrap_or(clap::crate_version!()))
+            .about(clap::crate_description!())
+            .author(clap::crate_authors!());
+
+        if let Some(bin_name) = self.parsing.bin_name {
+            app = app.bin_name(bin_name);
+        }
+
+        if let Some((spec, _)) = self.parsing.spec {
+            if !spec.name().is_empty() {
+                app = app.name(spec.name());
+            }
+
+            if let Some(desc) = spec.description() {
+                app = app.about(desc);
+            }
+
+            app = app.args(&spec.args());
+        }
+
+        if let Some(opts) = &self.opts {
+            app = opts(app);
+        }
+
+        if app.p.is_set(AppSettings::InferSubcommands) {
+            built.infer();
+        }
+
+        if let NoCmd::Leftover(_) | NoCmd::Suggest = *self.no_cmd {
+            app = app.setting(AppSettings::AllowExternalSubcommands);
+        }
+
+        if self.parsing.invalid_utf8 {
+            app = app.global_setting(AppSettings::AllowInvalidUtf8);
+        }
+
+        if self.parsing.subcommand_required || self.parsing.global_subcommand_required {
+            app = app.setting(AppSettings::SubcommandRequiredElseHelp);
+        }
+
+        if self.builtins.verbosity {
+            app = app
+                .arg(
+                    Arg::with_name("verbose")
+                        .short("v")
+                        .long("verbose")
+                        .multiple(true)
+                        .global(true)
+                        .help("Increases the verbosity, can be repeated"),
+                )
+                .arg(
+                    Arg::with_name("quiet")
+                        .short("q")
+                        .long("quiet")
+                        .multiple(true)
+                        .global(true)
+                        .help("Decreases the verbosity, can be repeated"),
+                );
+        }
+
+        if self.builtins.chdir {
+            app = app.arg(
+                Arg::with_name(workdir::ARG)
+                    .short("C")
+                    .value_name("DIR")
+                    .help("Runs as if started in DIR")
+                    .global(true),
+            );
+        }
+
+        if self.builtins.dry_run {
+            app = app.arg(
+                Arg::with_name("dry-run")
+                    .long("dry-run")
+                    .help("Shows what would be done without doing it")
+                    .global(true),
+            );
+        }
+
+        if self.builtins.color {
+            app = app.setting(AppSettings::ColoredHelp).arg(
+                Arg::with_name("color")
+                    .long("color")
+                    .value_name("WHEN")
+                    .possible_values(ColorChoice::VALUES)
+                    .help("Colors the output")
+                    .global(true),
+            );
+        }
+
+        if self.builtins.output {
+            app = app.arg(
+                Arg::with_name("output")
+                    .long("output")
+                    .value_name("FORMAT")
+                    .possible_values(OutputFormat::VALUES)
+                    .help("Selects the output format")
+                    .global(true),
+            );
+        }
+
+        if self
+            .builtins
+            .logging
+            .as_ref()
+            .is_some_and(LogConfig::has_format_flag)
+        {
+            app = app.arg(
+                Arg::with_name("log-format")
+                    .long("log-format")
+                    .value_name("FORMAT")
+                    .possible_values(&["text", "json"])
+                    .help("Selects the log format")
+                    .global(true),
+            );
+        }
+
+        if self.hooks.on_invocation.is_some() {
+            app = app.arg(
+                Arg::with_name("no-telemetry")
+                    .long("no-telemetry")
+                    .help("Disables usage analytics")
+                    .global(true),
+            );
+        }
+
+        if self.builtins.timings {
+            app = app.arg(
+                Arg::with_name("timings")
+                    .long("timings")
+                    .hidden(true)
+                    .global(true),
+            );
+        }
+
+        if self.builtins.json_errors {
+            app = app.arg(
+                Arg::with_name("json-errors")
+                    .long("json-errors")
+                    .help("Reports errors as JSON")
+                    .global(true),
+            );
+        }
+
+        if self.builtins.script {
+            app = app
+                .arg(
+                    Arg::with_name("script")
+                        .long("script")
+                        .value_name("PATH")
+                        .help("Runs the commands in the given file, one per line"),
+                )
+                .arg(
+                    Arg::with_name("keep-going")
+                        .long("keep-going")
+                        .requires("script")
+                        .help("Keeps running the script after a command fails"),
+                );
+        }
+
+        if self.sources.env_file.is_some() {
+            app = app.arg(
+                Arg::with_name("env-file")
+                    .long("env-file")
+                    .value_name("PATH")
+                    .help("Loads environment variables from the given file")
+                    .global(true),
+            );
+        }
+
+        let mut app = match build {
+            Build::All => self
+                .cmds
+                .iter()
+                .fold(app, |app, cmd| app.subcommand(cmd.app())),
+            Build::Along(args) => self.lazy_subcommands(app, args, built),
+            Build::Shallow => return app,
+        };
+
+        // Only the plugins given are looked up, unless they are listed in the help
+        let plugins = match build {
+            Build::Along(args) if !asks_for_help(args) => {
+                let mut plugins: Vec<_> = lazy::words(args)
+                    .filter_map(|index| args[index].to_str())
+                    .filter_map(|word| self.find_plugin(word))
+                    .collect();
+
+                plugins.sort_by(|a, b| a.name().cmp(b.name()));
+                plugins.dedup();
+                plugins
+            }
+            _ => self.discover_plugins().to_vec(),
+        };
+
+        // Plugins parse their own arguments, including `--help`
+        for plugin in &plugins {
+            app = app.subcommand(
+                SubCommand::with_name(plugin.name())
+                    .about("External plugin")
+                    .settings(&[
+                        AppSettings::DisableHelpFlags,
+                        AppSettings::DisableVersion,
+                        AppSettings::TrailingVarArg,
+                        AppSettings::AllowLeadingHyphen,
+                    ])
+                    .arg(
+                        Arg::with_name("args")
+                            .multiple(true)
+                            .allow_hyphen_values(true),
+                    ),
+            );
+        }
+
+        if self.has_builtin(codes::NAME, !self.builtins.explanations.is_empty()) {
+            app = app.subcommand(codes::subcommand());
+        }
+
+        if self.has_builtin(completions::NAME, self.builtins.completions) {
+            app = app.subcommand(completions::subcommand(&completions::backends(
+                &self.builtins.completion_backends,
+            )));
+        }
+
+        if self.has_builtin(docs::NAME, self.builtins.docs) {
+            app = app.subcommand(docs::subcommand());
+        }
+
+        #[cfg(feature = "self-update")]
+        if self.has_builtin(self_update::NAME, self.builtins.self_update.is_some()) {
+            app = app.subcommand(SelfUpdate::subcommand());
+        }
+
+        if self.has_builtin(doctor::NAME, self.builtins.doctor) {
+            app = app.subcommand(
+                SubCommand::with_name(doctor::NAME).about("Checks the environment for problems"),
+            );
+        }
+
+        fn require_subcommands(app: &mut App<'_, '_>) {
+            for subcmd in &mut app.p.subcommands {
+                if !subcmd.p.subcommands.is_empty() {
+                    subcmd.p.set(AppSettings::SubcommandRequiredElseHelp);
+                }
+
+                require_subcommands(subcmd);
+            }
+        }
+
+        if self.parsing.global_subcommand_required {
+            require_subcommands(&mut app);
+        }
+
+        #[cfg(feature = "i18n")]
+        if let Some(translations) = &self.hooks.translations {
+            translations.app(&mut app);
+        }
+
+        app
+    }
+
+    /// Adds fully built the subcommands named in `args` and the one `no_cmd` may run,
+    /// and stubs of the other ones
+    fn lazy_subcommands<'b>(
+        &'b self,
+        mut app: App<'b, 'b>,
+        args: &[OsString],
+        built: &mut Built,
+    ) -> App<'b, 'b> {
+        // The first word naming each subcommand, the arguments after it are its own
+        let mut given = HashMap::new();
+
+        for index in lazy::words(args) {
+            match args[index].to_str().and_then(|word| self.index.get(word)) {
+                Some(&position) => {
+                    given.entry(position).or_insert(index);
+                }
+                // Either an external subcommand or the value of an option
+                None => {
+                    built.enter(args[index].to_string_lossy());
+                    built.leave();
+                }
+            }
+        }
+
+        for (position, cmd) in self.cmds.iter().enumerate() {
+            let args = match (given.get(&position), &*self.no_cmd) {
+                (Some(&index), _) => Some(&args[index + 1..]),
+                (None, NoCmd::Default(name)) if self.index.get(*name) == Some(&position) => {
+                    Some(&[][..])
+                }
+                (None, NoCmd::Pick) if given.is_empty() => Some(&[][..]),
+                _ => None,
+            };
+
+            app = app.subcommand(match args {
+                Some(args) => {
+                    built.enter(cmd.name());
+                    let app = cmd.app_for(args, built);
+                    built.leave();
+                    app
+                }
+                None if self.parsing.trimmed_help => cmd.trimmed_stub(),
+                None => cmd.stub(),
+            });
+        }
+
+        app
+    }
+
+    fn run_with_data(&self, args: &S, matches: &ArgMatches<'_>, ctx: &Context<'_>) -> Result {
+        self.run_derived(&|run| (self.args)(args, matches, ctx, run), matches, ctx)
+    }
+
+    fn run_derived(
+        &self,
+        with_args: WithArgs<'_, T>,
+        matches: &ArgMatches<'_>,
+        ctx: &Context<'_>,
+    ) -> Result {
+        let help = ctx.help();
+        let run_cmd = |cmd: &dyn CommandLike<T>, matches: &ArgMatches<'_>| {
+            let help = help.cmd(cmd.name()).unwrap();
+            let ctx = ctx.child(cmd.name(), matches, &help);
+
+            instrument(&ctx, || with_args(&mut |args| cmd.run(args, matches, &ctx)))
+        };
+
+        if let (name, Some(matches)) = matches.subcommand() {
+            if let Some(cmd) = self.find_cmd(name) {
+                return run_cmd(cmd, matches);
+            }
+        }
+
+        match matches.subcommand() {
+            (codes::NAME, Some(matches))
+                if self.has_builtin(codes::NAME, !self.builtins.explanations.is_empty()) =>
+            {
+                let _ = write!(
+                    capture::stdout(),
+                    "{}",
+                    codes::explain(&self.builtins.explanations, matches)?
+                );
+                return Ok(());
+            }
+            (completions::NAME, Some(matches))
+                if self.has_builtin(completions::NAME, self.builtins.completions) =>
+            {
+                let backends = completions::backends(&self.builtins.completion_backends);
+                return completions::run(self.app(), matches, &backends);
+            }
+            (docs::NAME, Some(matches)) if self.has_builtin(docs::NAME, self.builtins.docs) => {
+                let _ = write!(capture::stdout(), "{}", docs::render(&self.app(), matches)?);
+                return Ok(());
+            }
+            (doctor::NAME, Some(matches))
+                if self.has_builtin(doctor::NAME, self.builtins.doctor) =>
+            {
+                let help = help.cmd(doctor::NAME).unwrap();
+
+                return doctor::run(
+                    &self.builtins.checks,
+                    &ctx.child(doctor::NAME, matches, &help),
+                );
+            }
+            #[cfg(feature = "self-update")]
+            (self_update::NAME, Some(matches))
+                if self.has_builtin(self_update::NAME, self.builtins.self_update.is_some()) =>
+            {
+                if let Some(self_update) = &self.builtins.self_update {
+                    return self_update.run(matches);
+                }
+            }
+            _ => {}
+        }
+
+        if let (name, Some(matches)) = matches.subcommand() {
+            if let Some(plugin) = self.find_plugin(name) {
+                let args: Vec<_> = match matches.values_of_os("args") {
+                    Some(values) => values.map(OsString::from).collect(),
+                    None => Vec::new(),
+                };
+
+                return plugin.run(&args);
+            }
+        }
+
+        let help_error = || {
+            let mut buf = Vec::new();
+
+            self.write_help(help, &[], &mut buf);
+
+            Err(ClapError::with_description(
+                &String::from_utf8(buf).unwrap(),
+                ClapErrorKind::HelpDisplayed,
+            ))
+        };
+
+        match &*self.no_cmd {
+            NoCmd::Help => help_error(),
+            NoCmd::Pick => {
+                let cmds: Vec<_> = self
+                    .cmds
+                    .iter()
+                    .map(|cmd| (cmd, cmd.stub()))
+                    .filter(|(_, app)| !app.p.is_set(AppSettings::Hidden))
+                    .collect();
+                let choices: Vec<_> = cmds
+                    .iter()
+                    .map(|(cmd, app)| (cmd.name(), app.p.meta.about))
+                    .collect();
+
+                match prompt::pick_on_terminal(&choices)? {
+                    Some(index) => run_cmd(&**cmds[index].0, matches),
+                    None => help_error(),
+                }
+            }
+            NoCmd::Default(name) => match self.find_cmd(name) {
+                Some(cmd) => run_cmd(cmd, matches),
+                None => Err(ClapError::with_description(
+                    &format!("No subcommand named `{}` to run by default", name),
+                    ClapErrorKind::InvalidSubcommand,
+                )),
+            },
+            NoCmd::Suggest => {
+                let names: Vec<_> = self
+                    .cmds
+                    .iter()
+                    .filter(|cmd| !cmd.stub().p.is_set(AppSettings::Hidden))
+                    .map(|cmd| cmd.name())
+                    .collect();
+
+                Err(match matches.subcommand_name() {
+                    Some(given) => {
+                        let mut msg = format!("The subcommand '{}' wasn't recognized", given);
+
+                        if let Some(closest) = names
+                            .iter()
+                            .map(|name| (edit_distance(given, name), name))
+                            .filter(|(distance, name)| *distance <= name.len() / 2 + 1)
+                            .min()
+                        {
+                            msg.push_str(&format!("\n\tDid you mean '{}'?", closest.1));
+                        }
+
+                        msg.push_str(&format!(
+                            "\n\nPossible subcommands are: {}",
+                            names.join(", ")
+                        ));
+                        ClapError::with_description(&msg, ClapErrorKind::UnrecognizedSubcommand)
+                    }
+                    None => ClapError::with_description(
+                        &format!(
+                            "A subcommand is required, possible subcommands are: {}",
+                            names.join(", ")
+                        ),
+                        ClapErrorKind::MissingSubcommand,
+                    ),
+                })
+            }
+            NoCmd::Run(no_cmd) => with_args(&mut |args| no_cmd(args, matches)),
+            NoCmd::Leftover(no_cmd) => {
+                let mut leftover = Vec::new();
+
+                if let (name, Some(external)) = matches.subcommand() {
+                    leftover.push(OsString::from(name));
+
+                    if let Some(values) = external.values_of_os("") {
+                        leftover.extend(values.map(OsString::from));
+                    }
+                }
+
+                with_args(&mut |args| no_cmd(args, &leftover, matches))
+            }
+        }
+    }
+
+    /// Checks the options of [`require_arg`](#method.require_arg),
+    /// [`exclusive_args`](#method.exclusive_args) and [`args_together`](#method.args_together)
+    fn check_global_args(
+        &self,
+        usages: &HashMap<&str, String>,
+        help: &Help<'_>,
+        matches: &ArgMatches<'_>,
+    ) -> StdResult<(), Failure> {
+        let usage = |name: &str| usages[name].as_str();
+
+        let missing: Vec<_> = self
+            .parsing
+            .required_args
+            .iter()
+            .filter(|name| !is_present(matches, name))
+            .map(|name| format!("    {}", usage(name)))
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(self.global_error(
+                help,
+                matches,
+                ClapError::with_description(
+                    &format!(
+                        "The following required arguments were not provided:\n{}",
+                        missing.join("\n")
+                    ),
+                    ClapErrorKind::MissingRequiredArgument,
+                ),
+            ));
+        }
+
+        for group in &self.parsing.arg_groups {
+            let (given, absent): (Vec<&str>, Vec<&str>) = group
+                .names()
+                .iter()
+                .partition(|name| is_present(matches, name));
+
+            let (msg, kind) = match group {
+                GlobalGroup::Exclusive(_) if given.len() > 1 => (
+                    format!(
+                        "The argument '{}' cannot be used with '{}'",
+                        usage(given[0]),
+                        usage(given[1])
+                    ),
+                    ClapErrorKind::ArgumentConflict,
+                ),
+                GlobalGroup::Together(_) if !given.is_empty() && !absent.is_empty() => (
+                    format!(
+                        "The argument '{}' requires '{}'",
+                        usage(given[0]),
+                        usage(absent[0])
+                    ),
+                    ClapErrorKind::MissingRequiredArgument,
+                ),
+                _ => continue,
+            };
+
+            return Err(self.global_error(help, matches, ClapError::with_description(&msg, kind)));
+        }
+
+        Ok(())
+    }
+
+    /// Fails with `error`, e.g. about global options, followed by the help of the deepest
+    /// command given like parse errors
+    fn global_error(&self, help: &Help<'_>, matches: &ArgMatches<'_>, error: ClapError) -> Failure {
+        let json = self.json_errors_requested(matches);
+        let mut help = help.clone();
+        let mut path = Vec::new();
+        let mut matches = matches;
+
+        while let (name, Some(sub)) = matches.subcommand() {
+            match help.cmd(name) {
+                Some(inner) => help = inner,
+                None => break,
+            }
+
+            path.push(name.to_owned());
+            matches = sub;
+        }
+
+        let mut buf = format!("{}\n\n", error.message).into_bytes();
+
+        self.write_help(&help, &[], &mut buf);
+
+        Failure {
+            error: ClapError {
+                message: String::from_utf8(buf).unwrap(),
+                kind: error.kind,
+                info: None,
+            },
+            path: CommandPath(path),
+            cause: Cause::Parsing,
+            json,
+        }
+    }
+
+    /// Whether the root is given `--json-errors` or `--output json`, when registered
+    fn json_errors_requested(&self, matches: &ArgMatches<'_>) -> bool {
+        (self.builtins.json_errors && matches.is_present("json-errors"))
+            || self.output_format(matches) == OutputFormat::Json
+    }
+
+    /// The format given to the root with `--output`, when registered
+    fn output_format(&self, matches: &ArgMatches<'_>) -> OutputFormat {
+        OutputFormat::from_name(matches.value_of("output").filter(|_| self.builtins.output))
+    }
+
+    fn write_help(&self, help: &Help<'_>, path: &[&str], out: &mut impl Write) {
+        let mut help = help.clone();
+
+        for &segment in path {
+            match help.cmd(segment) {
+                Some(inner) => help = inner,
+                None => unreachable!("Bad help structure (doesn't match with path)"),
+            }
+        }
+
+        out.write_all(&help.data()).unwrap();
+    }
+
+    fn multicall_persona(&self, args: &[OsString]) -> Option<(String, String)> {
+        let prefix = self.parsing.multicall?;
+        let bin_name = std::path::Path::new(args.first()?).file_name()?.to_str()?;
+        let stem = bin_name
+            .strip_suffix(std::env::consts::EXE_SUFFIX)
+            .unwrap_or(bin_name);
+        let name = stem.strip_prefix(prefix)?;
+
+        self.cmds.iter().find(|cmd| cmd.name() == name)?;
+
+        Some((bin_name.to_owned(), name.to_owned()))
+    }
+
+    pub fn into_cmd(self, name: &'a str) -> MultiCommand<'a, S, T> {
+        MultiCommand {
+            name,
+            desc: None,
+            cmd: self,
+        }
+    }
+}
+
+impl<'a> Commander<'a, (), ()> {
+    /// Builds the commands described by a [`Spec`](struct.Spec.html)
+    pub fn from_spec(spec: &'a Spec) -> Self {
+        let bindings = spec::Bindings::default();
+        let mut commander = Self::new();
+
+        for cmd in spec.cmds() {
+            commander = commander.add_cmd(spec::SpecCommand::new(cmd, "", bindings.clone()));
+        }
+
+        commander.parsing.spec = Some((spec, bindings));
+        commander
+    }
+
+    /// Runs `runner` for the command of the spec at the dotted `path`
+    pub fn bind(
+        self,
+        path: &str,
+        runner: impl Fn(&ArgMatches<'_>, &Context<'_>) -> Result + 'a,
+    ) -> Self {
+        match &self.parsing.spec {
+            Some((spec, bindings)) if spec.find(path).is_some() => {
+                bindings
+                    .borrow_mut()
+                    .insert(path.to_owned(), Rc::new(runner));
+            }
+            _ => panic!("No command `{}` in the spec to bind", path),
+        }
+
+        self
+    }
+}
+
+impl<'a, T: ?Sized> Commander<'a, (), T> {
+    /// Runs with the arguments of the process, expanding wildcards with the `wild` feature
+    pub fn run(&self) {
+        self.run_result().unwrap_or_else(|error| error.exit())
+    }
+
+    pub fn run_with_args(&self, args: impl IntoIterator<Item = impl Into<OsString> + Clone>) {
+        self.run_with_args_result(args)
+            .unwrap_or_else(|error| error.exit())
+    }
+
+    pub fn run_result(&self) -> Result {
+        self.run_with_args_result(env_args())
+    }
+
+    pub fn run_with_args_result(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> Result {
+        let args = args.into_iter().map(Into::into).collect();
+
+        match self
+            .execute_chain(args, &mut Timings::default())
+            .map_err(|chain| chain.failure)
+        {
+            Ok(_) => Ok(()),
+            Err(failure) if failure.cause == Cause::Parsing => Err(into_help(failure.error)),
+            Err(failure) => Err(failure.error),
+        }
+    }
+
+    pub fn run_status(&self) -> ExitStatus {
+        self.run_with_args_status(env_args())
+    }
+
+    pub fn run_with_args_status(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> ExitStatus {
+        let args = args.into_iter().map(Into::into).collect();
+
+        match self
+            .execute_chain(args, &mut Timings::default())
+            .map_err(|chain| chain.failure)
+        {
+            Ok(path) => ExitStatus::Ran(path),
+            Err(failure) if failure.cause == Cause::Parsing => {
+                ExitStatus::from_parse_error(failure.error)
+            }
+            Err(failure) => match failure.error.kind {
+                ClapErrorKind::HelpDisplayed => ExitStatus::HelpShown(failure.error.message),
+                ClapErrorKind::VersionDisplayed => ExitStatus::VersionShown,
+                _ if failure.cause == Cause::TimedOut => ExitStatus::TimedOut(failure.error),
+                _ => ExitStatus::RunnerError(failure.error),
+            },
+        }
+    }
+
+    /// Runs with the arguments of the process, then exits with the status
+    pub fn run_and_exit(&self) -> ! {
+        self.run_with_args_and_exit(env_args())
+    }
+
+    pub fn run_with_args_and_exit(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> ! {
+        std::process::exit(self.run_reported(args.into_iter().map(Into::into).collect()))
+    }
+
+    /// Runs capturing what's printed on this thread, without exiting
+    ///
+    /// What the threads spawned by runners write goes to the real streams.
+    pub fn run_capture(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> CapturedRun {
+        self.run_capture_with_stdin(args, Vec::new())
+    }
+
+    /// Like `run_capture`, with `stdin` given to `Context::read_stdin`
+    pub fn run_capture_with_stdin(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+        stdin: impl Into<Vec<u8>>,
+    ) -> CapturedRun {
+        let args = args.into_iter().map(Into::into).collect();
+
+        capture::run(stdin.into(), || self.run_reported(args))
+    }
+
+    /// Runs with the failure reported, returning the exit code
+    fn run_reported(&self, args: Vec<OsString>) -> i32 {
+        if let Some((path, mode)) = self.requested_script(&args) {
+            return self.report_script(&path, mode);
+        }
+
+        let mut timings = Timings::default();
+
+        let code = match self.execute_chain(args, &mut timings) {
+            Ok(_) => 0,
+            Err(chain) => {
+                let code = self.report(&chain.failure, chain.failure.json);
+
+                if chain.steps > 1 && chain.failure.error.use_stderr() {
+                    let _ = writeln!(
+                        capture::stderr(),
+                        "note: the chain stopped at step {} of {}",
+                        chain.step,
+                        chain.steps
+                    );
+                }
+
+                code
+            }
+        };
+
+        if timings.requested {
+            let _ = writeln!(capture::stderr(), "{}", timings);
+        }
+
+        code
+    }
+
+    /// Runs independent invocations on a thread per CPU, each with its own commander
+    pub fn run_parallel<I>(
+        make: impl Fn() -> Self + Sync,
+        invocations: impl IntoIterator<Item = I>,
+    ) -> Vec<ExitStatus>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let invocations: Vec<Vec<OsString>> = invocations
+            .into_iter()
+            .map(|args| args.into_iter().map(Into::into).collect())
+            .collect();
+        let next = AtomicUsize::new(0);
+        let statuses = Mutex::new(
+            std::iter::repeat_with(|| None)
+                .take(invocations.len())
+                .collect::<Vec<_>>(),
+        );
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .min(invocations.len());
+
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    let commander = make();
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+
+                        let args = match invocations.get(index) {
+                            Some(args) => args.clone(),
+                            None => break,
+                        };
+                        let status = commander.run_with_args_status(args);
+
+                        statuses.lock().unwrap_or_else(|err| err.into_inner())[index] =
+                            Some(status);
+                    }
+                });
+            }
+        });
+
+        statuses
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner())
+            .into_iter()
+            .map(|status| status.expect("Every invocation has run"))
+            .collect()
+    }
+
+    /// Runs the commands of a file, one per line
+    pub fn run_script(
+        &self,
+        path: impl AsRef<Path>,
+        mode: ScriptMode,
+    ) -> StdResult<Vec<(usize, ExitStatus)>, ClapError> {
+        let bin_name = std::env::args_os().next().unwrap_or_default();
+        let mut statuses = Vec::new();
+
+        for (number, line) in script::load(path.as_ref())? {
+            let status = match alias::split(&line) {
+                Ok(words) => self.run_with_args_status(
+                    std::iter::once(bin_name.clone()).chain(words.into_iter().map(OsString::from)),
+                ),
+                Err(err) => ExitStatus::ParseError(ClapError::with_description(
+                    &format!("Invalid command: {}", err),
+                    ClapErrorKind::InvalidValue,
+                )),
+            };
+            let failed = !status.is_success();
+
+            statuses.push((number, status));
+
+            if failed && mode == ScriptMode::StopOnError {
+                break;
+            }
+        }
+
+        Ok(statuses)
+    }
+
+    /// The path given to the root with `--script` and whether to `--keep-going`,
+    /// read from its matches once parsed since the words may also be arguments of a subcommand
+    fn requested_script(&self, args: &[OsString]) -> Option<(PathBuf, ScriptMode)> {
+        if !self.builtins.script || find_long_arg(args, "script").is_none() {
+            return None;
+        }
+
+        let (matches, _) = self
+            .parse_args(args.iter().cloned(), &mut Timings::default())
+            .ok()?;
+
+        let path = matches.value_of_os("script")?;
+        let mode = if matches.is_present("keep-going") {
+            ScriptMode::KeepGoing
+        } else {
+            ScriptMode::StopOnError
+        };
+
+        Some((path.into(), mode))
+    }
+
+    /// Runs a script for `--script`, returning the exit code of the last failure
+    fn report_script(&self, path: &Path, mode: ScriptMode) -> i32 {
+        let statuses = match self.run_script(path, mode) {
+            Ok(statuses) => statuses,
+            Err(err) => {
+                let _ = writeln!(capture::stderr(), "{}", err.message);
+                return self.exit_code_for(&err);
+            }
+        };
+
+        let mut code = 0;
+
+        for (number, status) in statuses {
+            let cause = match status {
+                ExitStatus::TimedOut(_) => Cause::TimedOut,
+                _ => Cause::Runner,
+            };
+
+            match status {
+                ExitStatus::HelpShown(help) => {
+                    let _ = writeln!(capture::stdout(), "{}", help);
+                }
+                ExitStatus::ParseError(err)
+                | ExitStatus::RunnerError(err)
+                | ExitStatus::TimedOut(err) => {
+                    let _ = writeln!(
+                        capture::stderr(),
+                        "{}:{}: {}",
+                        path.display(),
+                        number,
+                        err.message
+                    );
+                    code = self.exit_code(&err, cause);
+                }
+                ExitStatus::Ran(_) | ExitStatus::VersionShown => {}
+            }
+        }
+
+        code
+    }
+
+    /// Reads commands from stdin, running one per line
+    pub fn run_repl(&self, prompt: &str) -> Result {
+        let bin_name = std::env::args_os().next().unwrap_or_default();
+        let mut reader = repl::Reader::new(&self.app(), self.builtins.repl_history.clone())
+            .map_err(|err| ClapError::with_description(&err.to_string(), ClapErrorKind::Io))?;
+
+        while let Some(line) = reader
+            .read(prompt)
+            .map_err(|err| ClapError::with_description(&err.to_string(), ClapErrorKind::Io))?
+        {
+            let words = match alias::split(&line) {
+                Ok(words) => words,
+                Err(err) => {
+                    let _ = writeln!(capture::stderr(), "error: {}", err);
+                    continue;
+                }
+            };
+
+            match words.first().map(String::as_str) {
+                None => continue,
+                Some("exit") | Some("quit") => break,
+                Some(_) => {}
+            }
+
+            let args = std::iter::once(bin_name.clone())
+                .chain(words.into_iter().map(OsString::from))
+                .collect();
+
+            if let Err(failure) = self.execute(args, &mut Timings::default()) {
+                self.report(&failure, false);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints a failure like [`run_and_exit`](#method.run_and_exit) does, returning its exit code
+    fn report(&self, failure: &Failure, json: bool) -> i32 {
+        let err = &failure.error;
+        let code = self.exit_code(err, failure.cause);
+
+        // Help and version go to stdout, everything else is a failure.
+        if err.use_stderr() {
+            if json {
+                let _ = writeln!(
+                    capture::stderr(),
+                    "{}",
+                    report::json(err, &failure.path, code)
+                );
+            } else {
+                let _ = writeln!(capture::stderr(), "{}", err.message);
+
+                if let Some(code) = self.explained_code(err) {
+                    // Only the name of the root is needed, not its subcommands
+                    let app = self.build_app(Build::Shallow, &mut Built::default());
+                    let _ = writeln!(
+                        capture::stderr(),
+                        "\nFor more information about this error, try `{} {} {}`.",
+                        app.p.meta.bin_name.as_ref().unwrap_or(&app.p.meta.name),
+                        codes::NAME,
+                        code
+                    );
+                }
+            }
+        } else {
+            let _ = writeln!(capture::stdout(), "{}", err.message);
+        }
+
+        code
+    }
+
+    /// The code of an error when it has an explanation
+    fn explained_code(&self, err: &ClapError) -> Option<String> {
+        let code = err.code()?;
+
+        self.builtins
+            .explanations
+            .iter()
+            .any(|(known, _)| known.eq_ignore_ascii_case(&code))
+            .then_some(code)
+    }
+
+    /// Executes the invocations of a chain in order, stopping at the first failure
+    fn execute_chain(
+        &self,
+        args: Vec<OsString>,
+        timings: &mut Timings,
+    ) -> StdResult<CommandPath, ChainFailure> {
+        let _hook = self
+            .hooks
+            .friendly_panics
+            .as_ref()
+            .map(panic::HookGuard::install);
+        let steps = self.split_chain(args);
+        let count = steps.len();
+        let mut path = CommandPath::default();
+        // Setting variables isn't thread-safe, so the env file is loaded before any
+        // helper thread is spawned, an error sets nothing and is reported when parsing
+        let _ = self.load_env_file(&steps[0]);
+        let update = self
+            .builtins
+            .update_check
+            .as_ref()
+            .and_then(UpdateCheck::start);
+
+        for (index, args) in steps.into_iter().enumerate() {
+            path = self
+                .execute(args, timings)
+                .map_err(|failure| ChainFailure {
+                    failure,
+                    step: index + 1,
+                    steps: count,
+                })?;
+        }
+
+        if let Some(update) = update {
+            update.finish();
+        }
+
+        Ok(path)
+    }
+
+    /// Loads the `.env` file, or the one given with `--env-file`, where loading it
+    /// again is a no-op since the variables it sets are no longer unset
+    fn load_env_file(&self, args: &[OsString]) -> StdResult<(), ClapError> {
+        match &self.sources.env_file {
+            Some(path) => match find_long_arg(args, "env-file") {
+                Some(path) => dotenv::load(path.as_ref(), true),
+                None => dotenv::load(path, false),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Splits the arguments at the chain separator into one invocation per command,
+    /// each with the binary name and the options given before the first command
+    fn split_chain(&self, args: Vec<OsString>) -> Vec<Vec<OsString>> {
+        let separator = match self.parsing.chain {
+            Some(separator) => separator,
+            None => return vec![args],
+        };
+
+        let mut args = args.into_iter();
+        let bin_name: Vec<_> = args.next().into_iter().collect();
+        let mut steps = vec![Vec::new()];
+
+        for arg in args {
+            if arg == separator {
+                steps.push(Vec::new());
+            } else if let Some(step) = steps.last_mut() {
+                step.push(arg);
+            }
+        }
+
+        let shared: Vec<_> = steps[0]
+            .iter()
+            .take_while(|arg| self.cmds.iter().all(|cmd| **arg != *cmd.name()))
+            .cloned()
+            .collect();
+        let shared = if shared.len() < steps[0].len() {
+            shared
+        } else {
+            Vec::new()
+        };
+
+        steps
+            .into_iter()
+            .enumerate()
+            .filter(|(index, step)| *index == 0 || !step.is_empty())
+            .map(|(index, step)| {
+                let shared = if index == 0 { &[][..] } else { &shared[..] };
+                bin_name.iter().chain(shared).cloned().chain(step).collect()
+            })
+            .collect()
+    }
+
+    /// Parses then runs, recording the invocation in the audit log
+    fn execute(
+        &self,
+        args: Vec<OsString>,
+        timings: &mut Timings,
+    ) -> StdResult<CommandPath, Failure> {
+        let outcome = match self.parse_args(args.iter().cloned(), timings) {
+            Ok((matches, help)) => {
+                let start = Instant::now();
+                let path = CommandPath::from(&matches);
+
+                timings.requested |= self.builtins.timings && matches.is_present("timings");
+
+                let mut cause = Cause::Runner;
+                let result = self.run_matches(&matches, &help, &mut cause);
+
+                timings.run = start.elapsed();
+
+                match result {
+                    Ok(()) => Ok(path),
+                    Err(error) if cause == Cause::Invalid => {
+                        Err(self.global_error(&help, &matches, error))
+                    }
+                    Err(error) => Err(Failure {
+                        error,
+                        path,
+                        cause,
+                        json: self.json_errors_requested(&matches),
+                    }),
+                }
+            }
+            Err(failure) => Err(Failure {
+                json: self.json_errors_given(self.root_args(&args)),
+                ..failure
+            }),
+        };
+        let secrets = self.secrets(&args);
+        let outcome = outcome.map_err(|mut failure| {
+            failure.error = secrets.mask_error(failure.error);
+
+            #[cfg(feature = "i18n")]
+            if let Some(translations) = &self.hooks.translations {
+                failure.error.message = translations.message(&failure.error.message);
+            }
+
+            failure
+        });
+
+        if let (Some(hook), Err(failure)) = (&self.hooks.on_crash, &outcome) {
+            if failure.error.use_stderr() {
+                hook(&CrashReport::new(
+                    &failure.error,
+                    &failure.path,
+                    failure.cause == Cause::Panicked,
+                    self.parsing.version,
+                ));
+            }
+        }
+
+        if let Some(audit_log) = &self.hooks.audit_log {
+            let args: Vec<OsString> = args
+                .iter()
+                .map(|arg| secrets.mask(&arg.to_string_lossy()).into())
+                .collect();
+
+            match &outcome {
+                Ok(path) => audit_log.record(&args, path, 0),
+                Err(failure) => audit_log.record(
+                    &args,
+                    &failure.path,
+                    self.exit_code(&failure.error, failure.cause),
+                ),
+            }
+        }
+
+        outcome
+    }
+
+    /// Renders an error like `--json-errors` does
+    pub fn error_json(&self, err: &ClapError, path: &CommandPath) -> String {
+        report::json(err, path, self.exit_code_for(err))
+    }
+
+    /// The values of the sensitive arguments on the command line, even when it doesn't parse,
+    /// and in their environment variables
+    fn secrets(&self, args: &[OsString]) -> redact::Secrets {
+        if self.parsing.sensitive.is_empty() {
+            return redact::Secrets::default();
+        }
+
+        // Only the commands given may have their sensitive arguments on the command line
+        let words = given_words(args, &self.multicall_persona(args));
+        let app = self.root_app(Build::Along(&words), &mut Built::default());
+        let mut env_names = redact::env_names(&app, &self.parsing.sensitive);
+        let mut secrets =
+            redact::Secrets::from_args(&redact::options(&app, &self.parsing.sensitive), args);
+
+        if let Some(prefix) = self.sources.env_prefix {
+            env_names.extend(
+                self.parsing
+                    .sensitive
+                    .iter()
+                    .map(|name| config::env_name(prefix, name).into()),
+            );
+        }
+
+        secrets.extend(redact::Secrets::from_env(&env_names));
+        secrets
+    }
+
+    /// Like [`json_errors_requested`](#method.json_errors_requested) for the arguments
+    /// given to the root when they don't parse
+    fn json_errors_given(&self, args: &[OsString]) -> bool {
+        (self.builtins.json_errors && has_long_flag(args, "json-errors"))
+            || (self.builtins.output
+                && find_long_arg(args, "output").is_some_and(|format| format == "json"))
+    }
+
+    /// The arguments given before the first subcommand, the binary name included,
+    /// which are the root's
+    fn root_args<'x>(&self, args: &'x [OsString]) -> &'x [OsString] {
+        let end = args
+            .iter()
+            .skip(1)
+            .position(|arg| arg.to_str().is_some_and(|arg| self.index.contains_key(arg)))
+            .map_or(args.len(), |position| position + 1);
+
+        &args[..end]
+    }
+
+    fn run_matches(&self, matches: &ArgMatches<'_>, help: &Help<'_>, cause: &mut Cause) -> Result {
+        let _lock = self
+            .hooks
+            .single_instance
+            .map(instance::Lock::acquire)
+            .transpose()?;
+        let _chdir = workdir::Chdir::from_matches(matches)?;
+        let mut config = config::load(self.sources.config, &self.sources.config_files)?;
+
+        if let Some(prefix) = self.sources.env_prefix {
+            config.set_env_prefix(prefix);
+        }
+
+        let _environment = environment::Scope::enter(|| Environment::loaded(config.clone()));
+        let cancellation = CancellationToken::default();
+        let cleanups = interrupt::Cleanups::default();
+
+        #[cfg(feature = "ctrlc")]
+        let _signals = self
+            .hooks
+            .on_interrupt
+            .clone()
+            .map(|hook| {
+                signal::Guard::install(
+                    CommandPath::from(matches),
+                    hook,
+                    cancellation.clone(),
+                    cleanups.clone(),
+                )
+            })
+            .transpose()?;
+        let _cleanups = interrupt::CleanupGuard(cleanups.clone());
+
+        let ctx = Context::new(
+            self.sources.config.unwrap_or(help.app_name()),
+            matches,
+            help,
+            &config,
+            cancellation,
+            cleanups,
+            &self.services,
+        )
+        .with_color(ColorChoice::resolve(
+            matches.value_of("color").filter(|_| self.builtins.color),
+        ))
+        .with_output(self.output_format(matches));
+
+        if self.builtins.verbosity {
+            let logging = self.builtins.logging.clone().unwrap_or_default();
+            logging.init(ctx.verbosity(), ctx.global_value("log-format"));
+        }
+
+        let start = Instant::now();
+        let result = if self.hooks.catch_panics || self.hooks.on_crash.is_some() {
+            let run = AssertUnwindSafe(|| self.run_with_data(&(), matches, &ctx));
+            let caught = if self.hooks.catch_panics {
+                panic::catching(|| std::panic::catch_unwind(run))
+            } else {
+                std::panic::catch_unwind(run)
+            };
+
+            match caught {
+                Ok(result) => result,
+                Err(payload) => {
+                    let error = crash::panic_error(&*payload);
+
+                    if !self.hooks.catch_panics {
+                        if let Some(hook) = &self.hooks.on_crash {
+                            let path = CommandPath::from(matches);
+                            hook(&CrashReport::new(&error, &path, true, self.parsing.version));
+                        }
+
+                        std::panic::resume_unwind(payload)
+                    }
+
+                    *cause = Cause::Panicked;
+                    Err(error)
+                }
+            }
+        } else {
+            self.run_with_data(&(), matches, &ctx)
+        };
+
+        if let Some(halted) = ctx.take_cause() {
+            *cause = halted;
+        }
+
+        let secrets = redact::Secrets::from_matches(matches, &self.parsing.sensitive);
+        let result = result.map_err(|err| secrets.mask_error(err));
+
+        if let Some(hook) = &self.hooks.on_invocation {
+            let opted_out = ctx.global_flag("no-telemetry")
+                || std::env::var_os("DO_NOT_TRACK")
+                    .is_some_and(|value| !value.is_empty() && value != "0");
+
+            if !opted_out {
+                hook(
+                    &CommandPath::from(matches),
+                    &redact::matches(matches, &self.parsing.sensitive),
+                    &result,
+                    start.elapsed(),
+                );
+            }
+        }
+
+        result
+    }
+
+    /// Resolves the arguments into a command path and matches without running
+    pub fn parse(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> StdResult<(CommandPath, ArgMatches<'_>), ClapError> {
+        let (matches, _help) = self.get_matches(args).map_err(into_help)?;
+        Ok((CommandPath::from(&matches), matches))
+    }
+
+    fn get_matches(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> StdResult<(ArgMatches<'_>, Help<'_>), ClapError> {
+        self.parse_args(args, &mut Timings::default())
+            .map_err(|failure| failure.error)
+    }
+
+    fn parse_args(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+        timings: &mut Timings,
+    ) -> StdResult<(ArgMatches<'_>, Help<'_>), Failure> {
+        let start = Instant::now();
+        let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+
+        if let Some(preprocess) = &self.parsing.preprocess {
+            args = preprocess(args);
+        }
+
+        if self.parsing.response_files {
+            args = response::expand(args)?;
+        }
+
+        // Loaded first so the options of every command see the variables
+        self.load_env_file(&args)?;
+
+        if let Some(name) = self.parsing.cargo {
+            if args.get(1).is_some_and(|arg| arg == name) {
+                args.remove(1);
+            }
+        }
+
+        if !self.parsing.aliases.is_empty() || self.parsing.alias_file.is_some() {
+            let mut aliases = self.parsing.aliases.clone();
+
+            if let Some(path) = &self.parsing.alias_file {
+                aliases.extend(alias::load(path)?);
+            }
+
+            alias::expand(&mut args, &aliases, |name| {
+                self.index.contains_key(name) || self.find_plugin(name).is_some()
+            })?;
+        }
+
+        if args.get(1).is_some_and(|arg| arg == "help") {
+            if let Some(term) = find_long_arg(&args, "search") {
+                return Err(self.search_help(&term.to_string_lossy()).into());
+            }
+        }
+
+        let persona = self.multicall_persona(&args);
+
+        if !self.parsing.lazy {
+            return self.parse_with(self.app(), args, &persona, start, timings);
+        }
+
+        // Only the subcommands given are built, everything is when the parse proves it wrong
+        let words = given_words(&args, &persona);
+        let mut built = Built::default();
+        let app = self.root_app(Build::Along(&words), &mut built);
+        let result = self.parse_with(app, args.clone(), &persona, start, timings);
+        let complete = match &result {
+            Ok((matches, _)) => built.covers(matches),
+            Err(_) => !built.inferred(),
+        };
+
+        if complete {
+            return result;
+        }
+
+        self.parse_with(self.app(), args, &persona, start, timings)
+    }
+
+    /// Where an unknown word of the command line is a subcommand among the ones built
+    /// for the parse, e.g. `program show foo` for `foo` given at the top
+    fn misplaced_suggestion(
+        help: &Help<'_>,
+        bin_name: &str,
+        kind: &clap::ErrorKind,
+        info: &Option<Vec<String>>,
+    ) -> Option<String> {
+        match kind {
+            clap::ErrorKind::UnknownArgument
+            | clap::ErrorKind::InvalidSubcommand
+            | clap::ErrorKind::UnrecognizedSubcommand => {}
+            _ => return None,
+        }
+
+        let word = info.as_ref()?.first()?;
+
+        if word.starts_with('-') {
+            return None;
+        }
+
+        let found = help.paths_to(word);
+
+        if found.is_empty() {
+            return None;
+        }
+
+        let paths: Vec<_> = found
+            .iter()
+            .map(|path| format!("{} {}", bin_name, path))
+            .collect();
+
+        Some(paths.join("' or '"))
+    }
+
+    /// The name of the binary as run by users, e.g. `cargo deploy` for cargo subcommands
+    fn shown_bin_name(&self, app: &App<'_, '_>) -> String {
+        match self.parsing.cargo {
+            Some(name) => format!("cargo {}", name),
+            None => app
+                .p
+                .meta
+                .bin_name
+                .clone()
+                .unwrap_or_else(|| app.p.meta.name.clone()),
+        }
+    }
+
+    /// What `help --search <TERM>` prints, the commands matching with their descriptions
+    fn search_help(&self, term: &str) -> ClapError {
+        let app = self.app();
+        let bin_name = self.shown_bin_name(&app);
+        let mut found = Vec::new();
+
+        search_tree(&app, &term.to_lowercase(), &mut Vec::new(), &mut found);
+
+        if found.is_empty() {
+            return ClapError::with_description(
+                &format!("No command matches '{}'", term),
+                ClapErrorKind::InvalidValue,
+            );
+        }
+
+        let lines: Vec<_> = found
+            .iter()
+            .map(|(path, about)| (format!("{} {}", bin_name, path), about))
+            .collect();
+        let width = lines.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+        let mut message = String::new();
+
+        for (path, about) in lines {
+            let line = format!("    {:width$}    {}", path, about, width = width);
+
+            message.push_str(line.trim_end());
+            message.push('\n');
+        }
+
+        ClapError {
+            message: message.trim_end().to_owned(),
+            kind: ClapErrorKind::HelpDisplayed,
+            info: None,
+        }
+    }
+
+    fn parse_with<'b>(
+        &'b self,
+        mut app: App<'b, 'b>,
+        mut args: Vec<OsString>,
+        persona: &Option<(String, String)>,
+        start: Instant,
+        timings: &mut Timings,
+    ) -> StdResult<(ArgMatches<'b>, Help<'b>), Failure> {
+        if let Some(name) = self.parsing.cargo {
+            if app.p.meta.bin_name.is_none() {
+                app.p.meta.bin_name = Some(format!("cargo {}", name));
+            }
+        }
+
+        // Infer binary name
+        if let Some(name) = args.first() {
+            let path = std::path::Path::new(name);
+
+            // Only shown in usage lines, so a lossy conversion is fine
+            if let Some(filename) = path.file_name() {
+                if app.p.meta.bin_name.is_none() {
+                    app.p.meta.bin_name = Some(filename.to_string_lossy().into_owned());
+                }
+            }
+        }
+
+        // The persona subcommand is renamed after the binary and becomes the root of usage lines,
+        // its original name is restored in the matches and the help structure.
+        if let Some((bin_name, name)) = &persona {
+            args.insert(1, bin_name.into());
+
+            for subcmd in &mut app.p.subcommands {
+                if subcmd.p.meta.name == *name {
+                    subcmd.p.meta.name = bin_name.clone();
+                    subcmd.p.meta.bin_name = Some(bin_name.clone());
+                }
+            }
+        }
+
+        fn propagate_author<'a>(app: &mut App<'_, 'a>, author: &'a str) {
+            app.p.meta.author = Some(author);
+
+            for subcmd in &mut app.p.subcommands {
+                propagate_author(subcmd, author);
+            }
+        }
+
+        fn propagate_version<'a>(app: &mut App<'_, 'a>, version: Option<&'a str>) {
+            for subcmd in &mut app.p.subcommands {
+                match version {
+                    Some(version) => subcmd.p.meta.version = Some(version),
+                    None => {
+                        subcmd.p.meta.version = None;
+                        subcmd.p.set(AppSettings::DisableVersion);
+                    }
+                }
+
+                propagate_version(subcmd, version);
+            }
+        }
+
+        // Nested commanders have their own version, so it is overridden explicitly
+        if self.parsing.global_version {
+            app.p.set(AppSettings::GlobalVersion);
+
+            if let Some(version) = app.p.meta.version {
+                propagate_version(&mut app, Some(version));
+            }
+        } else {
+            propagate_version(&mut app, None);
+        }
+
+        // `clap` colors its messages while parsing, so the root's choice is read before
+        if self.builtins.color {
+            let color = find_long_arg(self.root_args(&args), "color");
+            app = app.global_setting(
+                ColorChoice::resolve(color.as_ref().and_then(|c| c.to_str())).setting(),
+            );
+        }
+
+        // What `clap` only does for the subcommands it parses, needed for the help of all
+        fn propagate_help_and_bin_names(app: &mut App<'_, '_>) {
+            app.p.create_help_and_version();
+
+            for subcmd in &mut app.p.subcommands {
+                if subcmd.p.meta.bin_name.is_none() {
+                    // Like `clap` does when parsing
+                    subcmd.p.meta.bin_name = Some(match &app.p.meta.bin_name {
+                        Some(bin_name) => format!("{} {}", bin_name, subcmd.p.meta.name),
+                        None => subcmd.p.meta.name.clone(),
+                    });
+                }
+
+                propagate_help_and_bin_names(subcmd);
+            }
+        }
+
+        propagate_help_and_bin_names(&mut app);
+        app.p.propagate_globals();
+        app.p.propagate_settings();
+        app.p.set(AppSettings::Propagated);
+
+        // Also propagate author to subcommands since `clap` doesn't do it
+        if let Some(author) = app.p.meta.author {
+            propagate_author(&mut app, author);
+        }
+
+        fn env_fallback<'b>(app: &mut App<'b, '_>, prefix: &str, names: &'b HashSet<String>) {
+            // `clap` borrows environment variable names for as long as the app lives
+            fn name<'b>(prefix: &str, arg: &str, names: &'b HashSet<String>) -> Option<&'b OsStr> {
+                names.get(&config::env_name(prefix, arg)).map(OsStr::new)
+            }
+
+            for opt in &mut app.p.opts {
+                if opt.v.env.is_none() {
+                    if let Some(name) = name(prefix, opt.b.name, names) {
+                        opt.v.env = Some((name, std::env::var_os(name)));
+                    }
+                }
+            }
+
+            for (_, pos) in &mut app.p.positionals {
+                if pos.v.env.is_none() {
+                    if let Some(name) = name(prefix, pos.b.name, names) {
+                        pos.v.env = Some((name, std::env::var_os(name)));
+                    }
+                }
+            }
+
+            for subcmd in &mut app.p.subcommands {
+                env_fallback(subcmd, prefix, names);
+            }
+        }
+
+        fn env_names(app: &App<'_, '_>, prefix: &str, names: &mut HashSet<String>) {
+            let args = app.p.opts.iter().map(|opt| opt.b.name);
+            let args = args.chain(app.p.positionals.values().map(|pos| pos.b.name));
+
+            names.extend(args.map(|arg| config::env_name(prefix, arg)));
+
+            for subcmd in &app.p.subcommands {
+                env_names(subcmd, prefix, names);
+            }
+        }
+
+        if let Some(prefix) = self.sources.env_prefix {
+            // Named once for the whole tree, since only the given subcommands are built here
+            let names = self.env_names.get_or_init(|| {
+                let mut names = HashSet::new();
+                env_names(&self.app(), prefix, &mut names);
+                names
+            });
+
+            env_fallback(&mut app, prefix, names);
+        }
+
+        redact::hide(&mut app, &self.parsing.sensitive);
+
+        let help_start = Instant::now();
+        let renamed = persona
+            .as_ref()
+            .map(|(bin_name, name)| (name.clone(), bin_name.clone()));
+        let help = Help::new(app.clone(), renamed);
+        let shown_bin_name = self.shown_bin_name(&app);
+
+        timings.help = help_start.elapsed();
+
+        if persona.is_some() {
+            // An empty first argument stops `clap` from inferring the binary name again
+            app.p.meta.bin_name = None;
+            args[0] = OsString::new();
+        }
+
+        let bin_name = app.p.meta.bin_name.clone();
+
+        // Listed like `clap` does, e.g. `--env <ENV>`
+        let usages: HashMap<&str, String> = self
+            .parsing
+            .required_args
+            .iter()
+            .chain(
+                self.parsing
+                    .arg_groups
+                    .iter()
+                    .flat_map(|group| group.names()),
+            )
+            .map(|&name| (name, arg_usage(&app, name)))
+            .collect();
+
+        let result = app.get_matches_from_safe(args);
+
+        timings.parse = start.elapsed() - timings.help;
+
+        match result {
+            Ok(mut matches) => {
+                if let (Some((_, name)), Some(subcmd)) = (&persona, &mut matches.subcommand) {
+                    subcmd.name = name.clone();
+                }
+
+                self.check_global_args(&usages, &help, &matches)?;
+
+                Ok((matches, help))
+            }
+            Err(err) => match err.kind {
+                clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => {
+                    Err(err.into())
+                }
+                // The message is already the help of the command missing a subcommand
+                clap::ErrorKind::MissingArgumentOrSubcommand => {
+                    Err(ClapError::with_description(err.message.trim_end(), err.kind).into())
+                }
+                _ => {
+                    let mut msg = err.message;
+                    let mut buf = Vec::new();
+                    let mut help_path = None;
+
+                    if let Some(suggestion) =
+                        Self::misplaced_suggestion(&help, &shown_bin_name, &err.kind, &err.info)
+                    {
+                        if !msg.contains("Did you mean") {
+                            if let Some(index) = msg.find("\n\n") {
+                                msg.insert_str(
+                                    index + 1,
+                                    &format!("\tDid you mean '{}'?\n", suggestion),
+                                );
                             }
+                        }
+                    }
 
-                            let mut path: Vec<_> = usage.split_whitespace().collect();
+                    if let Some(index) = msg.find("\nUSAGE") {
+                        let usage = msg.split_off(index);
+                        let mut lines = usage.lines();
+
+                        buf.extend_from_slice(msg.as_bytes());
+                        buf.push(b'\n');
+
+                        lines.next();
+                        lines.next();
 
-                            if path.len() > 0 {
-                                path.remove(0);
+                        if let Some(usage) = lines.next() {
+                            let mut usage = usage.trim();
+
+                            if let Some(index) = usage.find(['[', '<']) {
+                                usage = &usage[..index];
+                            }
+
+                            let mut path = Vec::new();
+
+                            // Usage lines start with the binary name, which may contain spaces
+                            let rest = match &persona {
+                                Some((bin_name, name)) => {
+                                    path.push(name.as_str());
+                                    usage.get(bin_name.len()..)
+                                }
+                                None => bin_name
+                                    .as_ref()
+                                    .and_then(|bin_name| usage.strip_prefix(bin_name.as_str())),
+                            };
+
+                            let rest = rest.or_else(|| {
+                                let first = usage.split_whitespace().next()?;
+                                Some(&usage[first.len()..])
+                            });
+
+                            if let Some(rest) = rest {
+                                // Required options are listed right after the command names
+                                path.extend(
+                                    rest.split_whitespace()
+                                        .take_while(|segment| !segment.starts_with('-')),
+                                );
                                 self.write_help(&help, &path, &mut buf);
-                                help_captured = true;
+                                help_path = Some(CommandPath(
+                                    path.iter().map(|segment| segment.to_string()).collect(),
+                                ));
                             }
                         }
                     }
 
-                    if help_captured {
-                        Err(ClapError::with_description(
-                            &String::from_utf8(buf).unwrap(),
-                            ClapErrorKind::HelpDisplayed,
-                        ))
+                    if let Some(path) = help_path {
+                        Err(Failure {
+                            error: ClapError::with_description(
+                                &String::from_utf8(buf).unwrap(),
+                                err.kind,
+                            ),
+                            path,
+                            cause: Cause::Parsing,
+                            json: false,
+                        })
                     } else {
-                        unreachable!("The help message from clap is missing a usage section.");
+                        // e.g. the errors of validators, which have no usage section
+                        Err(Failure {
+                            error: ClapError {
+                                message: format!(
+                                    "{}\n\nFor more information try --help",
+                                    msg.trim_end()
+                                ),
+                                kind: err.kind,
+                                info: err.info,
+                            },
+                            path: CommandPath::default(),
+                            cause: Cause::Parsing,
+                            json: false,
+                        })
                     }
                 }
             },
@@ -460,6 +3537,368 @@ impl<'a, T: ?Sized> Commander<'a, (), T> {
     }
 }
 
+/// Runs a command in a `command` span with its `path` (e.g. `show.foo`),
+/// recording the `outcome` and `duration_ms`, inside the span of its parent
+/// (e.g. `show`) for the nested ones
+#[cfg(feature = "tracing")]
+fn instrument(ctx: &Context<'_>, run: impl FnOnce() -> Result) -> Result {
+    let span = tracing::info_span!(
+        "command",
+        path = %ctx.path().join("."),
+        outcome = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+    );
+    let _entered = span.enter();
+    let start = std::time::Instant::now();
+    let result = run();
+
+    span.record("duration_ms", start.elapsed().as_millis() as u64);
+    span.record("outcome", if result.is_ok() { "ok" } else { "error" });
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+fn instrument(_ctx: &Context<'_>, run: impl FnOnce() -> Result) -> Result {
+    run()
+}
+
+/// An error along with the path of the command it happened in,
+/// which converts from parse errors
+struct Failure {
+    error: ClapError,
+    path: CommandPath,
+    cause: Cause,
+    /// Whether the root was asked to report it as JSON
+    json: bool,
+}
+
+/// How an invocation failed, which decides how it's reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cause {
+    /// The arguments didn't parse, the error carries the help
+    Parsing,
+    /// A runner returned an error
+    Runner,
+    /// A runner panicked, the error carries the message of the panic
+    Panicked,
+    /// A runner didn't return before its deadline
+    TimedOut,
+    /// [`Command::validate`](struct.Command.html#method.validate) refused the matches,
+    /// which fails like parsing does
+    Invalid,
+}
+
+impl From<ClapError> for Failure {
+    fn from(error: ClapError) -> Self {
+        Self {
+            error,
+            path: CommandPath::default(),
+            cause: Cause::Parsing,
+            json: false,
+        }
+    }
+}
+
+/// Global options checked together before dispatch
+#[derive(Clone, Copy)]
+enum GlobalGroup<'a> {
+    Exclusive(&'a [&'a str]),
+    Together(&'a [&'a str]),
+}
+
+impl<'a> GlobalGroup<'a> {
+    fn names(&self) -> &'a [&'a str] {
+        match self {
+            GlobalGroup::Exclusive(names) | GlobalGroup::Together(names) => names,
+        }
+    }
+}
+
+/// A failure in one of the invocations of a chain, which has only one by default
+struct ChainFailure {
+    failure: Failure,
+    step: usize,
+    steps: usize,
+}
+
+/// The arguments of the process, with wildcards expanded on Windows with the `wild` feature
+/// since its shells leave them to programs, unlike the shells of Unix
+#[cfg(feature = "wild")]
+fn env_args() -> impl Iterator<Item = OsString> {
+    wild::args_os()
+}
+
+#[cfg(not(feature = "wild"))]
+fn env_args() -> impl Iterator<Item = OsString> {
+    std::env::args_os()
+}
+
+/// The arguments after the binary name, which name the subcommands to build,
+/// preceded by the subcommand of the multicall persona
+fn given_words(args: &[OsString], persona: &Option<(String, String)>) -> Vec<OsString> {
+    let mut words = args.get(1..).unwrap_or_default().to_vec();
+
+    if let Some((_, name)) = persona {
+        words.insert(0, name.into());
+    }
+
+    words
+}
+
+/// Whether the words given to a commander may ask for its help, which lists all subcommands
+fn asks_for_help(words: &[OsString]) -> bool {
+    words
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "help" || arg == "--help" || arg == "-h")
+}
+
+/// The name and aliases of a subcommand
+fn cmd_names<T: ?Sized>(cmd: &(dyn CommandLike<T> + '_)) -> Vec<String> {
+    lazy::names(&cmd.stub()).map(str::to_owned).collect()
+}
+
+/// Whether an argument is given to the command of the matches or any of its subcommands
+fn is_present(matches: &ArgMatches<'_>, name: &str) -> bool {
+    matches.is_present(name)
+        || matches
+            .subcommand()
+            .1
+            .is_some_and(|sub| is_present(sub, name))
+}
+
+/// Collects the space-separated paths of the subcommands of the tree named or aliased `name`
+fn subcommand_paths(
+    app: &App<'_, '_>,
+    name: &str,
+    path: &mut Vec<String>,
+    found: &mut Vec<String>,
+) {
+    for subcmd in &app.p.subcommands {
+        let meta = &subcmd.p.meta;
+
+        path.push(meta.name.clone());
+
+        let mut aliases = meta.aliases.iter().flatten().map(|(alias, _)| *alias);
+
+        if meta.name == name || aliases.any(|alias| alias == name) {
+            found.push(path.join(" "));
+        }
+
+        subcommand_paths(subcmd, name, path, found);
+        path.pop();
+    }
+}
+
+/// Collects the paths and descriptions of the visible subcommands of the tree
+/// whose name or description contains `term`, in lowercase
+fn search_tree<'t>(
+    app: &App<'_, 't>,
+    term: &str,
+    path: &mut Vec<String>,
+    found: &mut Vec<(CommandPath, &'t str)>,
+) {
+    for subcmd in &app.p.subcommands {
+        if subcmd.p.is_set(AppSettings::Hidden) {
+            continue;
+        }
+
+        let meta = &subcmd.p.meta;
+        let about = meta.about.unwrap_or_default();
+
+        path.push(meta.name.clone());
+
+        if meta.name.to_lowercase().contains(term) || about.to_lowercase().contains(term) {
+            found.push((CommandPath(path.clone()), about));
+        }
+
+        search_tree(subcmd, term, path, found);
+        path.pop();
+    }
+}
+
+/// The number of single-character edits turning `from` into `to`
+fn edit_distance(from: &str, to: &str) -> usize {
+    let to: Vec<char> = to.chars().collect();
+    let mut row: Vec<usize> = (0..=to.len()).collect();
+
+    for (i, a) in from.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b) in to.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(a != *b))
+                .min(row[j] + 1)
+                .min(above + 1);
+            diagonal = above;
+        }
+    }
+
+    row[to.len()]
+}
+
+/// How `clap` shows an argument of the app in errors, e.g. `--env <ENV>`
+fn arg_usage(app: &App<'_, '_>, name: &str) -> String {
+    let opt = app.p.opts.iter().find(|opt| opt.b.name == name);
+    let flag = app.p.flags.iter().find(|flag| flag.b.name == name);
+
+    match (opt, flag) {
+        (Some(opt), _) => opt.to_string(),
+        (_, Some(flag)) => flag.to_string(),
+        _ => name.to_owned(),
+    }
+}
+
+/// Whether a long flag is among the arguments
+fn has_long_flag(args: &[OsString], name: &str) -> bool {
+    args.iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg.to_str().and_then(|arg| arg.strip_prefix("--")) == Some(name))
+}
+
+/// Finds the value of a long option among the arguments, for the few
+/// which must be known before parsing
+fn find_long_arg(args: &[OsString], name: &str) -> Option<OsString> {
+    let mut args = args.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg = match arg.to_str() {
+            Some("--") => break,
+            Some(arg) => arg,
+            None => continue,
+        };
+
+        match arg
+            .strip_prefix("--")
+            .and_then(|arg| arg.strip_prefix(name))
+        {
+            Some("") => return args.next().cloned(),
+            Some(value) if value.starts_with('=') => return Some(value[1..].into()),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Parse errors carry the substituted help message, and have always been
+// reported as help by the `Result`-returning APIs.
+fn into_help(err: ClapError) -> ClapError {
+    match err.kind {
+        ClapErrorKind::HelpDisplayed | ClapErrorKind::VersionDisplayed => err,
+        _ => ClapError {
+            kind: ClapErrorKind::HelpDisplayed,
+            ..err
+        },
+    }
+}
+
+/// The outcome of running a [`Commander`](struct.Commander.html),
+/// as returned by [`run_status`](struct.Commander.html#method.run_status)
+#[derive(Debug)]
+pub enum ExitStatus {
+    /// A command ran successfully
+    Ran(CommandPath),
+    /// A help message was generated instead of running a command
+    HelpShown(String),
+    /// The version was printed instead of running a command
+    VersionShown,
+    /// The arguments could not be parsed, the error carries the substituted help
+    ParseError(ClapError),
+    /// A runner was matched but it failed
+    RunnerError(ClapError),
+    /// A runner was matched but it didn't return before its
+    /// [`cancel_after`](struct.Command.html#method.cancel_after) deadline
+    TimedOut(ClapError),
+}
+
+impl ExitStatus {
+    fn from_parse_error(err: ClapError) -> Self {
+        match err.kind {
+            ClapErrorKind::HelpDisplayed => ExitStatus::HelpShown(err.message),
+            ClapErrorKind::VersionDisplayed => ExitStatus::VersionShown,
+            _ => ExitStatus::ParseError(err),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        match self {
+            ExitStatus::Ran(_) | ExitStatus::HelpShown(_) | ExitStatus::VersionShown => true,
+            ExitStatus::ParseError(_) | ExitStatus::RunnerError(_) | ExitStatus::TimedOut(_) => {
+                false
+            }
+        }
+    }
+}
+
+/// The names of the subcommands matched, in order from the root
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommandPath(Vec<String>);
+
+impl CommandPath {
+    pub fn segments(&self) -> &[String] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a, 'b> From<&'b ArgMatches<'a>> for CommandPath {
+    fn from(mut matches: &'b ArgMatches<'a>) -> Self {
+        let mut segments = Vec::new();
+
+        while let (name, Some(inner)) = matches.subcommand() {
+            segments.push(name.to_owned());
+            matches = inner;
+        }
+
+        CommandPath(segments)
+    }
+}
+
+impl fmt::Display for CommandPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join(" "))
+    }
+}
+
+/// What a [`Commander`](struct.Commander.html) does
+/// when none of its subcommands match
+pub enum NoCmd<'a, T: ?Sized> {
+    /// Fails with the help message of the commander, the default
+    Help,
+    /// Lets the user pick the subcommand to run from a menu on a terminal,
+    /// or fails with the help message like [`Help`](#variant.Help) otherwise
+    Pick,
+    /// Runs the subcommand with the given name, failing when there is none
+    Default(&'a str),
+    /// Fails with an error listing the possible subcommands, suggesting the closest one
+    /// to the unrecognized subcommand given if any
+    Suggest,
+    /// Runs a closure with the parsed arguments
+    Run(NoCmdRunner<'a, T>),
+    /// Runs a closure with the unrecognized subcommand name followed by its arguments,
+    /// which is empty when no subcommand was given at all
+    Leftover(LeftoverRunner<'a, T>),
+}
+
+impl<'a, T: ?Sized> fmt::Debug for NoCmd<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoCmd::Help => f.write_str("Help"),
+            NoCmd::Pick => f.write_str("Pick"),
+            NoCmd::Default(name) => f.debug_tuple("Default").field(name).finish(),
+            NoCmd::Suggest => f.write_str("Suggest"),
+            NoCmd::Run(_) => f.write_str("Run(..)"),
+            NoCmd::Leftover(_) => f.write_str("Leftover(..)"),
+        }
+    }
+}
+
 /// The result of converting a [`Commander`](struct.Commander.html)
 /// into a higher-order command
 pub struct MultiCommand<'a, S: ?Sized, T: ?Sized> {
@@ -468,20 +3907,30 @@ pub struct MultiCommand<'a, S: ?Sized, T: ?Sized> {
     cmd: Commander<'a, S, T>,
 }
 
-impl<'a, S: ?Sized, T: ?Sized> MultiCommand<'a, S, T> {
-    pub fn description(mut self, desc: impl Into<&'a str>) -> Self {
-        self.desc = Some(desc.into());
-        self
+impl<'a, S: ?Sized, T: ?Sized> Clone for MultiCommand<'a, S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            desc: self.desc,
+            cmd: self.cmd.clone(),
+        }
     }
 }
 
-impl<'a, S: ?Sized, T: ?Sized> CommandLike<S> for MultiCommand<'a, S, T> {
-    fn name(&self) -> &str {
-        self.name
+impl<'a, S: ?Sized, T: ?Sized> fmt::Debug for MultiCommand<'a, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiCommand")
+            .field("name", &self.name)
+            .field("description", &self.desc)
+            .field("cmds", &self.cmd.cmds)
+            .field("no_cmd", &self.cmd.no_cmd)
+            .finish_non_exhaustive()
     }
+}
 
-    fn app(&self) -> App {
-        let mut app = self.cmd.app().name(self.name);
+impl<'a, S: ?Sized, T: ?Sized> MultiCommand<'a, S, T> {
+    fn named<'b>(&'b self, app: App<'b, 'b>) -> App<'b, 'b> {
+        let mut app = app.name(self.name);
 
         if let Some(desc) = self.desc {
             app = app.about(desc);
@@ -490,28 +3939,50 @@ impl<'a, S: ?Sized, T: ?Sized> CommandLike<S> for MultiCommand<'a, S, T> {
         app
     }
 
-    fn run(&self, args: &S, matches: &ArgMatches<'_>, help: &Help) -> Result {
-        self.cmd.run_with_data(args, matches, help)
+    pub fn description(mut self, desc: impl Into<&'a str>) -> Self {
+        self.desc = Some(desc.into());
+        self
     }
 }
 
-#[doc(hidden)]
-pub struct Help {
-    data: Vec<u8>,
-    cmds: HashMap<String, Help>,
-}
+impl<'a, S: ?Sized, T: ?Sized> CommandLike<S> for MultiCommand<'a, S, T> {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn app(&self) -> App<'_, '_> {
+        self.named(self.cmd.build_app(Build::All, &mut Built::default()))
+    }
 
-impl Help {
-    fn from(app: &App) -> Self {
-        let mut data = Vec::new();
-        let mut cmds = HashMap::new();
+    fn app_for(&self, args: &[OsString], built: &mut Built) -> App<'_, '_> {
+        self.named(self.cmd.build_app(Build::Along(args), built))
+    }
 
-        app.write_help(&mut data).unwrap();
+    fn stub(&self) -> App<'_, '_> {
+        self.named(self.cmd.build_app(Build::Shallow, &mut Built::default()))
+    }
 
-        for app in &app.p.subcommands {
-            cmds.insert(app.p.meta.name.clone(), Self::from(app));
-        }
+    fn trimmed_stub(&self) -> App<'_, '_> {
+        self.named(SubCommand::with_name(self.name))
+    }
 
-        Self { data, cmds }
+    fn confirms(&self) -> bool {
+        self.cmd.cmds.iter().any(|cmd| cmd.confirms())
     }
+
+    fn run(&self, args: &S, matches: &ArgMatches<'_>, ctx: &Context<'_>) -> Result {
+        self.cmd.run_with_data(args, matches, ctx)
+    }
+}
+
+/// What [`extend_with`](struct.Commander.html#method.extend_with) does
+/// with the subcommands and `no_cmd` both commanders have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    /// Panics, like [`extend`](struct.Commander.html#method.extend)
+    Panic,
+    /// Keeps the ones of the extended commander
+    Keep,
+    /// Takes the ones of the other commander
+    Replace,
 }
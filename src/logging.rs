@@ -0,0 +1,113 @@
+//! Logger initialization, with `tracing-subscriber` or `env_logger` depending on the features
+
+#[cfg(any(feature = "tracing-subscriber", feature = "env_logger"))]
+use capture;
+#[cfg(all(feature = "env_logger", not(feature = "tracing-subscriber")))]
+use {context, report};
+
+/// How [`init_logging`](struct.Commander.html#method.init_logging) sets up the logger,
+/// whose level comes from the verbosity flags
+#[derive(Clone, Debug, Default)]
+pub struct LogConfig {
+    format_flag: bool,
+    json: bool,
+    env_var: Option<String>,
+}
+
+impl LogConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a global `--log-format <text|json>` option
+    pub fn format_flag(mut self) -> Self {
+        self.format_flag = true;
+        self
+    }
+
+    /// Logs JSON lines by default
+    pub fn json(mut self) -> Self {
+        self.json = true;
+        self
+    }
+
+    /// Reads per-module directives from the given variable instead of `RUST_LOG`
+    pub fn env_var(mut self, name: impl Into<String>) -> Self {
+        self.env_var = Some(name.into());
+        self
+    }
+
+    pub(crate) fn has_format_flag(&self) -> bool {
+        self.format_flag
+    }
+
+    /// Sets up the logger once, later calls are ignored, writing to the standard error
+    /// unless it's captured by `Commander::run_capture`
+    #[cfg_attr(
+        not(any(feature = "tracing-subscriber", feature = "env_logger")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn init(&self, verbosity: i64, format: Option<&str>) {
+        let json = format.map_or(self.json, |format| format == "json");
+        let env_var = self.env_var.as_deref().unwrap_or("RUST_LOG");
+
+        #[cfg(feature = "tracing-subscriber")]
+        {
+            use tracing::level_filters::LevelFilter;
+            use tracing_subscriber::EnvFilter;
+
+            let level = match verbosity {
+                i64::MIN..=-2 => LevelFilter::OFF,
+                -1 => LevelFilter::ERROR,
+                0 => LevelFilter::WARN,
+                1 => LevelFilter::INFO,
+                2 => LevelFilter::DEBUG,
+                _ => LevelFilter::TRACE,
+            };
+
+            let filter = EnvFilter::builder()
+                .with_default_directive(level.into())
+                .with_env_var(env_var)
+                .from_env_lossy();
+
+            let builder = tracing_subscriber::fmt()
+                .with_env_filter(filter)
+                .with_writer(capture::stderr);
+
+            let _ = if json {
+                builder.json().try_init()
+            } else {
+                builder.try_init()
+            };
+        }
+
+        #[cfg(all(feature = "env_logger", not(feature = "tracing-subscriber")))]
+        {
+            use std::io::Write;
+
+            let mut builder = env_logger::Builder::new();
+
+            builder
+                .filter_level(context::log_level(verbosity))
+                .target(env_logger::Target::Pipe(Box::new(capture::stderr())));
+
+            if let Ok(directives) = std::env::var(env_var) {
+                builder.parse_filters(&directives);
+            }
+
+            if json {
+                builder.format(|buf, record| {
+                    writeln!(
+                        buf,
+                        "{{\"level\":\"{}\",\"target\":{},\"message\":{}}}",
+                        record.level(),
+                        report::string(record.target()),
+                        report::string(&record.args().to_string())
+                    )
+                });
+            }
+
+            let _ = builder.try_init();
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Typed values of arguments, see `MatchesExt`
+
+use std::fmt::Display;
+use std::result::Result as StdResult;
+use std::str::FromStr;
+
+use clap::{ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// Parses the values of arguments, e.g. `matches.get::<u16>("port")?`,
+/// failing with errors reported like the ones of clap
+pub trait MatchesExt {
+    /// The value of an argument, `None` when it isn't given and has no default
+    fn get<T>(&self, name: &str) -> StdResult<Option<T>, ClapError>
+    where
+        T: FromStr,
+        T::Err: Display;
+
+    /// All the values of an argument taking multiple values, empty when it isn't given
+    fn get_many<T>(&self, name: &str) -> StdResult<Vec<T>, ClapError>
+    where
+        T: FromStr,
+        T::Err: Display;
+}
+
+impl<'a> MatchesExt for ArgMatches<'a> {
+    fn get<T>(&self, name: &str) -> StdResult<Option<T>, ClapError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        Ok(self.get_many(name)?.into_iter().next())
+    }
+
+    fn get_many<T>(&self, name: &str) -> StdResult<Vec<T>, ClapError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let values = match self.values_of_os(name) {
+            Some(values) => values,
+            None => return Ok(Vec::new()),
+        };
+
+        values
+            .map(|value| {
+                let value = value.to_str().ok_or_else(|| {
+                    ClapError::with_description(
+                        &format!("The value of '{}' isn't valid UTF-8", name),
+                        ClapErrorKind::InvalidUtf8,
+                    )
+                })?;
+
+                value.parse().map_err(|err| {
+                    ClapError::with_description(
+                        &format!("Invalid value '{}' for '{}': {}", value, name, err),
+                        ClapErrorKind::ValueValidation,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn typed_values() {
+    use clap::{App, Arg};
+
+    let matches = App::new("program")
+        .arg(Arg::with_name("port").long("port").takes_value(true))
+        .arg(Arg::with_name("files").multiple(true))
+        .get_matches_from(["program", "--port", "8080", "a", "b"]);
+
+    assert_eq!(matches.get::<u16>("port").unwrap(), Some(8080));
+    assert_eq!(matches.get_many::<String>("files").unwrap(), ["a", "b"]);
+    assert_eq!(matches.get::<u16>("missing").unwrap(), None);
+
+    let err = matches.get_many::<u8>("files").unwrap_err();
+    assert_eq!(err.kind, ClapErrorKind::ValueValidation);
+    assert!(err.message.contains("Invalid value 'a' for 'files'"));
+}
@@ -0,0 +1,118 @@
+#[cfg(feature = "serde")]
+use std::result::Result as StdResult;
+
+#[cfg(feature = "serde")]
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+#[cfg(feature = "serde")]
+use table::Table;
+
+/// The format of values emitted by runners, from `--output <json|yaml|table>`,
+/// see [`with_output`](struct.Commander.html#method.with_output)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Table,
+}
+
+impl OutputFormat {
+    pub(crate) const VALUES: &'static [&'static str] = &[
+        "json",
+        #[cfg(feature = "yaml")]
+        "yaml",
+        "table",
+    ];
+
+    pub(crate) fn from_name(name: Option<&str>) -> Self {
+        match name {
+            Some("json") => OutputFormat::Json,
+            Some("yaml") => OutputFormat::Yaml,
+            _ => OutputFormat::Table,
+        }
+    }
+
+    /// Renders a value in this format, tables being made of the fields
+    /// of a list of objects, or of the keys and values of a single object
+    #[cfg(feature = "serde")]
+    pub fn render(self, value: &impl serde::Serialize) -> StdResult<String, ClapError> {
+        let rendered = match self {
+            OutputFormat::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            #[cfg(feature = "yaml")]
+            OutputFormat::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+            #[cfg(not(feature = "yaml"))]
+            OutputFormat::Yaml => Err("YAML output requires the `yaml` feature".to_owned()),
+            OutputFormat::Table => serde_json::to_value(value)
+                .map(|value| table(&value))
+                .map_err(|e| e.to_string()),
+        };
+
+        rendered
+            .map(|rendered| format!("{}\n", rendered.trim_end()))
+            .map_err(|err| {
+                ClapError::with_description(
+                    &format!("Failed to render the output: {}", err),
+                    ClapErrorKind::Format,
+                )
+            })
+    }
+}
+
+#[cfg(feature = "serde")]
+fn table(value: &serde_json::Value) -> String {
+    use serde_json::Value;
+
+    let table = match value {
+        Value::Array(items) => {
+            let mut headers: Vec<&String> = Vec::new();
+
+            for item in items {
+                if let Value::Object(fields) = item {
+                    for key in fields.keys() {
+                        if !headers.contains(&key) {
+                            headers.push(key);
+                        }
+                    }
+                }
+            }
+
+            let mut table = Table::new(headers.iter().map(|h| h.to_uppercase()).collect());
+
+            for item in items {
+                if headers.is_empty() {
+                    table.row(vec![cell(item)]);
+                } else {
+                    table.row(
+                        headers
+                            .iter()
+                            .map(|header| item.get(header.as_str()).map_or(String::new(), cell))
+                            .collect(),
+                    );
+                }
+            }
+
+            table
+        }
+        Value::Object(fields) => {
+            let mut table = Table::new(Vec::<String>::new());
+
+            for (key, value) in fields {
+                table.row(vec![key.clone(), cell(value)]);
+            }
+
+            table
+        }
+        value => return cell(value),
+    };
+
+    table.fit_terminal().to_string()
+}
+
+#[cfg(feature = "serde")]
+fn cell(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
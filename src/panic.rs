@@ -0,0 +1,194 @@
+//! Friendly panic messages pointing to a report file, in the spirit of `human-panic`
+
+use std::cell::Cell;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type Hook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+/// Who to contact about a crash, see
+/// [`friendly_panics`](struct.Commander.html#method.friendly_panics)
+#[derive(Clone, Debug, Default)]
+pub struct PanicMetadata {
+    name: String,
+    version: String,
+    authors: Option<String>,
+    homepage: Option<String>,
+}
+
+impl PanicMetadata {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn authors(mut self, authors: impl Into<String>) -> Self {
+        self.authors = Some(authors.into());
+        self
+    }
+
+    /// Where to submit reports, e.g. the issue tracker
+    pub fn homepage(mut self, homepage: impl Into<String>) -> Self {
+        self.homepage = Some(homepage.into());
+        self
+    }
+
+    fn report(&self, info: &PanicHookInfo<'_>) -> String {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.as_str()
+        } else {
+            "Unknown"
+        };
+
+        let mut report = String::new();
+
+        let _ = writeln!(report, "name = {:?}", self.name);
+        let _ = writeln!(report, "version = {:?}", self.version);
+        let _ = writeln!(
+            report,
+            "os = \"{} {}\"",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        let _ = writeln!(report, "message = {:?}", message);
+
+        if let Some(location) = info.location() {
+            let _ = writeln!(report, "location = \"{}\"", location);
+        }
+
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        let _ = writeln!(report, "backtrace = '''\n{}'''", backtrace);
+        report
+    }
+
+    fn write_report(&self, info: &PanicHookInfo<'_>) -> Option<PathBuf> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        let path =
+            std::env::temp_dir().join(format!("report-{}-{}.toml", std::process::id(), nanos));
+
+        std::fs::write(&path, self.report(info)).ok()?;
+        Some(path)
+    }
+
+    fn print(&self, info: &PanicHookInfo<'_>) {
+        let stderr = std::io::stderr();
+        let mut stderr = stderr.lock();
+
+        let _ = writeln!(stderr, "Well, this is embarrassing.\n");
+        let _ = writeln!(
+            stderr,
+            "{} had a problem and crashed. To help us diagnose the problem \
+             you can send us a crash report.\n",
+            self.name
+        );
+
+        match self.write_report(info) {
+            Some(path) => {
+                let _ = writeln!(
+                    stderr,
+                    "We have generated a report file at \"{}\". Submit an issue or email \
+                     with the subject of \"{} Crash Report\" and include the report \
+                     as an attachment.\n",
+                    path.display(),
+                    self.name
+                );
+            }
+            None => {
+                let _ = writeln!(stderr, "We failed to generate a report file.\n");
+            }
+        }
+
+        if let Some(homepage) = &self.homepage {
+            let _ = writeln!(stderr, "- Homepage: {}", homepage);
+        }
+
+        if let Some(authors) = &self.authors {
+            let _ = writeln!(stderr, "- Authors: {}", authors);
+        }
+
+        let _ = writeln!(stderr, "\nThank you kindly!");
+    }
+}
+
+/// The hook replaced by the friendly one, with the number of runs using the friendly one
+static INSTALLED: Mutex<(usize, Option<Hook>)> = Mutex::new((0, None));
+
+thread_local! {
+    /// Whether a panic of this thread is caught and returned as an error,
+    /// see [`catch_panics`](struct.Commander.html#method.catch_panics)
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `run` with the panics of the current thread left to the caller,
+/// which reports them as errors instead of crashes
+pub(crate) fn catching<R>(run: impl FnOnce() -> R) -> R {
+    let outer = CATCHING.with(|catching| catching.replace(true));
+    let result = run();
+
+    CATCHING.with(|catching| catching.set(outer));
+    result
+}
+
+/// Keeps the friendly hook installed for a run, restoring the previous hook once
+/// the last run using it, e.g. of a REPL, a script or `run_parallel`, is over
+pub(crate) struct HookGuard {
+    installed: bool,
+}
+
+impl HookGuard {
+    /// Installs the friendly hook, unless `RUST_BACKTRACE` asks for the usual output
+    pub(crate) fn install(metadata: &PanicMetadata) -> Self {
+        if std::env::var_os("RUST_BACKTRACE").is_some() {
+            return Self { installed: false };
+        }
+
+        let mut installed = INSTALLED.lock().unwrap_or_else(|err| err.into_inner());
+
+        if installed.0 == 0 {
+            let metadata = metadata.clone();
+
+            // Left installed by a run which ended unwinding, with the previous one kept
+            if installed.1.is_none() {
+                installed.1 = Some(panic::take_hook());
+            }
+
+            panic::set_hook(Box::new(move |info| {
+                if !CATCHING.with(Cell::get) {
+                    metadata.print(info);
+                }
+            }));
+        }
+
+        installed.0 += 1;
+        Self { installed: true }
+    }
+}
+
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        if !self.installed {
+            return;
+        }
+
+        let mut installed = INSTALLED.lock().unwrap_or_else(|err| err.into_inner());
+        installed.0 -= 1;
+
+        // Hooks can't be changed while unwinding, the friendly one then stays
+        if installed.0 == 0 && !std::thread::panicking() {
+            if let Some(previous) = installed.1.take() {
+                panic::set_hook(previous);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Discovery and execution of external plugins, git-style
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use Result;
+
+/// An executable named `<prefix><name>` found in `$PATH`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plugin {
+    name: String,
+    path: PathBuf,
+}
+
+impl Plugin {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Runs the plugin with the given arguments and waits for it to finish
+    pub fn run(&self, args: &[OsString]) -> Result {
+        let status = process::Command::new(&self.path)
+            .args(args)
+            .status()
+            .map_err(|err| {
+                ClapError::with_description(
+                    &format!("Failed to run {}: {}", self.path.display(), err),
+                    ClapErrorKind::Io,
+                )
+            })?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(ClapError::with_description(
+                &format!("Plugin `{}` failed with {}", self.name, status),
+                ClapErrorKind::Io,
+            ))
+        }
+    }
+}
+
+/// Lists the plugins with the given prefix, in `$PATH` order
+/// with the first one winning when names collide
+pub fn discover(prefix: &str) -> Vec<Plugin> {
+    match std::env::var_os("PATH") {
+        Some(paths) => discover_in(prefix, std::env::split_paths(&paths)),
+        None => Vec::new(),
+    }
+}
+
+/// Like [`discover`](fn.discover.html), looking in the given directories instead of `$PATH`
+pub fn discover_in(prefix: &str, dirs: impl IntoIterator<Item = PathBuf>) -> Vec<Plugin> {
+    let mut plugins: Vec<Plugin> = Vec::new();
+
+    for dir in dirs {
+        let entries = match dir.read_dir() {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+
+            let name = match plugin_name(prefix, &path) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            if is_executable(&path) && plugins.iter().all(|plugin| plugin.name != name) {
+                plugins.push(Plugin { name, path });
+            }
+        }
+    }
+
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+/// Looks up a single plugin by name in `$PATH`, without listing the directories
+pub fn find(prefix: &str, name: &str) -> Option<Plugin> {
+    let paths = std::env::var_os("PATH")?;
+
+    find_in(prefix, name, std::env::split_paths(&paths))
+}
+
+/// Like [`find`](fn.find.html), looking in the given directories instead of `$PATH`
+pub fn find_in(
+    prefix: &str,
+    name: &str,
+    dirs: impl IntoIterator<Item = PathBuf>,
+) -> Option<Plugin> {
+    let file_name = format!("{}{}{}", prefix, name, std::env::consts::EXE_SUFFIX);
+
+    dirs.into_iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+        .map(|path| Plugin {
+            name: name.to_owned(),
+            path,
+        })
+}
+
+fn plugin_name(prefix: &str, path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let stem = match std::env::consts::EXE_SUFFIX {
+        "" => file_name,
+        suffix => file_name.strip_suffix(suffix)?,
+    };
+    let name = stem.strip_prefix(prefix)?;
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_owned())
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
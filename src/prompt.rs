@@ -0,0 +1,156 @@
+//! Questions asked on the terminal, e.g. by `Command::confirm` and `NoCmd::Pick`
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::result::Result as StdResult;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// Asks `question` on stderr, refusing when nobody can answer it
+pub(crate) fn confirm_on_terminal(question: &str) -> StdResult<(), ClapError> {
+    if !io::stdin().is_terminal() {
+        return Err(ClapError::with_description(
+            &format!("{} Pass --yes to confirm without a terminal", question),
+            ClapErrorKind::MissingRequiredArgument,
+        ));
+    }
+
+    let confirmed =
+        confirm(question, &mut io::stdin().lock(), &mut io::stderr()).map_err(|err| {
+            ClapError::with_description(
+                &format!("Failed to read the answer: {}", err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+    if !confirmed {
+        return Err(ClapError::with_description(
+            "Aborted",
+            ClapErrorKind::InvalidValue,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Asks which of the `(name, description)` choices to take, `None` without a terminal
+/// or when the user gives no answer
+pub(crate) fn pick_on_terminal(
+    choices: &[(&str, Option<&str>)],
+) -> StdResult<Option<usize>, ClapError> {
+    if choices.is_empty() || !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(None);
+    }
+
+    pick(choices, &mut io::stdin().lock(), &mut io::stderr()).map_err(|err| {
+        ClapError::with_description(
+            &format!("Failed to read the answer: {}", err),
+            ClapErrorKind::Io,
+        )
+    })
+}
+
+/// Lists the choices with numbers and asks again until the answer is a number or a name
+fn pick(
+    choices: &[(&str, Option<&str>)],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Option<usize>> {
+    let width = choices
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+
+    writeln!(output, "Pick a subcommand:")?;
+
+    for (index, (name, desc)) in choices.iter().enumerate() {
+        match desc {
+            Some(desc) => writeln!(output, "  {}) {:width$}  {}", index + 1, name, desc)?,
+            None => writeln!(output, "  {}) {}", index + 1, name)?,
+        }
+    }
+
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+
+        if input.read_line(&mut answer)? == 0 || answer.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let answer = answer.trim();
+        let index = match answer.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).filter(|&index| index < choices.len()),
+            Err(_) => choices.iter().position(|(name, _)| *name == answer),
+        };
+
+        match index {
+            Some(index) => return Ok(Some(index)),
+            None => writeln!(output, "No subcommand `{}`", answer)?,
+        }
+    }
+}
+
+/// Reads a secret from the terminal without echoing it
+#[cfg(feature = "rpassword")]
+pub(crate) fn secret(label: &str) -> StdResult<String, ClapError> {
+    rpassword::prompt_password(format!("{}: ", label)).map_err(|err| {
+        ClapError::with_description(
+            &format!("Failed to read the {}: {}", label.to_lowercase(), err),
+            ClapErrorKind::Io,
+        )
+    })
+}
+
+/// Whether the answer to a `[y/N]` question is yes, which is never the default
+fn confirm(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<bool> {
+    write!(output, "{} [y/N] ", question)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[test]
+fn confirm_answers() {
+    let answer = |input: &str| {
+        let mut output = Vec::new();
+        let confirmed = confirm("Continue?", &mut input.as_bytes(), &mut output).unwrap();
+
+        assert_eq!(output, b"Continue? [y/N] ");
+        confirmed
+    };
+
+    assert!(answer("y\n"));
+    assert!(answer("Yes\n"));
+    assert!(!answer("\n"));
+    assert!(!answer("nope\n"));
+    assert!(!answer(""));
+}
+
+#[test]
+fn pick_choices() {
+    let choices = [("show", Some("Shows things")), ("deploy", None)];
+    let answer = |input: &str| {
+        let mut output = Vec::new();
+        let picked = pick(&choices, &mut input.as_bytes(), &mut output).unwrap();
+
+        (picked, String::from_utf8(output).unwrap())
+    };
+
+    let (picked, output) = answer("2\n");
+    assert_eq!(picked, Some(1));
+    assert_eq!(
+        output,
+        "Pick a subcommand:\n  1) show    Shows things\n  2) deploy\n> "
+    );
+
+    assert_eq!(answer("show\n").0, Some(0));
+    assert_eq!(answer("3\ndeploy\n").0, Some(1));
+    assert_eq!(answer("\n").0, None);
+    assert_eq!(answer("").0, None);
+}
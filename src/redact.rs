@@ -0,0 +1,210 @@
+//! Hiding the values of sensitive arguments, see `Commander::sensitive`
+
+use std::ffi::OsString;
+
+use clap::{App, ArgMatches, ArgSettings, Error as ClapError};
+
+pub(crate) const REDACTED: &str = "***";
+
+/// The values to hide wherever they show up in a message
+#[derive(Default)]
+pub(crate) struct Secrets(Vec<String>);
+
+impl Secrets {
+    /// The values of the sensitive arguments at every level of the matches,
+    /// including the ones from the environment and the defaults
+    pub(crate) fn from_matches(matches: &ArgMatches<'_>, names: &[&str]) -> Self {
+        let mut secrets = Secrets::default();
+        let mut matches = Some(matches);
+
+        while let Some(current) = matches {
+            for name in names {
+                let values = current.values_of_lossy(name).unwrap_or_default();
+                secrets.0.extend(values);
+            }
+
+            matches = current.subcommand().1;
+        }
+
+        secrets
+    }
+
+    /// The values following the sensitive options in the arguments, which may not parse
+    pub(crate) fn from_args(options: &[String], args: &[OsString]) -> Self {
+        let values = sanitize(options, args)
+            .into_iter()
+            .zip(args.iter().skip(1))
+            .filter_map(|(sanitized, arg)| {
+                let arg = arg.to_string_lossy();
+                let prefix = sanitized.strip_suffix(REDACTED)?;
+
+                Some(arg.get(prefix.len()..)?.to_owned()).filter(|_| sanitized != arg)
+            });
+
+        Secrets(values.collect())
+    }
+
+    /// The values of the environment variables which are set
+    pub(crate) fn from_env(names: &[OsString]) -> Self {
+        let values = names.iter().filter_map(std::env::var_os);
+
+        Secrets(
+            values
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect(),
+        )
+    }
+
+    pub(crate) fn extend(&mut self, other: Secrets) {
+        self.0.extend(other.0);
+    }
+
+    pub(crate) fn mask(&self, text: &str) -> String {
+        let mut secrets: Vec<_> = self.0.iter().filter(|secret| !secret.is_empty()).collect();
+
+        // Longer first, so that a secret containing another one is hidden entirely
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        secrets.into_iter().fold(text.to_owned(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+    }
+
+    pub(crate) fn mask_error(&self, mut err: ClapError) -> ClapError {
+        err.message = self.mask(&err.message);
+        err
+    }
+}
+
+/// The options naming the sensitive arguments at any level, e.g. `--token` and `-t`
+pub(crate) fn options(app: &App<'_, '_>, names: &[&str]) -> Vec<String> {
+    let mut found = Vec::new();
+
+    for opt in app.p.opts.iter().filter(|opt| names.contains(&opt.b.name)) {
+        found.extend(opt.s.long.map(|long| format!("--{}", long)));
+        found.extend(opt.s.short.map(|short| format!("-{}", short)));
+    }
+
+    for subcmd in &app.p.subcommands {
+        found.extend(options(subcmd, names));
+    }
+
+    found
+}
+
+/// The names of the environment variables of the sensitive arguments at any level
+pub(crate) fn env_names(app: &App<'_, '_>, names: &[&str]) -> Vec<OsString> {
+    let opts = app.p.opts.iter().map(|opt| (opt.b.name, &opt.v.env));
+    let positionals = app
+        .p
+        .positionals
+        .values()
+        .map(|pos| (pos.b.name, &pos.v.env));
+    let mut found: Vec<_> = opts
+        .chain(positionals)
+        .filter(|(name, _)| names.contains(name))
+        .filter_map(|(_, env)| env.as_ref().map(|(name, _)| name.to_os_string()))
+        .collect();
+
+    for subcmd in &app.p.subcommands {
+        found.extend(env_names(subcmd, names));
+    }
+
+    found
+}
+
+/// Stops the help from showing the values of the sensitive arguments
+/// taken from the environment or by default
+pub(crate) fn hide(app: &mut App<'_, '_>, names: &[&str]) {
+    for opt in &mut app.p.opts {
+        if names.contains(&opt.b.name) {
+            opt.b.set(ArgSettings::HideEnvValues);
+            opt.b.set(ArgSettings::HideDefaultValue);
+        }
+    }
+
+    for (_, pos) in &mut app.p.positionals {
+        if names.contains(&pos.b.name) {
+            pos.b.set(ArgSettings::HideEnvValues);
+            pos.b.set(ArgSettings::HideDefaultValue);
+        }
+    }
+
+    for subcmd in &mut app.p.subcommands {
+        hide(subcmd, names);
+    }
+}
+
+/// A copy of the matches with the values of the sensitive arguments redacted
+pub(crate) fn matches<'a>(matches: &ArgMatches<'a>, names: &[&str]) -> ArgMatches<'a> {
+    let mut matches = matches.clone();
+    let mut current = Some(&mut matches);
+
+    while let Some(level) = current {
+        for (name, arg) in &mut level.args {
+            if names.contains(name) {
+                arg.vals.iter_mut().for_each(|val| *val = REDACTED.into());
+            }
+        }
+
+        current = level.subcommand.as_mut().map(|subcmd| &mut subcmd.matches);
+    }
+
+    matches
+}
+
+/// The arguments without the binary name, with the values of the options hidden,
+/// e.g. `--password=***`
+pub(crate) fn sanitize(options: &[String], args: &[OsString]) -> Vec<String> {
+    let mut sanitized = Vec::new();
+    let mut redact_next = false;
+
+    for arg in args.iter().skip(1) {
+        let arg = arg.to_string_lossy();
+
+        if redact_next {
+            sanitized.push(REDACTED.to_owned());
+            redact_next = false;
+            continue;
+        }
+
+        if options.iter().any(|option| *option == arg) {
+            redact_next = true;
+            sanitized.push(arg.into_owned());
+            continue;
+        }
+
+        let inline = options
+            .iter()
+            .find(|option| match arg.strip_prefix(option.as_str()) {
+                Some(value) if option.starts_with("--") => value.starts_with('='),
+                Some(value) => !value.is_empty(),
+                None => false,
+            });
+
+        match inline {
+            Some(option) if option.starts_with("--") => {
+                sanitized.push(format!("{}={}", option, REDACTED))
+            }
+            Some(option) => sanitized.push(format!("{}{}", option, REDACTED)),
+            None => sanitized.push(arg.into_owned()),
+        }
+    }
+
+    sanitized
+}
+
+#[test]
+fn masked_values() {
+    let args: Vec<OsString> = ["program", "login", "--token", "abc", "-tdef", "--token=ghi"]
+        .iter()
+        .map(OsString::from)
+        .collect();
+    let options = ["--token".to_owned(), "-t".to_owned()];
+    let secrets = Secrets::from_args(&options, &args);
+
+    assert_eq!(secrets.0, ["abc", "def", "ghi"]);
+    assert_eq!(
+        secrets.mask("Invalid token 'abc' after 'def'"),
+        "Invalid token '***' after '***'"
+    );
+}
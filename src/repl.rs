@@ -0,0 +1,260 @@
+//! An interactive shell running one command per line, see `Commander::run_repl`
+
+#[cfg(not(feature = "rustyline"))]
+use std::io::BufRead;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::App;
+#[cfg(feature = "rustyline")]
+use clap::ArgSettings;
+
+#[cfg(feature = "rustyline")]
+use alias::split;
+
+/// Reads the lines of the shell, with line editing and history with the `rustyline` feature
+pub(crate) struct Reader {
+    #[cfg(feature = "rustyline")]
+    editor: rustyline::Editor<Completion, rustyline::history::DefaultHistory>,
+    #[cfg(feature = "rustyline")]
+    history: Option<PathBuf>,
+}
+
+impl Reader {
+    #[cfg_attr(not(feature = "rustyline"), allow(unused_variables))]
+    pub(crate) fn new(app: &App<'_, '_>, history: Option<PathBuf>) -> io::Result<Self> {
+        #[cfg(feature = "rustyline")]
+        {
+            let mut editor = rustyline::Editor::new().map_err(into_io)?;
+
+            editor.set_helper(Some(Completion(Node::from(app))));
+
+            if let Some(path) = &history {
+                // There is no history yet on the first run
+                let _ = editor.load_history(path);
+            }
+
+            Ok(Self { editor, history })
+        }
+
+        #[cfg(not(feature = "rustyline"))]
+        Ok(Self {})
+    }
+
+    /// The next line, or `None` at the end of the input
+    pub(crate) fn read(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        #[cfg(feature = "rustyline")]
+        {
+            use rustyline::error::ReadlineError;
+
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = self.editor.add_history_entry(line.as_str());
+                    }
+
+                    Ok(Some(line))
+                }
+                // Ctrl-C discards the line, like in shells
+                Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+                Err(ReadlineError::Eof) => Ok(None),
+                Err(err) => Err(into_io(err)),
+            }
+        }
+
+        #[cfg(not(feature = "rustyline"))]
+        {
+            let mut stdout = io::stdout();
+
+            stdout.write_all(prompt.as_bytes())?;
+            stdout.flush()?;
+
+            let mut line = String::new();
+
+            match io::stdin().lock().read_line(&mut line)? {
+                0 => Ok(None),
+                _ => Ok(Some(line)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rustyline")]
+impl Drop for Reader {
+    fn drop(&mut self) {
+        if let Some(path) = &self.history {
+            if let Err(err) = self.editor.save_history(path) {
+                let _ = writeln!(
+                    io::stderr(),
+                    "warning: failed to save the history to {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rustyline")]
+fn into_io(err: rustyline::error::ReadlineError) -> io::Error {
+    match err {
+        rustyline::error::ReadlineError::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+/// The subcommands and options of a command, for tab completion
+#[cfg(feature = "rustyline")]
+#[derive(Debug, Default)]
+struct Node {
+    name: String,
+    cmds: Vec<Node>,
+    options: Vec<(String, Vec<String>)>,
+    global: Vec<(String, Vec<String>)>,
+}
+
+#[cfg(feature = "rustyline")]
+impl Node {
+    fn from(app: &App<'_, '_>) -> Self {
+        let mut node = Node {
+            name: app.p.meta.name.clone(),
+            cmds: app.p.subcommands.iter().map(Node::from).collect(),
+            options: vec![("--help".to_owned(), Vec::new())],
+            global: Vec::new(),
+        };
+
+        let flags = app.p.flags.iter().map(|flag| (&flag.b, flag.s.long, None));
+        let opts = app
+            .p
+            .opts
+            .iter()
+            .map(|opt| (&opt.b, opt.s.long, opt.v.possible_vals.as_ref()));
+
+        for (base, long, values) in flags.chain(opts) {
+            if let (Some(long), false) = (long, base.is_set(ArgSettings::Hidden)) {
+                let values = values.map_or(Vec::new(), |values| {
+                    values.iter().map(|value| value.to_string()).collect()
+                });
+                let option = (format!("--{}", long), values);
+
+                if base.is_set(ArgSettings::Global) {
+                    node.global.push(option);
+                } else {
+                    node.options.push(option);
+                }
+            }
+        }
+
+        node
+    }
+
+    /// The candidates for the last word given the previous ones
+    fn complete(&self, previous: &[String], word: &str) -> Vec<String> {
+        let mut node = self;
+        let mut global = self.global.clone();
+
+        for previous in previous {
+            if let Some(cmd) = node.cmds.iter().find(|cmd| cmd.name == *previous) {
+                node = cmd;
+                global.extend(cmd.global.iter().cloned());
+            }
+        }
+
+        let options = node.options.iter().chain(&global);
+
+        // The possible values of the option before the word
+        if let Some((_, values)) = previous
+            .last()
+            .and_then(|last| options.clone().find(|(option, _)| option == last))
+        {
+            if !values.is_empty() {
+                return values
+                    .iter()
+                    .filter(|value| value.starts_with(word))
+                    .cloned()
+                    .collect();
+            }
+        }
+
+        let mut candidates: Vec<String> = if word.starts_with('-') {
+            options.map(|(option, _)| option.clone()).collect()
+        } else {
+            node.cmds.iter().map(|cmd| cmd.name.clone()).collect()
+        };
+
+        candidates.retain(|candidate| candidate.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+#[cfg(feature = "rustyline")]
+struct Completion(Node);
+
+#[cfg(feature = "rustyline")]
+impl rustyline::completion::Completer for Completion {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+        let previous = split(&line[..start]).unwrap_or_default();
+
+        Ok((start, self.0.complete(&previous, &line[start..])))
+    }
+}
+
+#[cfg(feature = "rustyline")]
+impl rustyline::hint::Hinter for Completion {
+    type Hint = String;
+}
+
+#[cfg(feature = "rustyline")]
+impl rustyline::highlight::Highlighter for Completion {}
+
+#[cfg(feature = "rustyline")]
+impl rustyline::validate::Validator for Completion {}
+
+#[cfg(feature = "rustyline")]
+impl rustyline::Helper for Completion {}
+
+#[cfg(feature = "rustyline")]
+#[test]
+fn completion() {
+    use clap::{Arg, SubCommand};
+
+    let app = App::new("program")
+        .arg(
+            Arg::with_name("env")
+                .long("env")
+                .takes_value(true)
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["json", "yaml"]),
+                )
+                .subcommand(SubCommand::with_name("foo"))
+                .subcommand(SubCommand::with_name("bar")),
+        );
+    let node = Node::from(&app);
+    let words = |line: &str| split(line).unwrap();
+
+    assert_eq!(node.complete(&[], "s"), ["show"]);
+    assert_eq!(node.complete(&words("show"), ""), ["bar", "foo"]);
+    assert_eq!(
+        node.complete(&words("show"), "--"),
+        ["--env", "--format", "--help"]
+    );
+    assert_eq!(node.complete(&words("show --format"), ""), ["json", "yaml"]);
+}
@@ -0,0 +1,84 @@
+//! Machine-readable error reports, see `Commander::with_json_errors`
+
+use clap::Error as ClapError;
+
+use CommandPath;
+
+/// Renders an error as a single-line JSON object with its exit code, kind, message,
+/// command path and the suggestion of `clap` (e.g. `Did you mean 'foo'?`) if any
+pub(crate) fn json(err: &ClapError, path: &CommandPath, code: i32) -> String {
+    let text = strip_ansi(&err.message);
+    let mut lines = text.lines();
+
+    let mut message = lines.next().unwrap_or("").trim();
+
+    // Substituted parse errors are prefixed twice
+    while let Some(rest) = message.strip_prefix("error:") {
+        message = rest.trim_start();
+    }
+
+    let suggestion = lines
+        .map(str::trim)
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| line.strip_prefix("Did you mean "))
+        .map(|line| line.trim_end_matches('?'));
+
+    format!(
+        "{{\"code\":{},\"kind\":{},\"message\":{},\"path\":[{}],\"suggestion\":{}}}",
+        code,
+        string(&format!("{:?}", err.kind)),
+        string(message),
+        path.segments()
+            .iter()
+            .map(|segment| string(segment))
+            .collect::<Vec<_>>()
+            .join(","),
+        suggestion.map_or("null".to_owned(), |s| string(s.trim_matches('\''))),
+    )
+}
+
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+pub(crate) fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skips `ESC [ ... m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+#[test]
+fn escapes() {
+    assert_eq!(string("a \"b\"\\\n\u{1}"), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+    assert_eq!(strip_ansi("\u{1b}[1;31merror:\u{1b}[0m foo"), "error: foo");
+}
@@ -0,0 +1,116 @@
+//! Response files, i.e. `@args.txt` arguments replaced with the contents of the file
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::result::Result as StdResult;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use alias;
+
+/// How many response files may include each other
+const MAX_DEPTH: usize = 8;
+
+/// Expands the `@<path>` arguments before any `--`, recursively
+pub(crate) fn expand(args: Vec<OsString>) -> StdResult<Vec<OsString>, ClapError> {
+    let mut args = args.into_iter();
+    let mut expanded: Vec<OsString> = args.next().into_iter().collect();
+
+    expand_into(&mut expanded, args, 0)?;
+    Ok(expanded)
+}
+
+fn expand_into(
+    expanded: &mut Vec<OsString>,
+    args: impl IntoIterator<Item = OsString>,
+    depth: usize,
+) -> StdResult<(), ClapError> {
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            expanded.push(arg);
+            expanded.extend(args);
+            break;
+        }
+
+        let path = match arg.to_str().and_then(|arg| arg.strip_prefix('@')) {
+            Some(path) if !path.is_empty() => Path::new(path).to_owned(),
+            _ => {
+                expanded.push(arg);
+                continue;
+            }
+        };
+
+        if depth >= MAX_DEPTH {
+            return Err(ClapError::with_description(
+                &format!(
+                    "Response files are nested more than {} levels deep at {}",
+                    MAX_DEPTH,
+                    path.display()
+                ),
+                ClapErrorKind::InvalidValue,
+            ));
+        }
+
+        let contents = std::fs::read_to_string(&path).map_err(|err| {
+            ClapError::with_description(
+                &format!("Failed to read {}: {}", path.display(), err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+        let words = parse(&contents).map_err(|err| {
+            ClapError::with_description(
+                &format!("Invalid response file {}: {}", path.display(), err),
+                ClapErrorKind::InvalidValue,
+            )
+        })?;
+
+        expand_into(expanded, words.into_iter().map(OsString::from), depth + 1)?;
+    }
+
+    Ok(())
+}
+
+/// The words of a response file, which may span several lines and have `#` comments
+fn parse(contents: &str) -> StdResult<Vec<String>, String> {
+    let mut words = Vec::new();
+
+    for line in contents.lines() {
+        if !line.trim_start().starts_with('#') {
+            words.extend(alias::split(line)?);
+        }
+    }
+
+    Ok(words)
+}
+
+#[test]
+fn expand_nested() {
+    let dir = std::env::temp_dir();
+    let outer = dir.join(format!("clap-nested-outer-{}.txt", std::process::id()));
+    let inner = dir.join(format!("clap-nested-inner-{}.txt", std::process::id()));
+    let looping = dir.join(format!("clap-nested-loop-{}.txt", std::process::id()));
+
+    std::fs::write(&inner, "--env 'prod env'\n").unwrap();
+    std::fs::write(
+        &outer,
+        format!("# flags\nshow\n@{}\nfoo\n", inner.display()),
+    )
+    .unwrap();
+    std::fs::write(&looping, format!("@{}", looping.display())).unwrap();
+
+    let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+    let outer_arg = format!("@{}", outer.display());
+
+    assert_eq!(
+        expand(args(&["program", &outer_arg, "--", "@bar"])).unwrap(),
+        args(&["program", "show", "--env", "prod env", "foo", "--", "@bar"])
+    );
+    assert!(expand(args(&["program", &format!("@{}", looping.display())])).is_err());
+
+    for file in &[outer, inner, looping] {
+        std::fs::remove_file(file).unwrap();
+    }
+}
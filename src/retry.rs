@@ -0,0 +1,61 @@
+//! Delays between the attempts of a runner, see `Command::retries`
+
+use std::time::Duration;
+
+/// How long to wait before each retry of a runner
+pub trait Backoff {
+    /// The delay before the given retry, starting from `0`
+    fn delay(&self, retry: u32) -> Duration;
+}
+
+/// Waits the same time before every retry
+impl Backoff for Duration {
+    fn delay(&self, _retry: u32) -> Duration {
+        *self
+    }
+}
+
+/// Doubles the delay after every retry, up to a maximum
+#[derive(Clone, Copy, Debug)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    max: Duration,
+}
+
+impl ExponentialBackoff {
+    pub fn new(initial: Duration) -> Self {
+        Self {
+            initial,
+            max: Duration::from_secs(60),
+        }
+    }
+
+    /// Caps the delay, one minute by default
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100))
+    }
+}
+
+impl Backoff for ExponentialBackoff {
+    fn delay(&self, retry: u32) -> Duration {
+        self.initial
+            .checked_mul(1 << retry.min(31))
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+#[test]
+fn exponential() {
+    let backoff = ExponentialBackoff::new(Duration::from_secs(1)).max(Duration::from_secs(5));
+    let delays: Vec<_> = (0..5).map(|retry| backoff.delay(retry).as_secs()).collect();
+
+    assert_eq!(delays, [1, 2, 4, 5, 5]);
+    assert_eq!(backoff.delay(100), Duration::from_secs(5));
+}
@@ -0,0 +1,17 @@
+//! Commands run by structs holding their state, see `Command::runner_impl`
+
+use clap::ArgMatches;
+
+use Result;
+
+/// What a command runs, e.g. a struct holding the configuration and clients it needs
+pub trait Runner<T: ?Sized> {
+    fn run(&self, args: &T, matches: &ArgMatches<'_>) -> Result;
+}
+
+/// Closures taking the same arguments as the [`runner`](struct.Command.html#method.runner) ones
+impl<T: ?Sized, F: Fn(&T, &ArgMatches<'_>) -> Result> Runner<T> for F {
+    fn run(&self, args: &T, matches: &ArgMatches<'_>) -> Result {
+        self(args, matches)
+    }
+}
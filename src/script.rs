@@ -0,0 +1,48 @@
+//! Running commands from a file, one per line, see `Commander::run_script`
+
+use std::path::Path;
+use std::result::Result as StdResult;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// What [`run_script`](struct.Commander.html#method.run_script) does when a line fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptMode {
+    /// Skips the remaining lines, the default of `--script`
+    StopOnError,
+    /// Runs all lines whatever happens
+    KeepGoing,
+}
+
+/// The lines of a script with their numbers, skipping blank lines and `#` comments
+pub(crate) fn load(path: &Path) -> StdResult<Vec<(usize, String)>, ClapError> {
+    let contents = std::fs::read_to_string(path).map_err(|err| {
+        ClapError::with_description(
+            &format!("Failed to read {}: {}", path.display(), err),
+            ClapErrorKind::Io,
+        )
+    })?;
+
+    Ok(parse(&contents))
+}
+
+fn parse(contents: &str) -> Vec<(usize, String)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.to_owned()))
+        .collect()
+}
+
+#[test]
+fn parse_lines() {
+    assert_eq!(
+        parse("# setup\nbuild --release\n\n  deploy prod  \n"),
+        [
+            (2, "build --release".to_owned()),
+            (4, "deploy prod".to_owned())
+        ]
+    );
+}
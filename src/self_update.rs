@@ -0,0 +1,181 @@
+//! Replacing the running binary with a release, see `Commander::with_self_update`
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use clap::{
+    App, AppSettings, Arg, ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind, SubCommand,
+};
+
+use capture;
+use Result;
+
+/// The name of the subcommand
+pub(crate) const NAME: &str = "self";
+
+type Download = Arc<dyn Fn(&str) -> io::Result<Vec<u8>> + Send + Sync>;
+type Verify = Arc<dyn Fn(&[u8]) -> StdResult<(), String> + Send + Sync>;
+
+/// Where the releases are, with how to download and verify them
+#[derive(Clone)]
+pub struct SelfUpdate {
+    url: String,
+    download: Download,
+    verify: Verify,
+}
+
+impl SelfUpdate {
+    /// `url` is a pattern where `{version}` is the version asked for (`latest` by default)
+    /// and `{target}` the platform, e.g. `linux-x86_64`, while `download` fetches the binary
+    /// and `verify` checks it, e.g. its signature, before it replaces the running one
+    pub fn new(
+        url: impl Into<String>,
+        download: impl Fn(&str) -> io::Result<Vec<u8>> + Send + Sync + 'static,
+        verify: impl Fn(&[u8]) -> StdResult<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            url: url.into(),
+            download: Arc::new(download),
+            verify: Arc::new(verify),
+        }
+    }
+
+    pub(crate) fn subcommand<'a, 'b>() -> App<'a, 'b> {
+        SubCommand::with_name(NAME)
+            .about("Manages this program")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("update")
+                    .about("Replaces this program with a release")
+                    .arg(
+                        Arg::with_name("version")
+                            .help("The version to install")
+                            .default_value("latest"),
+                    ),
+            )
+    }
+
+    pub(crate) fn run(&self, matches: &ArgMatches<'_>) -> Result {
+        let version = match matches.subcommand() {
+            ("update", Some(matches)) => matches.value_of("version").unwrap_or("latest"),
+            _ => return Ok(()),
+        };
+        let exe = env::current_exe().map_err(|err| {
+            ClapError::with_description(
+                &format!("Cannot find the running binary: {}", err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+        self.update(version, &exe)?;
+        let _ = writeln!(
+            capture::stdout(),
+            "Updated {} to {}",
+            exe.display(),
+            version
+        );
+        Ok(())
+    }
+
+    fn update(&self, version: &str, exe: &Path) -> Result {
+        let url = self.url.replace("{version}", version).replace(
+            "{target}",
+            &format!("{}-{}", env::consts::OS, env::consts::ARCH),
+        );
+        let binary = (self.download)(&url).map_err(|err| {
+            ClapError::with_description(
+                &format!("Cannot download {}: {}", url, err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+        (self.verify)(&binary).map_err(|err| {
+            ClapError::with_description(
+                &format!("The download from {} failed verification: {}", url, err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+        replace(exe, &binary).map_err(|err| {
+            ClapError::with_description(
+                &format!("Cannot replace {}: {}", exe.display(), err),
+                ClapErrorKind::Io,
+            )
+        })
+    }
+}
+
+impl fmt::Debug for SelfUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SelfUpdate")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writes the binary next to `exe` then renames it over, so that `exe` is
+/// either the old binary or the new one, moving the running one aside on Windows
+fn replace(exe: &Path, binary: &[u8]) -> io::Result<()> {
+    let name = exe.file_name().unwrap_or_default().to_string_lossy();
+    let new = exe.with_file_name(format!(".{}.new", name));
+    let result = fs::write(&new, binary)
+        .and_then(|()| fs::set_permissions(&new, fs::metadata(exe)?.permissions()))
+        .and_then(|()| {
+            if cfg!(windows) {
+                let old = exe.with_file_name(format!(".{}.old", name));
+                let _ = fs::remove_file(&old);
+                fs::rename(exe, old)?;
+            }
+
+            fs::rename(&new, exe)
+        });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&new);
+    }
+
+    result
+}
+
+#[test]
+fn replaced() {
+    let dir = env::temp_dir().join(format!("clap-nested-self-update-{}", std::process::id()));
+    let exe = dir.join("program");
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&exe, "old").unwrap();
+
+    let update = SelfUpdate::new(
+        "https://example.com/{version}/program-{target}",
+        |url| Ok(url.as_bytes().to_vec()),
+        |binary| {
+            if binary.starts_with(b"https://example.com/1.2.0/") {
+                Ok(())
+            } else {
+                Err("bad signature".to_owned())
+            }
+        },
+    );
+
+    let err = update.update("latest", &exe).unwrap_err();
+    assert!(err.message.contains("failed verification: bad signature"));
+    assert_eq!(fs::read_to_string(&exe).unwrap(), "old");
+
+    update.update("1.2.0", &exe).unwrap();
+    assert_eq!(
+        fs::read_to_string(&exe).unwrap(),
+        format!(
+            "https://example.com/1.2.0/program-{}-{}",
+            env::consts::OS,
+            env::consts::ARCH
+        )
+    );
+    assert!(!dir.join(".program.new").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,57 @@
+//! Services built on demand for the runners, see `Commander::provide`
+
+use std::any::{self, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use Context;
+
+type Factory<'a> = Rc<dyn Fn(&Context<'_>) -> Rc<dyn Any> + 'a>;
+
+/// The factories of the services by type
+#[derive(Clone, Default)]
+pub(crate) struct Services<'a> {
+    factories: HashMap<TypeId, Factory<'a>>,
+}
+
+impl<'a> Services<'a> {
+    pub(crate) fn provide<V: Any>(&mut self, factory: impl Fn(&Context<'_>) -> V + 'a) {
+        let factory: Factory<'a> = Rc::new(move |ctx| Rc::new(factory(ctx)) as Rc<dyn Any>);
+
+        self.factories.insert(TypeId::of::<V>(), factory);
+    }
+}
+
+/// The services built during an invocation, shared by the contexts of all its commands
+#[derive(Clone)]
+pub(crate) struct Instances<'c> {
+    services: &'c Services<'c>,
+    built: Rc<RefCell<HashMap<TypeId, Rc<dyn Any>>>>,
+}
+
+impl<'c> Instances<'c> {
+    pub(crate) fn new(services: &'c Services<'c>) -> Self {
+        Self {
+            services,
+            built: Rc::default(),
+        }
+    }
+
+    pub(crate) fn get<V: Any>(&self, ctx: &Context<'_>) -> Rc<V> {
+        let id = TypeId::of::<V>();
+
+        if let Some(service) = self.built.borrow().get(&id) {
+            return service.clone().downcast().unwrap();
+        }
+
+        let factory = self.services.factories.get(&id).unwrap_or_else(|| {
+            panic!("No service of type `{}` is provided", any::type_name::<V>())
+        });
+        // Not borrowing the built services, which the factory may use
+        let service = factory(ctx);
+
+        self.built.borrow_mut().insert(id, service.clone());
+        service.downcast().unwrap()
+    }
+}
@@ -0,0 +1,242 @@
+//! Graceful shutdown on Ctrl-C, `SIGTERM` and `SIGHUP`, or the console control events
+//! of Windows, see `Commander::on_interrupt`
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use interrupt::{CancellationToken, Cleanups};
+use CommandPath;
+
+pub(crate) type InterruptHook = Arc<dyn Fn(&CommandPath) + Send + Sync>;
+
+/// The exit code of shells for a process killed by `SIGINT`, or by the signal seen last
+/// on Unix, e.g. `143` for `SIGTERM`
+fn exit_code() -> i32 {
+    #[cfg(unix)]
+    return 128 + unix::last();
+
+    #[cfg(not(unix))]
+    130
+}
+
+struct Running {
+    path: CommandPath,
+    hook: InterruptHook,
+    token: CancellationToken,
+    cleanups: Cleanups,
+}
+
+/// The command being run, the handler of `ctrlc` is process-wide and can only be set once
+static RUNNING: Mutex<Option<Running>> = Mutex::new(None);
+
+/// The running command, copied out so that hooks and cleanups run without the lock,
+/// which the runner takes again when it returns
+fn running() -> Option<(CommandPath, InterruptHook, CancellationToken, Cleanups)> {
+    let running = RUNNING.lock().unwrap_or_else(|err| err.into_inner());
+
+    running.as_ref().map(|running| {
+        (
+            running.path.clone(),
+            running.hook.clone(),
+            running.token.clone(),
+            running.cleanups.clone(),
+        )
+    })
+}
+
+/// Cancels the running command and calls its hook on the first signal,
+/// runs its cleanups and exits on the next one
+fn on_signal() {
+    if let Some((path, hook, token, cleanups)) = running() {
+        if !token.is_cancelled() {
+            token.cancel();
+            hook(&path);
+            return;
+        }
+
+        cleanups.run();
+    }
+
+    std::process::exit(exit_code())
+}
+
+/// Windows ends the process as soon as the handler of a close, logoff or shutdown event
+/// returns, so the command can't wait for the next signal to run its cleanups
+#[cfg(windows)]
+fn on_close() {
+    if let Some((path, hook, token, cleanups)) = running() {
+        token.cancel();
+        hook(&path);
+        cleanups.run();
+    }
+
+    std::process::exit(exit_code())
+}
+
+/// `ctrlc` handles `CTRL_C_EVENT` and `CTRL_BREAK_EVENT`, this handles the others
+#[cfg(windows)]
+mod console {
+    use windows_sys::core::BOOL;
+    use windows_sys::Win32::System::Console::{
+        SetConsoleCtrlHandler, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+    };
+
+    unsafe extern "system" fn handler(event: u32) -> BOOL {
+        match event {
+            CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => {
+                super::on_close();
+                1
+            }
+            // Passed on to the next handler, i.e. the one of `ctrlc`
+            _ => 0,
+        }
+    }
+
+    pub(super) fn install() -> std::io::Result<()> {
+        // Handlers are called from the latest registered one
+        if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// Remembers which signal came last, since `ctrlc` calls its handler the same way for all
+#[cfg(unix)]
+mod unix {
+    use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+    use libc::c_int;
+
+    static LAST: AtomicI32 = AtomicI32::new(libc::SIGINT);
+
+    /// The handlers of `ctrlc`, called once the signal is recorded
+    static PREVIOUS: [AtomicUsize; 3] = [
+        AtomicUsize::new(0),
+        AtomicUsize::new(0),
+        AtomicUsize::new(0),
+    ];
+
+    const SIGNALS: [c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    extern "C" fn handler(signal: c_int) {
+        LAST.store(signal, Ordering::SeqCst);
+
+        if let Some(i) = SIGNALS.iter().position(|&s| s == signal) {
+            let previous = PREVIOUS[i].load(Ordering::SeqCst);
+
+            if previous != libc::SIG_DFL && previous != libc::SIG_IGN {
+                // Set from the `sa_sigaction` of a handler taking only the signal
+                let previous: extern "C" fn(c_int) = unsafe { std::mem::transmute(previous) };
+                previous(signal);
+            }
+        }
+    }
+
+    pub(super) fn last() -> i32 {
+        LAST.load(Ordering::SeqCst)
+    }
+
+    /// Wraps the handlers of `ctrlc`, keeping their flags and masks
+    pub(super) fn install() -> std::io::Result<()> {
+        for (i, &signal) in SIGNALS.iter().enumerate() {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+
+                if libc::sigaction(signal, std::ptr::null(), &mut action) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+
+                PREVIOUS[i].store(action.sa_sigaction, Ordering::SeqCst);
+                action.sa_sigaction = handler as extern "C" fn(c_int) as libc::sighandler_t;
+
+                if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Routes signals to the running command for as long as it lives
+pub(crate) struct Guard;
+
+impl Guard {
+    pub(crate) fn install(
+        path: CommandPath,
+        hook: InterruptHook,
+        token: CancellationToken,
+        cleanups: Cleanups,
+    ) -> Result<Self, ClapError> {
+        static HANDLER: OnceLock<Result<(), String>> = OnceLock::new();
+
+        let handler = HANDLER.get_or_init(|| {
+            ctrlc::set_handler(on_signal).map_err(|err| err.to_string())?;
+
+            #[cfg(unix)]
+            unix::install().map_err(|err| err.to_string())?;
+
+            #[cfg(windows)]
+            console::install().map_err(|err| err.to_string())?;
+
+            Ok(())
+        });
+
+        if let Err(err) = handler {
+            return Err(ClapError::with_description(
+                &format!("Failed to handle interrupts: {}", err),
+                ClapErrorKind::Io,
+            ));
+        }
+
+        *RUNNING.lock().unwrap_or_else(|err| err.into_inner()) = Some(Running {
+            path,
+            hook,
+            token,
+            cleanups,
+        });
+
+        Ok(Guard)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        *RUNNING.lock().unwrap_or_else(|err| err.into_inner()) = None;
+    }
+}
+
+#[test]
+fn interrupt() {
+    let (interrupted, hooked) = std::sync::mpsc::channel();
+    let token = CancellationToken::default();
+
+    // Hooks may take their time, the runner can still return meanwhile
+    let hook: InterruptHook = Arc::new(move |path| {
+        let _ = interrupted.send((path.to_string(), running().is_some()));
+    });
+
+    let path = CommandPath(vec!["deploy".to_owned()]);
+    let _guard = Guard::install(path, hook, token.clone(), Cleanups::default()).unwrap();
+
+    #[cfg(unix)]
+    unsafe {
+        libc::raise(libc::SIGTERM);
+    }
+    #[cfg(not(unix))]
+    on_signal();
+
+    let hooked = hooked
+        .recv_timeout(std::time::Duration::from_secs(5))
+        .unwrap();
+
+    assert!(token.is_cancelled());
+    assert_eq!(hooked, ("deploy".to_owned(), true));
+
+    #[cfg(unix)]
+    assert_eq!(exit_code(), 143);
+}
@@ -0,0 +1,440 @@
+//! Command trees defined in YAML or TOML documents, see `Commander::from_spec`
+//!
+//! A document describes the root command, whose keys are all optional, e.g.
+//!
+//! ```yaml
+//! name: program
+//! description: Manages clusters
+//! args:
+//!   - { name: env, long: env, value_name: ENV, global: true }
+//! commands:
+//!   - name: cluster
+//!     commands:
+//!       - name: delete
+//!         description: Deletes a cluster
+//!         args:
+//!           - { name: force, long: force, help: Skips the checks }
+//!           - { name: name, required: true, takes_value: true }
+//! ```
+//!
+//! Arguments accept `help`, `long`, `short`, `value_name`, `takes_value`, `multiple`,
+//! `required`, `global`, `hidden`, `default` and `possible_values`, where arguments
+//! without `long` nor `short` are positional.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+use std::result::Result as StdResult;
+
+use clap::{App, Arg, ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind, SubCommand};
+
+use instrument;
+use CommandLike;
+use Context;
+use Result;
+
+pub(crate) type SpecRunner<'a> = Rc<dyn Fn(&ArgMatches<'_>, &Context<'_>) -> Result + 'a>;
+
+/// The runners bound to the dotted paths of the commands, e.g. `cluster.delete`
+pub(crate) type Bindings<'a> = Rc<RefCell<HashMap<String, SpecRunner<'a>>>>;
+
+/// A command with its arguments and subcommands, loaded from a document
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Spec {
+    name: String,
+    description: Option<String>,
+    args: Vec<ArgSpec>,
+    cmds: Vec<Spec>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ArgSpec {
+    name: String,
+    help: Option<String>,
+    long: Option<String>,
+    short: Option<String>,
+    value_name: Option<String>,
+    takes_value: bool,
+    multiple: bool,
+    required: bool,
+    global: bool,
+    hidden: bool,
+    default: Option<String>,
+    possible_values: Vec<String>,
+}
+
+impl Spec {
+    /// Loads a file, picking the format by extension
+    #[cfg_attr(not(any(feature = "toml", feature = "yaml")), allow(unused_variables))]
+    pub fn from_file(path: &Path) -> StdResult<Self, ClapError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            ClapError::with_description(
+                &format!("Failed to read {}: {}", path.display(), err),
+                ClapErrorKind::Io,
+            )
+        })?;
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+
+        let parsed = match extension {
+            #[cfg(feature = "toml")]
+            "toml" => Self::from_toml(&contents),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Self::from_yaml(&contents),
+            _ => Err(format!("unsupported format `{}`", extension)),
+        };
+
+        parsed.map_err(|err| {
+            ClapError::with_description(
+                &format!("Invalid command spec in {}: {}", path.display(), err),
+                ClapErrorKind::InvalidValue,
+            )
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(contents: &str) -> StdResult<Self, String> {
+        fn node(value: toml::Value) -> Node {
+            match value {
+                toml::Value::String(s) => Node::Scalar(s),
+                toml::Value::Array(array) => Node::List(array.into_iter().map(node).collect()),
+                toml::Value::Table(table) => Node::Map(
+                    table
+                        .into_iter()
+                        .map(|(key, value)| (key, node(value)))
+                        .collect(),
+                ),
+                value => Node::Scalar(value.to_string()),
+            }
+        }
+
+        let value: toml::Value = contents
+            .parse()
+            .map_err(|err: toml::de::Error| err.to_string())?;
+
+        Self::from_node(node(value), true)
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(contents: &str) -> StdResult<Self, String> {
+        fn node(value: serde_yaml::Value) -> StdResult<Node, String> {
+            Ok(match value {
+                serde_yaml::Value::Null => Node::Null,
+                serde_yaml::Value::Bool(b) => Node::Scalar(b.to_string()),
+                serde_yaml::Value::Number(n) => Node::Scalar(n.to_string()),
+                serde_yaml::Value::String(s) => Node::Scalar(s),
+                serde_yaml::Value::Sequence(sequence) => Node::List(
+                    sequence
+                        .into_iter()
+                        .map(node)
+                        .collect::<StdResult<_, _>>()?,
+                ),
+                serde_yaml::Value::Mapping(mapping) => Node::Map(
+                    mapping
+                        .into_iter()
+                        .map(|(key, value)| match node(key)? {
+                            Node::Scalar(key) => Ok((key, node(value)?)),
+                            _ => Err("keys must be strings".to_owned()),
+                        })
+                        .collect::<StdResult<_, _>>()?,
+                ),
+                serde_yaml::Value::Tagged(tagged) => node(tagged.value)?,
+            })
+        }
+
+        let value: serde_yaml::Value =
+            serde_yaml::from_str(contents).map_err(|err| err.to_string())?;
+
+        Self::from_node(node(value)?, true)
+    }
+
+    #[cfg_attr(not(any(feature = "toml", feature = "yaml")), allow(dead_code))]
+    fn from_node(node: Node, root: bool) -> StdResult<Self, String> {
+        let mut spec = Spec::default();
+
+        for (key, value) in node.into_map("a command")? {
+            match key.as_str() {
+                "name" => spec.name = value.into_string(&key)?,
+                "description" => spec.description = Some(value.into_string(&key)?),
+                "args" => {
+                    spec.args = value
+                        .into_list(&key)?
+                        .into_iter()
+                        .map(ArgSpec::from_node)
+                        .collect::<StdResult<_, _>>()?
+                }
+                "commands" => {
+                    spec.cmds = value
+                        .into_list(&key)?
+                        .into_iter()
+                        .map(|node| Self::from_node(node, false))
+                        .collect::<StdResult<_, _>>()?
+                }
+                _ => return Err(format!("unknown key `{}` of a command", key)),
+            }
+        }
+
+        if !root && spec.name.is_empty() {
+            return Err("a command has no name".to_owned());
+        }
+
+        Ok(spec)
+    }
+
+    /// The command at the dotted `path` below this one
+    pub(crate) fn find(&self, path: &str) -> Option<&Spec> {
+        path.split('.').try_fold(self, |spec, name| {
+            spec.cmds.iter().find(|cmd| cmd.name == name)
+        })
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// The arguments of the command for an `App`
+    pub(crate) fn args(&self) -> Vec<Arg<'_, '_>> {
+        self.args.iter().map(ArgSpec::arg).collect()
+    }
+
+    pub(crate) fn cmds(&self) -> &[Spec] {
+        &self.cmds
+    }
+}
+
+impl ArgSpec {
+    #[cfg_attr(not(any(feature = "toml", feature = "yaml")), allow(dead_code))]
+    fn from_node(node: Node) -> StdResult<Self, String> {
+        let mut arg = ArgSpec::default();
+
+        for (key, value) in node.into_map("an argument")? {
+            match key.as_str() {
+                "name" => arg.name = value.into_string(&key)?,
+                "help" => arg.help = Some(value.into_string(&key)?),
+                "long" => arg.long = Some(value.into_string(&key)?),
+                "short" => arg.short = Some(value.into_string(&key)?),
+                "value_name" => arg.value_name = Some(value.into_string(&key)?),
+                "takes_value" => arg.takes_value = value.into_bool(&key)?,
+                "multiple" => arg.multiple = value.into_bool(&key)?,
+                "required" => arg.required = value.into_bool(&key)?,
+                "global" => arg.global = value.into_bool(&key)?,
+                "hidden" => arg.hidden = value.into_bool(&key)?,
+                "default" => arg.default = Some(value.into_string(&key)?),
+                "possible_values" => {
+                    arg.possible_values = value
+                        .into_list(&key)?
+                        .into_iter()
+                        .map(|value| value.into_string(&key))
+                        .collect::<StdResult<_, _>>()?
+                }
+                _ => return Err(format!("unknown key `{}` of an argument", key)),
+            }
+        }
+
+        if arg.name.is_empty() {
+            return Err("an argument has no name".to_owned());
+        }
+
+        Ok(arg)
+    }
+
+    fn arg(&self) -> Arg<'_, '_> {
+        let mut arg = Arg::with_name(&self.name)
+            .takes_value(
+                self.takes_value
+                    || self.value_name.is_some()
+                    || self.default.is_some()
+                    || !self.possible_values.is_empty(),
+            )
+            .multiple(self.multiple)
+            .required(self.required)
+            .global(self.global)
+            .hidden(self.hidden);
+
+        if let Some(help) = &self.help {
+            arg = arg.help(help);
+        }
+
+        if let Some(long) = &self.long {
+            arg = arg.long(long);
+        }
+
+        if let Some(short) = &self.short {
+            arg = arg.short(short);
+        }
+
+        if let Some(value_name) = &self.value_name {
+            arg = arg.value_name(value_name);
+        }
+
+        if let Some(default) = &self.default {
+            arg = arg.default_value(default);
+        }
+
+        for value in &self.possible_values {
+            arg = arg.possible_value(value);
+        }
+
+        arg
+    }
+}
+
+/// The parts of YAML and TOML documents making up specs, where `Null` only comes from YAML
+#[cfg_attr(not(feature = "yaml"), allow(dead_code))]
+enum Node {
+    Null,
+    Scalar(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+#[cfg_attr(not(any(feature = "toml", feature = "yaml")), allow(dead_code))]
+impl Node {
+    fn into_map(self, what: &str) -> StdResult<Vec<(String, Node)>, String> {
+        match self {
+            Node::Map(map) => Ok(map),
+            Node::Null => Ok(Vec::new()),
+            _ => Err(format!("{} must be a table", what)),
+        }
+    }
+
+    fn into_list(self, key: &str) -> StdResult<Vec<Node>, String> {
+        match self {
+            Node::List(list) => Ok(list),
+            Node::Null => Ok(Vec::new()),
+            _ => Err(format!("`{}` must be a list", key)),
+        }
+    }
+
+    fn into_string(self, key: &str) -> StdResult<String, String> {
+        match self {
+            Node::Scalar(value) => Ok(value),
+            _ => Err(format!("`{}` must be a string", key)),
+        }
+    }
+
+    fn into_bool(self, key: &str) -> StdResult<bool, String> {
+        match self {
+            Node::Scalar(value) => value
+                .parse()
+                .map_err(|_| format!("`{}` must be `true` or `false`", key)),
+            _ => Err(format!("`{}` must be `true` or `false`", key)),
+        }
+    }
+}
+
+fn subcommand(spec: &Spec) -> App<'_, '_> {
+    let mut app = SubCommand::with_name(&spec.name).args(&spec.args());
+
+    if let Some(desc) = &spec.description {
+        app = app.about(desc.as_str());
+    }
+
+    spec.cmds
+        .iter()
+        .fold(app, |app, cmd| app.subcommand(subcommand(cmd)))
+}
+
+/// A command of a spec, running the runner bound to its path
+pub(crate) struct SpecCommand<'a> {
+    spec: &'a Spec,
+    path: String,
+    bindings: Bindings<'a>,
+}
+
+impl<'a> SpecCommand<'a> {
+    pub(crate) fn new(spec: &'a Spec, parent: &str, bindings: Bindings<'a>) -> Self {
+        let path = match parent {
+            "" => spec.name.clone(),
+            parent => format!("{}.{}", parent, spec.name),
+        };
+
+        Self {
+            spec,
+            path,
+            bindings,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for SpecCommand<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpecCommand")
+            .field("path", &self.path)
+            .field("spec", self.spec)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> CommandLike<()> for SpecCommand<'a> {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn app(&self) -> App<'_, '_> {
+        subcommand(self.spec)
+    }
+
+    fn run(&self, _args: &(), matches: &ArgMatches<'_>, ctx: &Context<'_>) -> Result {
+        for cmd in &self.spec.cmds {
+            if let Some(matches) = matches.subcommand_matches(&cmd.name) {
+                let help = ctx.help().cmd(&cmd.name).unwrap();
+                let cmd = SpecCommand::new(cmd, &self.path, self.bindings.clone());
+
+                let ctx = ctx.child(&cmd.spec.name, matches, &help);
+
+                return instrument(&ctx, || cmd.run(&(), matches, &ctx));
+            }
+        }
+
+        if let Some(runner) = self.bindings.borrow().get(&self.path) {
+            return runner(matches, ctx);
+        }
+
+        if !self.spec.cmds.is_empty() {
+            return Err(ClapError::with_description(
+                &String::from_utf8_lossy(&ctx.help().data()),
+                ClapErrorKind::HelpDisplayed,
+            ));
+        }
+
+        Err(ClapError::with_description(
+            &format!(
+                "The command `{}` has no runner",
+                self.path.replace('.', " ")
+            ),
+            ClapErrorKind::ArgumentNotFound,
+        ))
+    }
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn from_yaml() {
+    let spec = Spec::from_yaml(
+        "description: Manages clusters\n\
+         commands:\n  \
+           - name: cluster\n    \
+             commands:\n      \
+               - name: delete\n        \
+                 args:\n          \
+                   - { name: force, long: force, help: Skips the checks }\n          \
+                   - { name: name, required: true, takes_value: true }\n",
+    )
+    .unwrap();
+
+    let delete = spec.find("cluster.delete").unwrap();
+    assert_eq!(delete.name(), "delete");
+    assert_eq!(delete.args.len(), 2);
+    assert!(delete.args[1].required);
+    assert!(spec.find("cluster.create").is_none());
+
+    assert!(Spec::from_yaml("commands:\n  - description: No name\n").is_err());
+    assert!(Spec::from_yaml("args:\n  - { name: env, lng: env }\n").is_err());
+}
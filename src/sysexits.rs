@@ -0,0 +1,58 @@
+//! Exit codes from BSD's `sysexits.h`, and a mapping from `clap` errors onto them
+//!
+//! ```
+//! use clap_nested::{sysexits, Commander};
+//!
+//! let commander: Commander<(), ()> = Commander::new().exit_codes(sysexits::from_error);
+//! ```
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// Successful termination
+pub const OK: i32 = 0;
+/// The command was used incorrectly
+pub const USAGE: i32 = 64;
+/// The input data was incorrect in some way
+pub const DATAERR: i32 = 65;
+/// An input file did not exist or was not readable
+pub const NOINPUT: i32 = 66;
+/// A service is unavailable
+pub const UNAVAILABLE: i32 = 69;
+/// An internal software error has been detected
+pub const SOFTWARE: i32 = 70;
+/// An error occurred while doing I/O on some file
+pub const IOERR: i32 = 74;
+/// A temporary failure, the user is invited to retry
+pub const TEMPFAIL: i32 = 75;
+/// The user did not have sufficient permission
+pub const NOPERM: i32 = 77;
+/// Something was found in an unconfigured or misconfigured state
+pub const CONFIG: i32 = 78;
+
+/// Maps usage errors to [`USAGE`](constant.USAGE.html),
+/// I/O errors to [`IOERR`](constant.IOERR.html)
+/// and internal errors to [`SOFTWARE`](constant.SOFTWARE.html)
+pub fn from_error(err: &ClapError) -> Option<i32> {
+    let code = match err.kind {
+        ClapErrorKind::HelpDisplayed | ClapErrorKind::VersionDisplayed => OK,
+        ClapErrorKind::InvalidValue
+        | ClapErrorKind::UnknownArgument
+        | ClapErrorKind::InvalidSubcommand
+        | ClapErrorKind::UnrecognizedSubcommand
+        | ClapErrorKind::EmptyValue
+        | ClapErrorKind::ValueValidation
+        | ClapErrorKind::TooManyValues
+        | ClapErrorKind::TooFewValues
+        | ClapErrorKind::WrongNumberOfValues
+        | ClapErrorKind::ArgumentConflict
+        | ClapErrorKind::MissingRequiredArgument
+        | ClapErrorKind::MissingSubcommand
+        | ClapErrorKind::MissingArgumentOrSubcommand
+        | ClapErrorKind::UnexpectedMultipleUsage
+        | ClapErrorKind::InvalidUtf8 => USAGE,
+        ClapErrorKind::Io => IOERR,
+        ClapErrorKind::ArgumentNotFound | ClapErrorKind::Format => SOFTWARE,
+    };
+
+    Some(code)
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::io::IsTerminal;
+
+/// The alignment of a column of a [`Table`](struct.Table.html)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A plain text table with aligned columns, rendered with `Display`
+///
+/// ```
+/// use clap_nested::{Align, Table};
+///
+/// let mut table = Table::new(vec!["NAME", "SIZE"]).align(1, Align::Right);
+///
+/// table.row(vec!["foo", "1"]);
+/// table.row(vec!["foobar", "100"]);
+///
+/// assert_eq!(table.to_string(), "NAME    SIZE\nfoo        1\nfoobar   100\n");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    aligns: Vec<Align>,
+    max_width: Option<usize>,
+}
+
+impl Table {
+    /// Creates a table with the given headers, which may be empty to have none
+    pub fn new<S: Into<String>>(headers: Vec<S>) -> Self {
+        Self {
+            headers: headers.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn align(mut self, column: usize, align: Align) -> Self {
+        if self.aligns.len() <= column {
+            self.aligns.resize(column + 1, Align::Left);
+        }
+
+        self.aligns[column] = align;
+        self
+    }
+
+    /// Truncates the widest columns so that lines fit in the given width
+    pub fn max_width(mut self, width: usize) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+
+    /// Truncates to the width of the terminal (from `COLUMNS`)
+    /// when the standard output is one
+    pub fn fit_terminal(self) -> Self {
+        match terminal_width() {
+            Some(width) => self.max_width(width),
+            None => self,
+        }
+    }
+
+    pub fn row<S: Into<String>>(&mut self, cells: Vec<S>) -> &mut Self {
+        self.rows.push(cells.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &Vec<String>> {
+        let headers = if self.headers.is_empty() {
+            None
+        } else {
+            Some(&self.headers)
+        };
+
+        headers.into_iter().chain(&self.rows)
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths = Vec::new();
+
+        for line in self.lines() {
+            if widths.len() < line.len() {
+                widths.resize(line.len(), 0);
+            }
+
+            for (width, cell) in widths.iter_mut().zip(line) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        if let Some(max_width) = self.max_width {
+            let gaps = widths.len().saturating_sub(1) * 2;
+
+            while widths.iter().sum::<usize>() + gaps > max_width {
+                let widest = widths.iter_mut().max().unwrap();
+
+                // Columns are never truncated below a readable width
+                if *widest <= 4 {
+                    break;
+                }
+
+                *widest -= 1;
+            }
+        }
+
+        widths
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths = self.widths();
+
+        for line in self.lines() {
+            let mut text = String::new();
+
+            for (index, width) in widths.iter().enumerate() {
+                let cell = line.get(index).map_or("", |cell| cell.as_str());
+                let cell = truncate(cell, *width);
+                let padding = " ".repeat(width - cell.chars().count());
+                let last = index + 1 == widths.len();
+
+                if index > 0 {
+                    text.push_str("  ");
+                }
+
+                match self.aligns.get(index) {
+                    Some(Align::Right) => {
+                        text.push_str(&padding);
+                        text.push_str(&cell);
+                    }
+                    _ => {
+                        text.push_str(&cell);
+
+                        if !last {
+                            text.push_str(&padding);
+                        }
+                    }
+                }
+            }
+
+            writeln!(f, "{}", text)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn truncate(cell: &str, width: usize) -> String {
+    if cell.chars().count() <= width {
+        cell.to_owned()
+    } else {
+        let mut cell: String = cell.chars().take(width.saturating_sub(1)).collect();
+        cell.push('…');
+        cell
+    }
+}
+
+pub(crate) fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
@@ -0,0 +1,309 @@
+//! Assertions on the output of a tree of commands, for the tests of apps
+//!
+//! ```
+//! use clap_nested::{testing, Command, Commander};
+//!
+//! let commander = Commander::new()
+//!     .options(|app| app.name("program"))
+//!     .add_cmd(Command::new("show").description("Shows things"));
+//!
+//! testing::assert_output(
+//!     &commander,
+//!     &["program", "show", "--help"],
+//!     "program-show __VERSION__
+//! __AUTHOR__
+//! Shows things
+//!
+//! USAGE:
+//!     program show
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information",
+//!     false,
+//! );
+//! ```
+
+use std::ffi::OsString;
+use std::panic::{self, AssertUnwindSafe};
+
+use clap::{App, AppSettings, ArgSettings};
+
+use Commander;
+use Result;
+use Timings;
+
+/// Runs `args`, which has to fail or show help or the version, and asserts that what's
+/// printed is `expected` after [`expand`](fn.expand.html), on stderr when `use_stderr`
+pub fn assert_output<T: ?Sized>(
+    commander: &Commander<'_, (), T>,
+    args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    expected: &str,
+    use_stderr: bool,
+) {
+    assert_result_with(
+        commander,
+        commander.run_with_args_result(args),
+        expected,
+        use_stderr,
+    );
+}
+
+/// Asserts that `result` is an error printing `expected`, on stderr when `use_stderr`,
+/// after [`expand`](fn.expand.html) with the app of a default commander
+pub fn assert_result(result: Result, expected: &str, use_stderr: bool) {
+    assert_result_with(&Commander::<(), ()>::new(), result, expected, use_stderr);
+}
+
+/// Like [`assert_result`](fn.assert_result.html), expanding with the app of `commander`
+pub fn assert_result_with<S: ?Sized, T: ?Sized>(
+    commander: &Commander<'_, S, T>,
+    result: Result,
+    expected: &str,
+    use_stderr: bool,
+) {
+    let err = match result {
+        Ok(()) => panic!("expected an error printing:\n{}", expected),
+        Err(err) => err,
+    };
+    let mut buf = Vec::new();
+
+    err.write_to(&mut buf).unwrap();
+
+    let output = normalize(&String::from_utf8_lossy(&buf));
+    let expected = normalize(&expand(commander, expected));
+
+    assert_eq!(
+        err.use_stderr(),
+        use_stderr,
+        "expected the output on {}",
+        if use_stderr { "stderr" } else { "stdout" }
+    );
+    assert!(
+        output == expected,
+        "the output differs\n--> output\n{}\n--> expected\n{}\n--",
+        output,
+        expected
+    );
+}
+
+/// Replaces in `text` the placeholders `__NAME__`, `__VERSION__`, `__DESC__` and `__AUTHOR__`
+/// with the values of the app of `commander`, and `__BIN_NAME__` with the file name
+/// of the running binary, which `clap` shows in usages when there's no bin name
+pub fn expand<S: ?Sized, T: ?Sized>(commander: &Commander<'_, S, T>, text: &str) -> String {
+    let app = commander.app();
+    let meta = &app.p.meta;
+    let mut text = text
+        .replace("__NAME__", &meta.name)
+        .replace("__VERSION__", meta.version.unwrap_or_default())
+        .replace("__DESC__", meta.about.unwrap_or_default())
+        .replace("__AUTHOR__", meta.author.unwrap_or_default());
+
+    if let Some(bin_name) = running_bin_name() {
+        text = text.replace("__BIN_NAME__", &bin_name);
+    }
+
+    text
+}
+
+/// What running `args` prints, help and errors included, stable across versions and
+/// machines for snapshot tests, e.g. with `insta::assert_snapshot!`, see
+/// [`scrub`](fn.scrub.html), with the `snapshot` feature
+#[cfg(feature = "snapshot")]
+pub fn snapshot<T: ?Sized>(
+    commander: &Commander<'_, (), T>,
+    args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+) -> String {
+    let run = commander.run_capture(args);
+    let mut out = run.stdout + &run.stderr;
+
+    if run.status != 0 {
+        out = format!("{}\n[exit status: {}]", out.trim_end(), run.status);
+    }
+
+    scrub(commander, &out)
+}
+
+/// The help of the command at `path`, e.g. `&["show", "foo"]`, like in
+/// [`snapshot`](fn.snapshot.html), with the `snapshot` feature
+#[cfg(feature = "snapshot")]
+pub fn help_snapshot<T: ?Sized>(commander: &Commander<'_, (), T>, path: &[&str]) -> String {
+    let app = commander.app();
+    let args = std::iter::once(&*app.p.meta.name)
+        .chain(path.iter().cloned())
+        .chain(std::iter::once("--help"));
+
+    snapshot(commander, args)
+}
+
+/// The reverse of [`expand`](fn.expand.html) for the version, the author and the bin name,
+/// with colors, trailing whitespace and the carriage returns of Windows removed
+pub fn scrub<S: ?Sized, T: ?Sized>(commander: &Commander<'_, S, T>, text: &str) -> String {
+    let app = commander.app();
+    let meta = &app.p.meta;
+    let mut text = normalize(text);
+
+    if let Some(author) = meta.author.filter(|author| !author.is_empty()) {
+        text = text.replace(author, "__AUTHOR__");
+    }
+
+    if let Some(version) = meta.version.filter(|version| !version.is_empty()) {
+        text = text.replace(version, "__VERSION__");
+    }
+
+    if let Some(bin_name) = running_bin_name() {
+        text = text.replace(&bin_name, "__BIN_NAME__");
+    }
+
+    text.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses `runs` random command lines built from the tree of `commander`, half of them
+/// made invalid e.g. with unknown arguments or missing values, without running anything,
+/// and panics with the command line when parsing panics or fails with an error
+/// which shows neither the usage nor how to get help, the same `seed` giving the same ones
+pub fn fuzz<T: ?Sized>(commander: &Commander<'_, (), T>, runs: usize, seed: u64) {
+    let app = commander.app();
+    let mut rng = Rng(seed.max(1));
+
+    for run in 0..runs {
+        let mut args = rng.args(&app);
+
+        if run % 2 == 1 {
+            rng.break_args(&mut args);
+        }
+
+        let parsed = panic::catch_unwind(AssertUnwindSafe(|| {
+            commander
+                .parse_args(args.iter().cloned(), &mut Timings::default())
+                .err()
+                .map(|failure| failure.error)
+        }));
+
+        match parsed {
+            Err(_) => panic!("parsing {:?} panicked", args),
+            Ok(Some(err)) if err.use_stderr() => {
+                let message = strip_ansi(&err.message);
+
+                assert!(
+                    message.contains("USAGE:") || message.contains("--help"),
+                    "the error of {:?} shows neither the usage nor the help: {}",
+                    args,
+                    message
+                );
+            }
+            Ok(_) => {}
+        }
+    }
+}
+
+/// A xorshift generator, enough to pick arguments
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+
+    fn value(&mut self, possible: &Option<Vec<&str>>) -> String {
+        match possible {
+            Some(values) if !values.is_empty() => values[self.below(values.len())].to_owned(),
+            _ => ["value", "1", "-", "a b", ""][self.below(5)].to_owned(),
+        }
+    }
+
+    /// A command line going down a random path, with random arguments at each level
+    fn args(&mut self, app: &App<'_, '_>) -> Vec<String> {
+        let mut args = vec![app.p.meta.name.clone()];
+        let mut app = app;
+
+        loop {
+            let p = &app.p;
+
+            for flag in &p.flags {
+                if self.chance(20) {
+                    args.extend(switch(flag.s.short, flag.s.long));
+                }
+            }
+
+            for opt in &p.opts {
+                if opt.b.is_set(ArgSettings::Required) || self.chance(20) {
+                    args.extend(switch(opt.s.short, opt.s.long));
+                    args.push(self.value(&opt.v.possible_vals));
+                }
+            }
+
+            for pos in p.positionals.values() {
+                if pos.b.is_set(ArgSettings::Required) || self.chance(50) {
+                    args.push(self.value(&pos.v.possible_vals));
+                }
+            }
+
+            let subcmds: Vec<_> = p
+                .subcommands
+                .iter()
+                .filter(|subcmd| !subcmd.p.is_set(AppSettings::Hidden))
+                .collect();
+
+            if subcmds.is_empty() || self.chance(20) {
+                return args;
+            }
+
+            app = subcmds[self.below(subcmds.len())];
+            args.push(app.p.meta.name.clone());
+        }
+    }
+
+    /// Makes a command line likely invalid
+    fn break_args(&mut self, args: &mut Vec<String>) {
+        let at = 1 + self.below(args.len());
+
+        match self.below(5) {
+            0 => args.insert(at, "--not-an-argument".to_owned()),
+            1 => args.insert(at, "not-a-command".to_owned()),
+            2 if args.len() > 1 => {
+                args.remove(at.min(args.len() - 1));
+            }
+            3 => args.insert(at, "-".repeat(1 + self.below(3))),
+            _ => {
+                let arg = args[self.below(args.len())].clone();
+                args.insert(at, arg);
+            }
+        }
+    }
+}
+
+fn switch(short: Option<char>, long: Option<&str>) -> Option<String> {
+    long.map(|long| format!("--{}", long))
+        .or_else(|| short.map(|short| format!("-{}", short)))
+}
+
+fn running_bin_name() -> Option<String> {
+    let arg = std::env::args_os().next()?;
+
+    Some(std::path::Path::new(&arg).file_name()?.to_str()?.to_owned())
+}
+
+/// Removes the ANSI escape sequences, e.g. of colors
+pub fn strip_ansi(text: &str) -> String {
+    ::report::strip_ansi(text)
+}
+
+/// Strips colors, surrounding whitespace and the carriage returns of Windows
+fn normalize(text: &str) -> String {
+    strip_ansi(text).trim().replace('\r', "")
+}
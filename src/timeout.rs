@@ -0,0 +1,70 @@
+//! Deadlines for runners, watched from another thread
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use clap::{Error as ClapError, ErrorKind as ClapErrorKind};
+
+use interrupt::CancellationToken;
+
+/// The exit code of `timeout(1)` when the command times out
+pub(crate) const EXIT_CODE: i32 = 124;
+
+/// Cancels the runner once the deadline passes, since runners borrow too much
+/// to be moved to another thread and abandoned there
+pub(crate) struct Watchdog {
+    expired: Arc<AtomicBool>,
+    done: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn start(cancellation: CancellationToken, timeout: Duration) -> Self {
+        let expired = Arc::new(AtomicBool::new(false));
+        let (done, finished) = mpsc::channel::<()>();
+
+        let thread = thread::spawn({
+            let expired = expired.clone();
+
+            move || {
+                if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(timeout) {
+                    expired.store(true, Ordering::SeqCst);
+                    cancellation.cancel();
+                }
+            }
+        });
+
+        Self {
+            expired,
+            done: Some(done),
+            thread: Some(thread),
+        }
+    }
+
+    /// Whether the deadline passed before the runner returned
+    pub(crate) fn expired(&self) -> bool {
+        self.expired.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the watchdog up
+        self.done.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The error of a command whose runner didn't return in time
+pub(crate) fn error(path: &str, timeout: Duration) -> ClapError {
+    ClapError::with_description(
+        &format!("The command `{}` timed out after {:?}", path, timeout),
+        ClapErrorKind::Io,
+    )
+}
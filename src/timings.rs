@@ -0,0 +1,29 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Where time goes inside the framework, reported by the hidden `--timings` flag
+#[derive(Debug, Default)]
+pub(crate) struct Timings {
+    pub(crate) parse: Duration,
+    pub(crate) help: Duration,
+    pub(crate) run: Duration,
+    /// Whether the root was given `--timings`, once parsed
+    pub(crate) requested: bool,
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn ms(duration: Duration) -> f64 {
+            duration.as_secs_f64() * 1000.0
+        }
+
+        write!(
+            f,
+            "timings: parse {:.2}ms, help {:.2}ms, run {:.2}ms, total {:.2}ms",
+            ms(self.parse),
+            ms(self.help),
+            ms(self.run),
+            ms(self.parse + self.help + self.run)
+        )
+    }
+}
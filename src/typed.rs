@@ -0,0 +1,287 @@
+//! Deserializing the matches of a command into a struct, see `Command::runner_typed`
+
+use std::fmt::{self, Display};
+use std::result::Result as StdResult;
+
+use clap::{ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+
+/// Fills `A` from the arguments, whose names may use `-` where the fields use `_`
+pub(crate) fn from_matches<A: DeserializeOwned>(
+    matches: &ArgMatches<'_>,
+) -> StdResult<A, ClapError> {
+    A::deserialize(Matches(matches))
+        .map_err(|err| ClapError::with_description(&err.0, ClapErrorKind::ValueValidation))
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+struct Matches<'m, 'a>(&'m ArgMatches<'a>);
+
+impl<'m, 'a> Matches<'m, 'a> {
+    fn value(&self, name: &str) -> StdResult<Value, Error> {
+        let arg = self
+            .0
+            .args
+            .get(name)
+            .or_else(|| self.0.args.get(name.replace('_', "-").as_str()));
+
+        let arg = match arg {
+            Some(arg) => arg,
+            None => return Ok(Value::Absent(name.to_owned())),
+        };
+
+        if arg.vals.is_empty() {
+            return Ok(Value::Flag(arg.occurs));
+        }
+
+        let mut vals = arg
+            .vals
+            .iter()
+            .map(|val| {
+                val.to_str()
+                    .map(str::to_owned)
+                    .ok_or_else(|| Error(format!("The value of '{}' isn't valid UTF-8", name)))
+            })
+            .collect::<StdResult<Vec<_>, _>>()?;
+
+        Ok(match vals.len() {
+            1 => Value::One(vals.remove(0)),
+            _ => Value::Many(vals),
+        })
+    }
+}
+
+impl<'de, 'm, 'a> de::Deserializer<'de> for Matches<'m, 'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value, Error> {
+        let values = self
+            .0
+            .args
+            .keys()
+            .map(|name| Ok((name.to_string(), self.value(name)?)))
+            .collect::<StdResult<Vec<_>, Error>>()?;
+
+        visitor.visit_map(MapDeserializer::new(values.into_iter()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> StdResult<V::Value, Error> {
+        // Every field is given so that absent flags are `false` rather than missing
+        let values = fields
+            .iter()
+            .map(|field| Ok((*field, self.value(field)?)))
+            .collect::<StdResult<Vec<_>, Error>>()?;
+
+        visitor.visit_map(MapDeserializer::new(values.into_iter()))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// The matched values of an argument
+enum Value {
+    /// An argument which isn't given, with its name for errors
+    Absent(String),
+    /// A flag given that many times
+    Flag(u64),
+    One(String),
+    Many(Vec<String>),
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse {
+    ($($deserialize:ident => $visit:ident,)*) => {
+        $(
+            fn $deserialize<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value, Error> {
+                match self {
+                    Value::One(value) => visitor.$visit(value.parse().map_err(|err| {
+                        Error(format!("Invalid value '{}': {}", value, err))
+                    })?),
+                    value => value.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value, Error> {
+        match self {
+            Value::Absent(name) => Err(Error(format!("The argument '{}' is required", name))),
+            Value::Flag(occurs) => visitor.visit_u64(occurs),
+            Value::One(value) => visitor.visit_string(value),
+            Value::Many(values) => {
+                visitor.visit_seq(SeqDeserializer::new(values.into_iter().map(Value::One)))
+            }
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value, Error> {
+        match self {
+            Value::Absent(_) => visitor.visit_bool(false),
+            Value::Flag(occurs) => visitor.visit_bool(occurs > 0),
+            Value::One(value) => visitor.visit_bool(
+                value
+                    .parse()
+                    .map_err(|err| Error(format!("Invalid value '{}': {}", value, err)))?,
+            ),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value, Error> {
+        match self {
+            Value::Absent(_) => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value, Error> {
+        let values = match self {
+            Value::Absent(_) => Vec::new(),
+            Value::One(value) => vec![value],
+            Value::Many(values) => values,
+            value => return value.deserialize_any(visitor),
+        };
+
+        visitor.visit_seq(SeqDeserializer::new(values.into_iter().map(Value::One)))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> StdResult<V::Value, Error> {
+        match self {
+            Value::One(value) => visitor.visit_enum(value.into_deserializer()),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> StdResult<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    parse! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[test]
+fn deserialize_matches() {
+    use clap::{App, Arg};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Format {
+        Json,
+        Yaml,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Args {
+        port: u16,
+        dry_run: bool,
+        force: bool,
+        verbose: u8,
+        format: Option<Format>,
+        env: Option<String>,
+        files: Vec<std::path::PathBuf>,
+    }
+
+    let app = App::new("program")
+        .arg(Arg::with_name("port").long("port").takes_value(true))
+        .arg(Arg::with_name("dry-run").long("dry-run"))
+        .arg(Arg::with_name("force").long("force"))
+        .arg(Arg::with_name("verbose").short("v").multiple(true))
+        .arg(Arg::with_name("format").long("format").takes_value(true))
+        .arg(Arg::with_name("env").long("env").takes_value(true))
+        .arg(Arg::with_name("files").multiple(true));
+    let matches = app.clone().get_matches_from([
+        "program",
+        "--port",
+        "8080",
+        "--dry-run",
+        "-vv",
+        "--format",
+        "yaml",
+        "a",
+        "b",
+    ]);
+
+    assert_eq!(
+        from_matches::<Args>(&matches).unwrap(),
+        Args {
+            port: 8080,
+            dry_run: true,
+            force: false,
+            verbose: 2,
+            format: Some(Format::Yaml),
+            env: None,
+            files: vec!["a".into(), "b".into()],
+        }
+    );
+
+    let matches = app.get_matches_from(["program", "--port", "http"]);
+    let err = from_matches::<Args>(&matches).unwrap_err();
+    assert_eq!(err.kind, ClapErrorKind::ValueValidation);
+    assert!(err.message.contains("Invalid value 'http'"));
+}
@@ -0,0 +1,275 @@
+//! Notices of newer versions, see `Commander::check_for_updates`
+
+use std::cmp::Ordering;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use capture;
+use dirs;
+
+/// How [`check_for_updates`](struct.Commander.html#method.check_for_updates) finds
+/// the latest version, at most once per interval with the result cached in between
+#[derive(Clone)]
+pub struct UpdateCheck {
+    name: String,
+    current: String,
+    fetch: Arc<dyn Fn() -> Option<String> + Send + Sync>,
+    interval: Duration,
+    cache_file: Option<PathBuf>,
+}
+
+impl UpdateCheck {
+    /// `fetch` asks the version endpoint for the latest version, e.g. `1.2.0`,
+    /// and runs in the background while the command does
+    pub fn new(
+        name: impl Into<String>,
+        current: impl Into<String>,
+        fetch: impl Fn() -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            current: current.into(),
+            fetch: Arc::new(fetch),
+            interval: Duration::from_secs(24 * 60 * 60),
+            cache_file: None,
+        }
+    }
+
+    /// How long the latest version is reused before fetching it again, a day by default
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Where the latest version is cached, `<cache dir>/<name>/latest-version` by default
+    pub fn cache_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_file = Some(path.into());
+        self
+    }
+
+    /// Reads the cached version, fetching it again in the background when stale,
+    /// unless stderr isn't a terminal, on CI or with `NO_UPDATE_NOTIFIER` set
+    pub(crate) fn start(&self) -> Option<Pending<'_>> {
+        let quiet = env::var_os("CI").is_some() || env::var_os("NO_UPDATE_NOTIFIER").is_some();
+
+        if quiet || !std::io::stderr().is_terminal() {
+            return None;
+        }
+
+        self.start_unchecked()
+    }
+
+    fn start_unchecked(&self) -> Option<Pending<'_>> {
+        let path = self
+            .cache_file
+            .clone()
+            .or_else(|| dirs::cache_dir().map(|dir| dir.join(&self.name).join("latest-version")))?;
+        let (latest, stale) = read_cache(
+            &fs::read_to_string(&path).unwrap_or_default(),
+            self.interval,
+        );
+        let refresh = if stale {
+            let (sender, receiver) = mpsc::channel();
+            let fetch = self.fetch.clone();
+
+            thread::spawn(move || {
+                let latest = fetch();
+
+                write_cache(&path, latest.as_deref());
+                let _ = sender.send(latest);
+            });
+
+            Some(receiver)
+        } else {
+            None
+        };
+
+        Some(Pending {
+            check: self,
+            latest,
+            refresh,
+        })
+    }
+}
+
+impl fmt::Debug for UpdateCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpdateCheck")
+            .field("name", &self.name)
+            .field("current", &self.current)
+            .field("interval", &self.interval)
+            .field("cache_file", &self.cache_file)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A check started before running the command
+pub(crate) struct Pending<'u> {
+    check: &'u UpdateCheck,
+    latest: Option<String>,
+    refresh: Option<Receiver<Option<String>>>,
+}
+
+impl<'u> Pending<'u> {
+    /// Prints the notice once the command is done, with the fetched version if it's there
+    /// already, otherwise it's left for the next invocations
+    pub(crate) fn finish(self) {
+        if let Some(notice) = self.notice() {
+            let _ = writeln!(capture::stderr(), "{}", notice);
+        }
+    }
+
+    fn notice(self) -> Option<String> {
+        // The cached version still holds when fetching it again failed
+        let fetched = self.refresh.and_then(|refresh| refresh.try_recv().ok());
+        let latest = fetched.flatten().or(self.latest)?;
+
+        if !newer(&latest, &self.check.current) {
+            return None;
+        }
+
+        Some(format!(
+            "A newer version of {} is available: {} -> {}",
+            self.check.name, self.check.current, latest
+        ))
+    }
+}
+
+/// The cached version if any, and whether it's older than the interval,
+/// from `<unix time> <version>`
+fn read_cache(contents: &str, interval: Duration) -> (Option<String>, bool) {
+    let (time, version) = match contents.trim().split_once(' ') {
+        Some((time, version)) => (time, Some(version.to_owned())),
+        None => (contents.trim(), None),
+    };
+    let stale = match time.parse() {
+        Ok(secs) => now().saturating_sub(Duration::from_secs(secs)) >= interval,
+        Err(_) => true,
+    };
+
+    (version, stale)
+}
+
+/// Also records failures to fetch, so that they are only retried after the interval
+fn write_cache(path: &Path, latest: Option<&str>) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+
+    let line = match latest {
+        Some(latest) => format!("{} {}\n", now().as_secs(), latest),
+        None => format!("{}\n", now().as_secs()),
+    };
+    let _ = fs::write(path, line);
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Whether a version like `v1.10.0` is after another, comparing the numbers then the
+/// pre-release suffixes like semver does, e.g. `1.2.0-beta.2` comes before `1.2.0`
+fn newer(latest: &str, current: &str) -> bool {
+    /// A part of a pre-release suffix, where numbers come before words
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    enum Part {
+        Number(u64),
+        Word(String),
+    }
+
+    fn parse(version: &str) -> (Vec<u64>, Option<Vec<Part>>) {
+        // Without the build metadata, which doesn't order versions
+        let version = version
+            .trim_start_matches('v')
+            .split('+')
+            .next()
+            .unwrap_or_default();
+        let (release, pre) = match version.split_once('-') {
+            Some((release, pre)) => (release, Some(pre)),
+            None => (version, None),
+        };
+        let numbers = release
+            .split('.')
+            .map(|part| {
+                let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+                digits.parse().unwrap_or(0)
+            })
+            .collect();
+        let pre = pre.map(|pre| {
+            pre.split('.')
+                .map(|part| {
+                    part.parse()
+                        .map_or_else(|_| Part::Word(part.to_owned()), Part::Number)
+                })
+                .collect()
+        });
+
+        (numbers, pre)
+    }
+
+    let ((mut latest, latest_pre), (mut current, current_pre)) = (parse(latest), parse(current));
+    let len = latest.len().max(current.len());
+
+    latest.resize(len, 0);
+    current.resize(len, 0);
+
+    match latest.cmp(&current) {
+        Ordering::Equal => match (latest_pre, current_pre) {
+            // Releases come after their pre-releases
+            (None, Some(_)) => true,
+            (Some(latest), Some(current)) => latest > current,
+            _ => false,
+        },
+        ordering => ordering == Ordering::Greater,
+    }
+}
+
+#[test]
+fn cached_notice() {
+    assert!(newer("v1.10.0", "1.9.3"));
+    assert!(!newer("1.2", "1.2.0"));
+    assert!(!newer("1.2.0-beta", "1.2.0"));
+    assert!(newer("1.2.0", "1.2.0-beta"));
+    assert!(newer("1.2.0-beta.11", "1.2.0-beta.2"));
+    assert!(newer("1.2.0-rc.1", "1.2.0-beta.11"));
+    assert!(!newer("1.2.0+build.2", "1.2.0+build.1"));
+
+    let path = env::temp_dir().join(format!("clap-nested-update-{}", std::process::id()));
+    let check = UpdateCheck::new("program", "1.0.0", || Some("1.1.0".to_owned())).cache_file(&path);
+
+    // Nothing is cached yet, so the fetched version is written for the next invocation
+    let pending = check.start_unchecked().unwrap();
+    pending.refresh.as_ref().unwrap().recv().unwrap();
+    assert_eq!(pending.latest, None);
+
+    let pending = check.start_unchecked().unwrap();
+    assert!(pending.refresh.is_none());
+    assert_eq!(
+        pending.notice().unwrap(),
+        "A newer version of program is available: 1.0.0 -> 1.1.0"
+    );
+
+    // Failing to fetch the version again keeps the cached one
+    let (sender, refresh) = mpsc::channel();
+    sender.send(None).unwrap();
+    let pending = Pending {
+        check: &check,
+        latest: Some("1.1.0".to_owned()),
+        refresh: Some(refresh),
+    };
+    assert!(pending.notice().is_some());
+
+    let (latest, stale) = read_cache("0 1.1.0", Duration::from_secs(60));
+    assert_eq!((latest.as_deref(), stale), (Some("1.1.0"), true));
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,55 @@
+//! Environment variables set around runners, see `Command::with_env`
+
+use std::env;
+use std::ffi::OsString;
+
+/// Restores the variables it set to their previous values when dropped
+pub(crate) struct Vars(Vec<(String, Option<OsString>)>);
+
+impl Vars {
+    pub(crate) fn set(vars: &[(String, OsString)]) -> Self {
+        let previous = vars
+            .iter()
+            .map(|(name, value)| {
+                let previous = env::var_os(name);
+
+                env::set_var(name, value);
+                (name.clone(), previous)
+            })
+            .collect();
+
+        Vars(previous)
+    }
+}
+
+impl Drop for Vars {
+    fn drop(&mut self) {
+        // In reverse, so that the first value of a variable set twice is restored last
+        for (name, previous) in self.0.drain(..).rev() {
+            match previous {
+                Some(value) => env::set_var(&name, value),
+                None => env::remove_var(&name),
+            }
+        }
+    }
+}
+
+#[test]
+fn restored() {
+    env::set_var("CLAP_NESTED_VARS_KEPT", "before");
+    env::remove_var("CLAP_NESTED_VARS_NEW");
+
+    let vars = Vars::set(&[
+        ("CLAP_NESTED_VARS_KEPT".to_owned(), "first".into()),
+        ("CLAP_NESTED_VARS_NEW".to_owned(), "new".into()),
+        ("CLAP_NESTED_VARS_KEPT".to_owned(), "second".into()),
+    ]);
+
+    assert_eq!(env::var("CLAP_NESTED_VARS_KEPT").unwrap(), "second");
+    assert_eq!(env::var("CLAP_NESTED_VARS_NEW").unwrap(), "new");
+
+    drop(vars);
+
+    assert_eq!(env::var("CLAP_NESTED_VARS_KEPT").unwrap(), "before");
+    assert!(env::var_os("CLAP_NESTED_VARS_NEW").is_none());
+}
@@ -0,0 +1,50 @@
+//! The working directory given with `-C`, see `Commander::with_chdir_flag`
+
+use std::env;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+use clap::{ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind};
+
+/// The name of the `-C` option
+pub(crate) const ARG: &str = "chdir";
+
+/// Changes the working directory, restoring the previous one when dropped
+pub(crate) struct Chdir(Option<PathBuf>);
+
+impl Chdir {
+    /// Changes to the directory given at any level, if any
+    pub(crate) fn from_matches(matches: &ArgMatches<'_>) -> Result<Self, ClapError> {
+        let dir = match deepest(matches) {
+            Some(dir) => dir,
+            None => return Ok(Chdir(None)),
+        };
+        let error = |err: std::io::Error| {
+            ClapError::with_description(
+                &format!(
+                    "Cannot change the working directory to '{}': {}",
+                    dir.to_string_lossy(),
+                    err
+                ),
+                ClapErrorKind::Io,
+            )
+        };
+        let previous = env::current_dir().map_err(error)?;
+
+        env::set_current_dir(dir).map_err(error)?;
+        Ok(Chdir(Some(previous)))
+    }
+}
+
+impl Drop for Chdir {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            let _ = env::set_current_dir(previous);
+        }
+    }
+}
+
+fn deepest<'m>(matches: &'m ArgMatches<'_>) -> Option<&'m OsStr> {
+    let inner = matches.subcommand().1.and_then(deepest);
+    inner.or_else(|| matches.value_of_os(ARG))
+}
@@ -1,13 +1,21 @@
+// The first tests pass their arguments by reference, which `run_with_args` also takes
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 extern crate clap;
 extern crate clap_nested;
-extern crate regex;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 use clap::Arg;
-use clap_nested::{Command, Commander};
+use clap_nested::{
+    sysexits, Align, AuditLog, ColorChoice, Command, Commander, CompletionBackend, Conflict,
+    DenyReason, ErrorExt, ExitStatus, ExponentialBackoff, MatchesExt, NoCmd,
+    OutputFormat, ScriptMode, Table,
+};
 
 mod common;
 
-use common::{assert_output, assert_result};
+use common::{assert_output, assert_result, lock_env};
 
 #[test]
 fn two_level_commander() {
@@ -172,7 +180,13 @@ fn failed_command() {
                     )
                 })
                 .runner(|_args, _matches| {
-                    Err(std::io::Error::from(std::io::ErrorKind::Other).into())
+                    // Displayed, since `clap` converts I/O errors with the deprecated
+                    // `Error::description`
+                    let err = std::io::Error::other("other os error");
+                    Err(clap::Error::with_description(
+                        &err.to_string(),
+                        clap::ErrorKind::Io,
+                    ))
                 }),
         ),
         &["test", "fail"],
@@ -180,3 +194,3433 @@ fn failed_command() {
         true,
     );
 }
+
+#[test]
+fn parse() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo").runner(|_args, _matches| panic!("foo ran")))
+                .into_cmd("show"),
+        )
+        .add_cmd(Command::new("what"));
+
+    let (path, matches) = commander.parse(["program", "show", "foo"]).unwrap();
+    assert_eq!(path.segments(), ["show", "foo"]);
+    assert_eq!(path.to_string(), "show foo");
+    assert!(matches.subcommand_matches("show").is_some());
+
+    let (path, _matches) = commander.parse(["program"]).unwrap();
+    assert!(path.is_empty());
+
+    assert!(commander.parse(["program", "bar"]).is_err());
+}
+
+#[test]
+fn exit_codes() {
+    let commander: Commander<(), ()> = Commander::new();
+
+    let failure = clap::Error::with_description("failed", clap::ErrorKind::Io);
+    assert_eq!(commander.exit_code_for(&failure), 1);
+
+    let commander = commander.exit_codes(sysexits::from_error);
+    assert_eq!(commander.exit_code_for(&failure), sysexits::IOERR);
+
+    let usage = clap::Error::with_description("bad", clap::ErrorKind::UnknownArgument);
+    assert_eq!(commander.exit_code_for(&usage), sysexits::USAGE);
+
+    let commander = commander.exit_codes(|err| match err.kind {
+        clap::ErrorKind::Io => Some(3),
+        _ => None,
+    });
+    assert_eq!(commander.exit_code_for(&failure), 3);
+    assert_eq!(commander.exit_code_for(&usage), 1);
+}
+
+#[test]
+fn run_status() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("foo"))
+        .add_cmd(Command::new("fail").runner(|_args, _matches| {
+            Err(clap::Error::with_description("failed", clap::ErrorKind::Io))
+        }));
+
+    match commander.run_with_args_status(["program", "foo"]) {
+        ExitStatus::Ran(path) => assert_eq!(path.segments(), ["foo"]),
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    match commander.run_with_args_status(["program", "--help"]) {
+        ExitStatus::HelpShown(help) => assert!(help.contains("USAGE")),
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    match commander.run_with_args_status(["program", "--version"]) {
+        ExitStatus::VersionShown => {}
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    match commander.run_with_args_status(["program", "-x"]) {
+        ExitStatus::ParseError(err) => assert_eq!(err.kind, clap::ErrorKind::UnknownArgument),
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    match commander.run_with_args_status(["program", "fail"]) {
+        ExitStatus::RunnerError(err) => assert_eq!(err.kind, clap::ErrorKind::Io),
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    assert!(commander.run_with_args_status(["program"]).is_success());
+    assert!(!commander
+        .run_with_args_status(["program", "fail"])
+        .is_success());
+}
+
+#[test]
+fn no_cmd_policies() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("foo").runner(|_args, _matches| {
+            ran.borrow_mut().push("foo".to_owned());
+            Ok(())
+        }))
+        .add_cmd(Command::new("bar"));
+
+    let commander = commander.no_cmd_policy(NoCmd::Default("foo"));
+    assert!(commander.run_with_args_result(["program"]).is_ok());
+    assert_eq!(*ran.borrow(), ["foo"]);
+
+    let commander = commander.no_cmd_policy(NoCmd::Suggest);
+    let err = commander.run_with_args_result(["program"]).unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::MissingSubcommand);
+    assert!(err.message.contains("foo, bar"));
+
+    let err = commander
+        .run_with_args_result(["program", "fo"])
+        .unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::UnrecognizedSubcommand);
+    assert_eq!(
+        err.message,
+        "error: The subcommand 'fo' wasn't recognized\n\tDid you mean 'foo'?\n\n\
+         Possible subcommands are: foo, bar"
+    );
+
+    let commander = commander.no_cmd_policy(NoCmd::Default("nope"));
+    let err = commander.run_with_args_result(["program"]).unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::InvalidSubcommand);
+
+    let commander =
+        commander.no_cmd_policy(NoCmd::Leftover(Box::new(|_args, leftover, _matches| {
+            let leftover: Vec<_> = leftover
+                .iter()
+                .map(|token| token.to_str().unwrap())
+                .collect();
+            ran.borrow_mut().push(leftover.join(" "));
+            Ok(())
+        })));
+    assert!(commander
+        .run_with_args_result(["program", "baz", "-x", "1"])
+        .is_ok());
+    assert!(commander.run_with_args_result(["program"]).is_ok());
+    assert_eq!(*ran.borrow(), ["foo", "baz -x 1", ""]);
+
+    let commander = commander.no_cmd_policy(NoCmd::Help);
+    let err = commander.run_with_args_result(["program"]).unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::HelpDisplayed);
+
+    // Without a terminal to show the menu on
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        let commander = commander.no_cmd_policy(NoCmd::Pick);
+        let err = commander.run_with_args_result(["program"]).unwrap_err();
+        assert_eq!(err.kind, clap::ErrorKind::HelpDisplayed);
+    }
+}
+
+#[test]
+fn subcommand_required() {
+    let show = || {
+        Commander::new()
+            .add_cmd(Command::new("foo"))
+            .no_cmd(|_args, _matches| Ok(()))
+            .into_cmd("show")
+            .description("Shows things")
+    };
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(show())
+        .no_cmd(|_args, _matches| Ok(()));
+
+    assert!(commander.run_with_args_result(["program"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "show"]).is_ok());
+
+    let commander = commander.subcommand_required(true);
+
+    assert_eq!(
+        commander.parse(["program"]).unwrap_err().kind,
+        clap::ErrorKind::HelpDisplayed
+    );
+    assert!(commander.run_with_args_result(["program", "show"]).is_ok());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(show())
+        .global_subcommand_required(true);
+
+    match commander.run_with_args_status(["program", "show"]) {
+        ExitStatus::ParseError(err) => {
+            assert_eq!(err.kind, clap::ErrorKind::MissingArgumentOrSubcommand);
+            assert!(err.message.contains("program show <SUBCOMMAND>"));
+        }
+        status => panic!("unexpected status: {:?}", status),
+    }
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo"])
+        .is_ok());
+}
+
+#[test]
+fn version() {
+    let commander = || {
+        Commander::new()
+            .options(|app| app.name("program"))
+            .version("1.2.3+build")
+            .add_cmd(
+                Commander::new()
+                    .add_cmd(Command::new("foo"))
+                    .into_cmd("show"),
+            )
+    };
+
+    let global = commander();
+    let help = global
+        .run_with_args_result(["program", "show", "foo", "--help"])
+        .unwrap_err();
+    assert!(help.message.starts_with("program-show-foo 1.2.3+build"));
+    assert!(help.message.contains("--version"));
+
+    let local = commander().global_version(false);
+    let help = local
+        .run_with_args_result(["program", "show", "foo", "--help"])
+        .unwrap_err();
+    assert!(help.message.starts_with("program-show-foo \n"));
+    assert!(!help.message.contains("--version"));
+
+    let help = local
+        .run_with_args_result(["program", "show", "--help"])
+        .unwrap_err();
+    assert!(help.message.starts_with("program-show \n"));
+
+    match local.run_with_args_status(["program", "show", "-V"]) {
+        ExitStatus::ParseError(err) => assert_eq!(err.kind, clap::ErrorKind::UnknownArgument),
+        status => panic!("unexpected status: {:?}", status),
+    }
+}
+
+#[test]
+fn bin_name() {
+    assert_output(
+        &Commander::new()
+            .bin_name("mycli")
+            .add_cmd(Command::new("foo").description("Shows foo")),
+        ["target/debug/deps/mycli-abc123", "foo", "--help"],
+        "mycli-foo __VERSION__
+__AUTHOR__
+Shows foo
+
+USAGE:
+    mycli foo
+
+FLAGS:
+    -h, --help       Prints help information
+    -V, --version    Prints version information",
+        false,
+    );
+}
+
+#[test]
+fn multicall() {
+    let commander = Commander::new()
+        .options(|app| app.name("kubectl"))
+        .multicall("kubectl-")
+        .add_cmd(
+            Command::new("get")
+                .description("Gets resources")
+                .options(|app| app.arg(Arg::with_name("all").short("a").help("Gets everything"))),
+        )
+        .add_cmd(Command::new("set"));
+
+    let (path, _matches) = commander.parse(["/usr/bin/kubectl-get", "-a"]).unwrap();
+    assert_eq!(path.segments(), ["get"]);
+
+    let (path, _matches) = commander.parse(["kubectl", "set"]).unwrap();
+    assert_eq!(path.segments(), ["set"]);
+
+    let help = commander
+        .run_with_args_result(["kubectl-get", "--help"])
+        .unwrap_err();
+    assert!(help.message.contains("USAGE:\n    kubectl-get [FLAGS]"));
+
+    assert_output(
+        &commander,
+        ["/usr/bin/kubectl-get", "-x"],
+        "error: error: Found argument '-x' which wasn't expected, or isn't valid in this context
+
+kubectl-get __VERSION__
+__AUTHOR__
+Gets resources
+
+USAGE:
+    kubectl-get [FLAGS]
+
+FLAGS:
+    -a               Gets everything
+    -h, --help       Prints help information
+    -V, --version    Prints version information",
+        false,
+    );
+}
+
+#[test]
+fn cargo_subcommand() {
+    let commander = Commander::new()
+        .cargo_subcommand("foo")
+        .add_cmd(Command::new("bar").description("Shows bar"));
+
+    let (path, _matches) = commander.parse(["cargo-foo", "foo", "bar"]).unwrap();
+    assert_eq!(path.segments(), ["bar"]);
+
+    let (path, _matches) = commander.parse(["cargo-foo", "bar"]).unwrap();
+    assert_eq!(path.segments(), ["bar"]);
+
+    assert_output(
+        &commander,
+        ["cargo-foo", "foo", "bar", "-x"],
+        "error: error: Found argument '-x' which wasn't expected, or isn't valid in this context
+
+cargo-foo-bar __VERSION__
+__AUTHOR__
+Shows bar
+
+USAGE:
+    cargo foo bar
+
+FLAGS:
+    -h, --help       Prints help information
+    -V, --version    Prints version information",
+        false,
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn plugins() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("clap-nested-plugins-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let script = dir.join("program-hello");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\necho \"$@\" > \"$(dirname \"$0\")/out\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let script = dir.join("program-fail");
+    std::fs::write(&script, "#!/bin/sh\nexit 3\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .plugins("program-")
+        .plugin_dirs([&dir])
+        .add_cmd(Command::new("fail"));
+
+    let plugins: Vec<_> = commander
+        .discover_plugins()
+        .iter()
+        .map(|plugin| plugin.name().to_owned())
+        .collect();
+    assert_eq!(plugins, ["hello"]);
+
+    // Scanned once per commander
+    let script = dir.join("program-later");
+    std::fs::write(&script, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(commander.discover_plugins().len(), 1);
+
+    let help = commander
+        .run_with_args_result(["program", "--help"])
+        .unwrap_err();
+    assert!(help.message.contains("hello    External plugin"));
+
+    assert!(commander
+        .run_with_args_result(["program", "hello", "--help", "-x", "world"])
+        .is_ok());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out")).unwrap(),
+        "--help -x world\n"
+    );
+
+    // Registered subcommands win over plugins
+    assert!(commander.run_with_args_result(["program", "fail"]).is_ok());
+
+    // The plugin given is looked up by name, without scanning for the others
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .plugins("program-")
+        .plugin_dirs([&dir]);
+
+    assert!(commander.run_with_args_result(["program", "hello"]).is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "later", "later"])
+        .is_ok());
+
+    let script = dir.join("program-last");
+    std::fs::write(&script, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(commander.discover_plugins().len(), 4);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn aliases() {
+    let file = std::env::temp_dir().join(format!("clap-nested-aliases-{}", std::process::id()));
+    std::fs::write(
+        &file,
+        "[alias]\nsf = \"show foo\"\nloop = again\nagain = loop\n",
+    )
+    .unwrap();
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Commander::new()
+                .options(|app| app.arg(Arg::with_name("debug").short("d")))
+                .add_cmd(Command::new("foo"))
+                .into_cmd("show"),
+        )
+        .alias("sd", "show -d")
+        .alias("show", "sd")
+        .alias_file(&file);
+
+    let (path, matches) = commander.parse(["program", "sd", "foo"]).unwrap();
+    assert_eq!(path.segments(), ["show", "foo"]);
+    assert!(matches
+        .subcommand_matches("show")
+        .unwrap()
+        .is_present("debug"));
+
+    let (path, _matches) = commander.parse(["program", "sf"]).unwrap();
+    assert_eq!(path.segments(), ["show", "foo"]);
+
+    // Aliases can't shadow subcommands
+    let (_path, matches) = commander.parse(["program", "show"]).unwrap();
+    assert!(!matches
+        .subcommand_matches("show")
+        .unwrap()
+        .is_present("debug"));
+
+    match commander.run_with_args_status(["program", "loop"]) {
+        ExitStatus::ParseError(err) => {
+            assert!(err.message.contains("loop -> again -> loop"))
+        }
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn config() {
+    use std::cell::RefCell;
+
+    let _env = lock_env();
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program").arg(
+                Arg::with_name("env")
+                    .long("env")
+                    .takes_value(true)
+                    .global(true),
+            )
+        })
+        .add_cmd(
+            Commander::new()
+                .add_cmd(
+                    Command::new("foo").runner_with_context(|_args, _matches, ctx| {
+                        seen.borrow_mut().push(ctx.value_of("env"));
+                        Ok(())
+                    }),
+                )
+                .into_cmd("show"),
+        )
+        .config("clap-nested-test-config");
+
+    std::env::remove_var("CLAP_NESTED_TEST_CONFIG_ENV");
+    std::env::remove_var("CLAP_NESTED_TEST_CONFIG_SHOW_FOO_ENV");
+
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo"])
+        .is_ok());
+
+    std::env::set_var("CLAP_NESTED_TEST_CONFIG_ENV", "staging");
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo"])
+        .is_ok());
+
+    // Keys scoped to the command path are more specific
+    std::env::set_var("CLAP_NESTED_TEST_CONFIG_SHOW_FOO_ENV", "prod");
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo"])
+        .is_ok());
+
+    // The command line wins over everything
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo", "--env", "dev"])
+        .is_ok());
+
+    std::env::remove_var("CLAP_NESTED_TEST_CONFIG_ENV");
+    std::env::remove_var("CLAP_NESTED_TEST_CONFIG_SHOW_FOO_ENV");
+
+    assert_eq!(
+        *seen.borrow(),
+        [
+            None,
+            Some("staging".to_owned()),
+            Some("prod".to_owned()),
+            Some("dev".to_owned()),
+        ]
+    );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn config_file() {
+    use std::cell::RefCell;
+
+    let file = std::env::temp_dir().join(format!("clap-nested-config-{}.toml", std::process::id()));
+    std::fs::write(&file, "env = \"staging\"\n\n[show]\nregion = \"eu\"\n").unwrap();
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Command::new("show").runner_with_context(|_args, _matches, ctx| {
+                seen.borrow_mut().push(ctx.value_of("env"));
+                seen.borrow_mut().push(ctx.value_of("region"));
+                seen.borrow_mut().push(ctx.config().get("show.region"));
+                Ok(())
+            }),
+        )
+        .config_file(&file);
+
+    assert!(commander.run_with_args_result(["program", "show"]).is_ok());
+    assert_eq!(
+        *seen.borrow(),
+        [
+            Some("staging".to_owned()),
+            Some("eu".to_owned()),
+            Some("eu".to_owned()),
+        ]
+    );
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn env_prefix() {
+    let _env = lock_env();
+
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program")
+                .arg(Arg::with_name("dry-run").long("dry-run").takes_value(true))
+        })
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo").options(|app| {
+                    app.arg(
+                        Arg::with_name("region")
+                            .long("region")
+                            .takes_value(true)
+                            .required(true),
+                    )
+                }))
+                .into_cmd("show"),
+        )
+        .env_prefix("CLAP_NESTED_TEST_PREFIX");
+
+    std::env::set_var("CLAP_NESTED_TEST_PREFIX_DRY_RUN", "yes");
+    std::env::set_var("CLAP_NESTED_TEST_PREFIX_REGION", "eu");
+
+    let (_path, matches) = commander.parse(["program", "show", "foo"]).unwrap();
+    assert_eq!(matches.value_of("dry-run"), Some("yes"));
+    assert_eq!(
+        matches
+            .subcommand_matches("show")
+            .and_then(|matches| matches.subcommand_matches("foo"))
+            .and_then(|matches| matches.value_of("region")),
+        Some("eu")
+    );
+
+    // The command line wins over the environment
+    let (_path, matches) = commander
+        .parse(["program", "--dry-run", "no", "show", "foo"])
+        .unwrap();
+    assert_eq!(matches.value_of("dry-run"), Some("no"));
+
+    std::env::remove_var("CLAP_NESTED_TEST_PREFIX_REGION");
+    assert!(commander.parse(["program", "show", "foo"]).is_err());
+
+    std::env::remove_var("CLAP_NESTED_TEST_PREFIX_DRY_RUN");
+}
+
+#[test]
+fn env_file() {
+    let _env = lock_env();
+
+    let dir = std::env::temp_dir().join(format!("clap-nested-env-file-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(".env"), "CLAP_NESTED_TEST_ENV_FILE_REGION=eu\n").unwrap();
+    std::fs::write(
+        dir.join("other.env"),
+        "CLAP_NESTED_TEST_ENV_FILE_USER=bob\n",
+    )
+    .unwrap();
+
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program")
+                .arg(
+                    Arg::with_name("region")
+                        .long("region")
+                        .env("CLAP_NESTED_TEST_ENV_FILE_REGION"),
+                )
+                .arg(Arg::with_name("user").long("user").takes_value(true))
+        })
+        .add_cmd(Command::new("foo"))
+        .env_prefix("CLAP_NESTED_TEST_ENV_FILE")
+        .env_file(dir.join(".env"));
+
+    let (_path, matches) = commander.parse(["program", "foo"]).unwrap();
+    assert_eq!(matches.value_of("region"), Some("eu"));
+    assert_eq!(matches.value_of("user"), None);
+
+    let other = dir.join("other.env");
+    let (_path, matches) = commander
+        .parse([
+            "program".as_ref(),
+            "foo".as_ref(),
+            "--env-file".as_ref(),
+            other.as_os_str(),
+        ])
+        .unwrap();
+    assert_eq!(matches.value_of("user"), Some("bob"));
+
+    assert!(commander
+        .parse(["program", "--env-file", "missing.env", "foo"])
+        .is_err());
+
+    std::env::remove_var("CLAP_NESTED_TEST_ENV_FILE_REGION");
+    std::env::remove_var("CLAP_NESTED_TEST_ENV_FILE_USER");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn verbosity() {
+    use std::cell::RefCell;
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Commander::new()
+                .add_cmd(
+                    Command::new("foo").runner_with_context(|_args, _matches, ctx| {
+                        seen.borrow_mut().push(ctx.verbosity());
+                        Ok(())
+                    }),
+                )
+                .into_cmd("show"),
+        )
+        .with_verbosity();
+
+    for args in [
+        &["program", "show", "foo"][..],
+        &["program", "-vv", "show", "foo"],
+        &["program", "-vvv", "show", "foo", "-q"],
+        &["program", "show", "--quiet", "foo"],
+    ] {
+        assert!(commander.run_with_args_result(args).is_ok());
+    }
+
+    assert_eq!(*seen.borrow(), [0, 2, 2, -1]);
+}
+
+#[test]
+fn color() {
+    use std::cell::RefCell;
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Command::new("foo").runner_with_context(|_args, _matches, ctx| {
+                seen.borrow_mut().push(ctx.color());
+                Ok(())
+            }),
+        )
+        .with_color();
+
+    assert!(commander
+        .run_with_args_result(["program", "--color", "never", "foo"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "foo", "--color=always"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "--color", "sometimes", "foo"])
+        .is_err());
+    assert_eq!(*seen.borrow(), [ColorChoice::Never, ColorChoice::Always]);
+
+    match commander.run_with_args_status(["program", "--color", "always", "--help"]) {
+        ExitStatus::HelpShown(help) => assert!(help.contains('\u{1b}')),
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    match commander.run_with_args_status(["program", "--color", "never", "--help"]) {
+        ExitStatus::HelpShown(help) => assert!(!help.contains('\u{1b}')),
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    // Not the one of a command when the commander doesn't register it
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("grep")
+            .options(|app| app.arg(Arg::with_name("color").long("color").takes_value(true)))
+            .runner_with_context(|_args, _matches, ctx| {
+                seen.borrow_mut().push(ctx.color());
+                Ok(())
+            }),
+    );
+
+    assert!(commander
+        .run_with_args_result(["program", "grep", "--color", "always"])
+        .is_ok());
+    assert_ne!(seen.borrow().last(), Some(&ColorChoice::Always));
+}
+
+#[test]
+fn output_format() {
+    use std::cell::RefCell;
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Command::new("foo").runner_with_context(|_args, _matches, ctx| {
+                seen.borrow_mut().push(ctx.output_format());
+                Ok(())
+            }),
+        )
+        .with_output();
+
+    assert!(commander.run_with_args_result(["program", "foo"]).is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "foo", "--output", "json"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "--output", "xml", "foo"])
+        .is_err());
+    assert_eq!(*seen.borrow(), [OutputFormat::Table, OutputFormat::Json]);
+
+    // Not the one of a command when the commander doesn't register it
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("dump")
+            .options(|app| app.arg(Arg::with_name("output").long("output").takes_value(true)))
+            .runner_with_context(|_args, _matches, ctx| {
+                seen.borrow_mut().push(ctx.output_format());
+                Err(clap::Error::with_description("failed", clap::ErrorKind::Io))
+            }),
+    );
+
+    let run = commander.run_capture(["program", "dump", "--output", "json"]);
+    assert_eq!(run.stderr, "error: failed\n");
+    assert_eq!(seen.borrow().last(), Some(&OutputFormat::Table));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn output_render() {
+    use std::collections::BTreeMap;
+
+    let rows: Vec<BTreeMap<&str, &str>> = vec![
+        vec![("name", "foo"), ("env", "prod")].into_iter().collect(),
+        vec![("name", "bar")].into_iter().collect(),
+    ];
+
+    assert_eq!(
+        OutputFormat::Json.render(&rows).unwrap(),
+        "[\n  {\n    \"env\": \"prod\",\n    \"name\": \"foo\"\n  },\n  {\n    \"name\": \"bar\"\n  }\n]\n"
+    );
+    assert_eq!(
+        OutputFormat::Table.render(&rows).unwrap(),
+        "ENV   NAME\nprod  foo\n      bar\n"
+    );
+}
+
+#[test]
+fn table() {
+    let mut table = Table::new(vec!["NAME", "DESCRIPTION", "SIZE"])
+        .align(2, Align::Right)
+        .max_width(30);
+
+    table.row(vec!["foo", "Shows foo", "1"]);
+    table.row(vec!["bar", "Shows bar in much more detail", "1024"]);
+    table.row(vec!["baz"]);
+
+    assert_eq!(
+        table.to_string(),
+        "NAME  DESCRIPTION         SIZE\n\
+         foo   Shows foo              1\n\
+         bar   Shows bar in much…  1024\n\
+         baz                           \n"
+    );
+}
+
+#[test]
+fn json_errors() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo"))
+                .into_cmd("show"),
+        )
+        .add_cmd(Command::new("fail").runner(|_args, _matches| {
+            Err(clap::Error::with_description("failed", clap::ErrorKind::Io))
+        }))
+        .with_json_errors();
+
+    let err = match commander.run_with_args_status(["program", "show", "fo", "--json-errors"]) {
+        ExitStatus::ParseError(err) => err,
+        status => panic!("unexpected status: {:?}", status),
+    };
+    let (path, _matches) = commander.parse(["program", "show"]).unwrap();
+
+    assert_eq!(
+        commander.error_json(&err, &path),
+        "{\"code\":1,\"kind\":\"InvalidSubcommand\",\
+         \"message\":\"The subcommand 'fo' wasn't recognized\",\
+         \"path\":[\"show\"],\"suggestion\":\"foo\"}"
+    );
+
+    let run = commander.run_capture(["program", "fail", "--json-errors"]);
+    assert!(run.stderr.starts_with("{\"code\":1,\"kind\":\"Io\""));
+
+    // Only the arguments of the root are known when parsing fails
+    let run = commander.run_capture(["program", "--json-errors", "nope"]);
+    assert!(run.stderr.starts_with("{\"code\":1,\"kind\":\"UnknownArgument\""));
+    let run = commander.run_capture(["program", "fail", "--json-errors", "nope"]);
+    assert!(run.stderr.starts_with("error: "));
+}
+
+#[test]
+fn context_path() {
+    use std::cell::RefCell;
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Commander::new()
+            .add_cmd(
+                Command::new("foo").runner_with_context(|_args, _matches, ctx| {
+                    seen.borrow_mut().push(ctx.path().join("."));
+                    Ok(())
+                }),
+            )
+            .into_cmd("show"),
+    );
+
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo"])
+        .is_ok());
+    assert_eq!(*seen.borrow(), ["show.foo"]);
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_spans() {
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// The paths of the spans in the order they're opened, and the outcomes recorded
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<(Vec<String>, Vec<String>)>>);
+
+    /// Reads one field of a span
+    struct Value(&'static str, Option<String>);
+
+    impl Visit for Value {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == self.0 {
+                self.1 = Some(value.to_owned());
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == self.0 {
+                self.1 = Some(format!("{:?}", value));
+            }
+        }
+    }
+
+    impl Subscriber for Spans {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut path = Value("path", None);
+            let mut spans = self.0.lock().unwrap();
+
+            attrs.record(&mut path);
+            spans.0.push(path.1.unwrap_or_default());
+            Id::from_u64(spans.0.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut outcome = Value("outcome", None);
+            let mut spans = self.0.lock().unwrap();
+
+            values.record(&mut outcome);
+
+            if let Some(outcome) = outcome.1 {
+                let path = spans.0[span.into_u64() as usize - 1].clone();
+                spans.1.push(format!("{} {}", path, outcome));
+            }
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Commander::new()
+            .add_cmd(Command::new("foo").runner(|_args, _matches| Ok(())))
+            .add_cmd(Command::new("fail").runner(|_args, _matches| {
+                Err(clap::Error::with_description("failed", clap::ErrorKind::Io))
+            }))
+            .into_cmd("show"),
+    );
+    let spans = Spans::default();
+
+    tracing::subscriber::with_default(spans.clone(), || {
+        assert!(commander
+            .run_with_args_result(["program", "show", "foo"])
+            .is_ok());
+        assert!(commander
+            .run_with_args_result(["program", "show", "fail"])
+            .is_err());
+    });
+
+    let spans = spans.0.lock().unwrap();
+    assert_eq!(spans.0, ["show", "show.foo", "show", "show.fail"]);
+    assert_eq!(
+        spans.1,
+        ["show.foo ok", "show ok", "show.fail error", "show error"]
+    );
+}
+
+#[test]
+fn timings() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("foo"))
+        .with_timings();
+
+    let run = commander.run_capture(["program", "foo", "--timings"]);
+    assert_eq!(run.status, 0);
+    assert!(run.stderr.starts_with("timings: parse "));
+    assert!(run.stderr.contains("ms, help "));
+    assert!(run.stderr.contains("ms, run "));
+    assert!(run.stderr.ends_with("ms\n"));
+
+    assert!(commander.run_capture(["program", "foo"]).stderr.is_empty());
+
+    // Not when it's the argument of a command
+    let commander = commander.add_cmd(Command::new("exec").options(|app| {
+        app.setting(clap::AppSettings::TrailingVarArg)
+            .arg(Arg::with_name("args").multiple(true))
+    }));
+    let run = commander.run_capture(["program", "exec", "make", "--timings"]);
+    assert_eq!(run.status, 0);
+    assert!(run.stderr.is_empty());
+
+    match commander.run_with_args_status(["program", "--help"]) {
+        ExitStatus::HelpShown(help) => assert!(!help.contains("--timings")),
+        status => panic!("unexpected status: {:?}", status),
+    }
+}
+
+#[test]
+fn on_invocation() {
+    use std::cell::RefCell;
+
+    let _env = lock_env();
+
+    // Pinned, the hook isn't called for users setting it
+    std::env::remove_var("DO_NOT_TRACK");
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo"))
+                .add_cmd(Command::new("fail").runner(|_args, _matches| {
+                    Err(clap::Error::with_description("failed", clap::ErrorKind::Io))
+                }))
+                .into_cmd("show"),
+        )
+        .on_invocation(|path, _matches, outcome, _duration| {
+            seen.borrow_mut().push((path.to_string(), outcome.is_ok()));
+        });
+
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "show", "fail"])
+        .is_err());
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo", "--no-telemetry"])
+        .is_ok());
+
+    assert_eq!(
+        *seen.borrow(),
+        [
+            ("show foo".to_owned(), true),
+            ("show fail".to_owned(), false),
+        ]
+    );
+}
+
+#[test]
+fn audit_log() {
+    let file = std::env::temp_dir().join(format!("clap-nested-audit-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&file);
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("login").options(|app| {
+            app.arg(
+                Arg::with_name("password")
+                    .long("password")
+                    .takes_value(true),
+            )
+        }))
+        .audit_log(AuditLog::new(&file).redact("--password"));
+
+    assert!(commander
+        .run_with_args_result(["program", "login", "--password", "secret"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "logout"])
+        .is_err());
+
+    let log = std::fs::read_to_string(&file).unwrap();
+    let lines: Vec<_> = log.lines().collect();
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0]
+        .ends_with("\"path\":[\"login\"],\"args\":[\"login\",\"--password\",\"***\"],\"exit\":0}"));
+    assert!(lines[1].ends_with("\"path\":[],\"args\":[\"logout\"],\"exit\":1}"));
+    assert!(!log.contains("secret"));
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn on_crash() {
+    use std::cell::RefCell;
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("foo"))
+        .add_cmd(Command::new("fail").runner(|_args, _matches| {
+            Err(clap::Error::with_description("failed", clap::ErrorKind::Io))
+        }))
+        .add_cmd(Command::new("panic").runner(|_args, _matches| panic!("oops")))
+        .on_crash(|report| {
+            seen.borrow_mut().push((
+                report.path().to_string(),
+                report.panicked(),
+                report.error().message.lines().next().unwrap().to_owned(),
+            ));
+        });
+
+    assert!(commander.run_with_args_result(["program", "foo"]).is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "--help"])
+        .is_err());
+    assert!(commander.run_with_args_result(["program", "bar"]).is_err());
+    assert!(commander.run_with_args_result(["program", "fail"]).is_err());
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        commander.run_with_args_result(["program", "panic"])
+    }))
+    .is_err());
+
+    assert_eq!(
+        *seen.borrow(),
+        [
+            (
+                "".to_owned(),
+                false,
+                "error: error: Found argument 'bar' which wasn't expected, or isn't valid in this context"
+                    .to_owned()
+            ),
+            ("fail".to_owned(), false, "error: failed".to_owned()),
+            (
+                "panic".to_owned(),
+                true,
+                "error: The command panicked: oops".to_owned()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn catch_panics() {
+    use std::cell::{Cell, RefCell};
+
+    let _env = lock_env();
+
+    // Pinned, the hook isn't called for users setting it
+    std::env::remove_var("DO_NOT_TRACK");
+
+    let seen = RefCell::new(Vec::new());
+    let crashed = Cell::new(false);
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("panic").runner(|_args, _matches| panic!("oops")))
+        .on_invocation(|path, _matches, outcome, _duration| {
+            seen.borrow_mut().push((path.to_string(), outcome.is_ok()));
+        })
+        .on_crash(|report| crashed.set(report.panicked()))
+        .catch_panics();
+
+    match commander.run_with_args_status(["program", "panic"]) {
+        ExitStatus::RunnerError(err) => {
+            assert_eq!(err.kind, clap::ErrorKind::Format);
+            assert_eq!(err.message, "error: The command panicked: oops");
+        }
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    assert_eq!(*seen.borrow(), [("panic".to_owned(), false)]);
+    assert!(crashed.get());
+}
+
+#[test]
+fn friendly_panics() {
+    use clap_nested::PanicMetadata;
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    static HOOKED: Mutex<Vec<ThreadId>> = Mutex::new(Vec::new());
+
+    let _env = lock_env();
+
+    // Which asks for the usual output instead
+    let backtrace = std::env::var_os("RUST_BACKTRACE");
+    std::env::remove_var("RUST_BACKTRACE");
+
+    let hooked = || {
+        HOOKED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|id| **id == thread::current().id())
+            .count()
+    };
+    let reports = || {
+        std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter_map(|entry| std::fs::read_to_string(entry.ok()?.path()).ok())
+            .filter(|report| report.contains("message = \"friendly oops\""))
+            .count()
+    };
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_info| {
+        HOOKED.lock().unwrap().push(thread::current().id());
+    }));
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("panic").runner(|_args, _matches| panic!("friendly oops")))
+        .friendly_panics(PanicMetadata::new("program", "1.0.0"))
+        .catch_panics();
+
+    // Caught panics are errors, without a crash banner nor a report
+    assert!(!commander
+        .run_with_args_status(["program", "panic"])
+        .is_success());
+    assert_eq!(reports(), 0);
+    assert_eq!(hooked(), 0);
+
+    // The previous hook is back once the run is over
+    assert!(std::panic::catch_unwind(|| panic!("after the run")).is_err());
+    assert_eq!(hooked(), 1);
+
+    std::panic::set_hook(previous);
+
+    if let Some(backtrace) = backtrace {
+        std::env::set_var("RUST_BACKTRACE", backtrace);
+    }
+}
+
+#[test]
+fn cancel_after() {
+    use std::time::Duration;
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("quick")
+            .cancel_after(Duration::from_secs(60))
+            .runner(|_args, _matches| Ok(())),
+    );
+
+    assert!(commander
+        .run_with_args_status(["program", "quick"])
+        .is_success());
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("slow")
+            .cancel_after(Duration::from_millis(50))
+            .runner_with_context(|_args, _matches, ctx| {
+                while !ctx.cancellation().is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+
+                Ok(())
+            }),
+    );
+
+    match commander.run_with_args_status(["program", "slow"]) {
+        ExitStatus::TimedOut(err) => {
+            assert_eq!(
+                err.message,
+                "error: The command `slow` timed out after 50ms"
+            );
+            assert_eq!(commander.exit_code_for(&err), 1);
+        }
+        status => panic!("unexpected status: {:?}", status),
+    }
+
+    assert_eq!(commander.run_capture(["program", "slow"]).status, 124);
+}
+
+#[test]
+fn retries() {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    let attempts = Cell::new(0);
+    let flaky = |_args: &(), _matches: &clap::ArgMatches<'_>| {
+        attempts.set(attempts.get() + 1);
+
+        if attempts.get() < 3 {
+            Err(clap::Error::with_description(
+                "Connection reset",
+                clap::ErrorKind::Io,
+            ))
+        } else {
+            Ok(())
+        }
+    };
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Command::new("flaky")
+                .retries(3)
+                .backoff(ExponentialBackoff::new(Duration::from_millis(1)))
+                .runner(flaky),
+        )
+        .add_cmd(
+            Command::new("strict")
+                .retries(3)
+                .retry_if(|err| err.kind != clap::ErrorKind::Io)
+                .runner(flaky),
+        );
+
+    assert!(commander
+        .run_with_args_status(["program", "flaky"])
+        .is_success());
+    assert_eq!(attempts.get(), 3);
+
+    attempts.set(0);
+    assert!(!commander
+        .run_with_args_status(["program", "strict"])
+        .is_success());
+    assert_eq!(attempts.get(), 1);
+}
+
+#[test]
+fn cancellation() {
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("wait").runner_with_context(|_args, _matches, ctx| {
+            assert!(!ctx.cancellation().is_cancelled());
+            ctx.cancellation().cancel();
+            assert!(ctx.cancellation().is_cancelled());
+            Ok(())
+        }),
+    );
+
+    assert!(commander
+        .run_with_args_status(["program", "wait"])
+        .is_success());
+}
+
+#[test]
+fn defer() {
+    use std::sync::{Arc, Mutex};
+
+    let ran = Arc::new(Mutex::new(Vec::new()));
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("deploy").runner_with_context(|_args, _matches, ctx| {
+            for resource in &["bucket", "instance"] {
+                let ran = ran.clone();
+                ctx.defer(move || ran.lock().unwrap().push(*resource));
+            }
+
+            Err(clap::Error::with_description(
+                "Deployment failed",
+                clap::ErrorKind::Io,
+            ))
+        }),
+    );
+
+    assert!(!commander
+        .run_with_args_status(["program", "deploy"])
+        .is_success());
+    assert_eq!(*ran.lock().unwrap(), ["instance", "bucket"]);
+}
+
+#[test]
+fn run_script() {
+    use std::cell::RefCell;
+
+    let file = std::env::temp_dir().join(format!("clap-nested-script-{}", std::process::id()));
+    std::fs::write(&file, "# deploy\nbuild --release\nnope\n\nbuild\n").unwrap();
+
+    let built = RefCell::new(Vec::new());
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("build")
+            .options(|app| app.arg(Arg::with_name("release").long("release")))
+            .runner(|_args, matches| {
+                built.borrow_mut().push(matches.is_present("release"));
+                Ok(())
+            }),
+    );
+
+    let statuses = commander
+        .run_script(&file, ScriptMode::StopOnError)
+        .unwrap();
+    let lines: Vec<_> = statuses
+        .iter()
+        .map(|(number, status)| (*number, status.is_success()))
+        .collect();
+
+    assert_eq!(lines, [(2, true), (3, false)]);
+    assert_eq!(*built.borrow(), [true]);
+
+    let statuses = commander.run_script(&file, ScriptMode::KeepGoing).unwrap();
+
+    assert_eq!(statuses.len(), 3);
+    assert_eq!(*built.borrow(), [true, true, false]);
+
+    let commander = commander.with_script().add_cmd(
+        Command::new("exec")
+            .options(|app| {
+                app.arg(Arg::with_name("script").long("script").takes_value(true))
+                    .arg(Arg::with_name("keep-going").long("keep-going"))
+            })
+            .runner(|_args, _matches| Ok(())),
+    );
+    let file = file.to_str().unwrap();
+
+    let run = commander.run_capture(["program", "--keep-going", "--script", file]);
+    assert_eq!(run.status, 1);
+    assert!(run.stderr.starts_with(&format!("{}:3: ", file)));
+    assert_eq!(*built.borrow(), [true, true, false, true, false]);
+
+    // Not the one of a subcommand
+    let run = commander.run_capture(["program", "exec", "--script", file]);
+    assert_eq!(run.status, 0);
+    assert_eq!(built.borrow().len(), 5);
+
+    let run = commander.run_capture(["program", "--script", file, "exec", "--keep-going"]);
+    assert_eq!(run.status, 1);
+    assert_eq!(built.borrow().len(), 6);
+
+    std::fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn chain() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+    let step = |name: &'static str| {
+        let ran = &ran;
+
+        Command::new(name).runner(move |_args, matches: &clap::ArgMatches<'_>| {
+            ran.borrow_mut().push(format!(
+                "{} {}",
+                name,
+                matches.value_of("env").unwrap_or("dev")
+            ));
+
+            if name == "fail" {
+                Err(clap::Error::with_description("Failed", clap::ErrorKind::Io))
+            } else {
+                Ok(())
+            }
+        })
+    };
+
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program").arg(
+                Arg::with_name("env")
+                    .long("env")
+                    .takes_value(true)
+                    .global(true),
+            )
+        })
+        .add_cmd(step("build"))
+        .add_cmd(step("deploy"))
+        .add_cmd(step("fail"))
+        .chain("+");
+
+    assert!(commander
+        .run_with_args_result(["program", "--env", "prod", "build", "+", "deploy"])
+        .is_ok());
+    assert_eq!(*ran.borrow(), ["build prod", "deploy prod"]);
+
+    ran.borrow_mut().clear();
+
+    match commander.run_with_args_status(["program", "build", "+", "fail", "+", "deploy"]) {
+        ExitStatus::RunnerError(err) => assert_eq!(err.message, "error: Failed"),
+        status => panic!("unexpected status: {:?}", status),
+    }
+    assert_eq!(*ran.borrow(), ["build dev", "fail dev"]);
+}
+
+#[test]
+#[should_panic(expected = "`--` can't separate chained commands")]
+fn chain_double_dash() {
+    let _ = Commander::<(), ()>::new().chain("--");
+}
+
+#[test]
+fn run_parallel() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let checked = AtomicUsize::new(0);
+
+    let statuses = Commander::run_parallel(
+        || {
+            Commander::new().options(|app| app.name("program")).add_cmd(
+                Command::new("check").runner(|_args, _matches| {
+                    checked.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }),
+            )
+        },
+        vec![
+            vec!["program", "check"],
+            vec!["program", "nope"],
+            vec!["program", "check"],
+        ],
+    );
+
+    let successes: Vec<_> = statuses.iter().map(ExitStatus::is_success).collect();
+
+    assert_eq!(successes, [true, false, true]);
+    assert_eq!(checked.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn tty_detection() {
+    use std::cell::RefCell;
+
+    let seen = RefCell::new(Vec::new());
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("tty").runner_with_context(|_args, _matches, ctx| {
+            seen.borrow_mut().push((
+                ctx.stdin_is_tty(),
+                ctx.stdout_is_tty(),
+                ctx.stderr_is_tty(),
+                ctx.read_stdin()?,
+            ));
+            Ok(())
+        }),
+    );
+
+    assert_eq!(commander.run_capture(["program", "tty"]).status, 0);
+    assert_eq!(
+        commander
+            .run_capture_with_stdin(["program", "tty"], "piped\n")
+            .status,
+        0
+    );
+    assert_eq!(
+        *seen.borrow(),
+        [
+            (false, false, false, Some("".to_owned())),
+            (false, false, false, Some("piped\n".to_owned())),
+        ]
+    );
+
+    let run = commander.run_capture_with_stdin(["program", "tty"], vec![0xff]);
+    assert_eq!(run.status, 1);
+    assert!(run
+        .stderr
+        .starts_with("error: Failed to read the standard input: invalid utf-8"));
+}
+
+#[test]
+fn trailing_args() {
+    use std::cell::RefCell;
+    use std::ffi::OsString;
+
+    let passed = RefCell::new(Vec::new());
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("exec")
+            .options(|app| app.arg(Arg::with_name("dry-run").long("dry-run")))
+            .trailing_args(|_args, trailing, matches| {
+                assert!(matches.is_present("dry-run"));
+                passed.borrow_mut().extend_from_slice(trailing);
+                Ok(())
+            }),
+    );
+
+    assert!(commander
+        .run_with_args_result([
+            "program",
+            "exec",
+            "--dry-run",
+            "--",
+            "kubectl",
+            "get",
+            "pods",
+            "--help"
+        ])
+        .is_ok());
+
+    let expected: Vec<OsString> = vec![
+        "kubectl".into(),
+        "get".into(),
+        "pods".into(),
+        "--help".into(),
+    ];
+    assert_eq!(*passed.borrow(), expected);
+}
+
+#[cfg(unix)]
+#[test]
+fn invalid_utf8() {
+    use std::cell::RefCell;
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let name = OsString::from_vec(b"caf\xe9.log".to_vec());
+    let seen = RefCell::new(None);
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Command::new("clean")
+                .options(|app| app.arg(Arg::with_name("file").required(true)))
+                .runner_with_context(|_args, _matches, ctx| {
+                    *seen.borrow_mut() = Some((ctx.value_of_os("file"), ctx.value_of("file")));
+                    Ok(())
+                }),
+        )
+        .allow_invalid_utf8();
+
+    let args = vec![
+        OsString::from_vec(b"prog\xff".to_vec()),
+        "clean".into(),
+        name.clone(),
+    ];
+
+    assert!(commander.run_with_args_result(args).is_ok());
+    assert_eq!(
+        *seen.borrow(),
+        Some((Some(name), Some("caf\u{fffd}.log".to_owned())))
+    );
+
+    match commander.run_with_args_status([OsString::from_vec(b"prog\xff".to_vec()), "nope".into()])
+    {
+        ExitStatus::ParseError(err) => assert!(err.message.contains("prog\u{fffd} [SUBCOMMAND]")),
+        status => panic!("unexpected status: {:?}", status),
+    }
+}
+
+#[test]
+fn response_files() {
+    let file = std::env::temp_dir().join(format!("clap-nested-args-{}.txt", std::process::id()));
+    std::fs::write(&file, "show\n--env prod\n").unwrap();
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Command::new("show")
+                .options(|app| app.arg(Arg::with_name("env").long("env").takes_value(true)))
+                .runner(|_args, matches| {
+                    assert_eq!(matches.value_of("env"), Some("prod"));
+                    Ok(())
+                }),
+        )
+        .response_files();
+
+    assert!(commander
+        .run_with_args_result(["program".to_owned(), format!("@{}", file.display())])
+        .is_ok());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn preprocess() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Command::new("show")
+                .options(|app| app.arg(Arg::with_name("env").long("env").takes_value(true)))
+                .runner(|_args, matches| {
+                    assert_eq!(matches.value_of("env"), Some("prod"));
+                    Ok(())
+                }),
+        )
+        // Translates the legacy `-E` flag
+        .preprocess(|args| {
+            args.into_iter()
+                .map(|arg| if arg == "-E" { "--env".into() } else { arg })
+                .collect()
+        });
+
+    assert!(commander
+        .run_with_args_result(["program", "show", "-E", "prod"])
+        .is_ok());
+}
+
+#[test]
+fn confirm() {
+    use std::cell::Cell;
+    use std::io::IsTerminal;
+
+    let deleted = Cell::new(0);
+
+    let cluster = Commander::new().add_cmd(
+        Command::new("delete")
+            .confirm("This will delete the cluster. Continue?")
+            .runner(|_args, _matches| {
+                deleted.set(deleted.get() + 1);
+                Ok(())
+            }),
+    );
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .with_docs()
+        .add_cmd(cluster.into_cmd("cluster"));
+
+    // Also part of the app outside of parsing
+    let run = commander.run_capture(["program", "docs"]);
+    assert!(run.stdout.contains("`-y, --yes`"));
+
+    assert!(commander
+        .run_with_args_result(["program", "--yes", "cluster", "delete"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "cluster", "delete", "-y"])
+        .is_ok());
+    assert_eq!(deleted.get(), 2);
+
+    if !std::io::stdin().is_terminal() {
+        assert!(commander
+            .run_with_args_result(["program", "cluster", "delete"])
+            .is_err());
+        assert_eq!(deleted.get(), 2);
+    }
+
+    // Apps with their own `yes` keep it
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program").arg(
+                Arg::with_name("yes")
+                    .long("yes")
+                    .help("Assumes yes")
+                    .global(true),
+            )
+        })
+        .with_docs()
+        .add_cmd(Command::new("delete").confirm("Continue?").runner(
+            |_args, _matches| {
+                deleted.set(deleted.get() + 1);
+                Ok(())
+            },
+        ));
+
+    let run = commander.run_capture(["program", "docs"]);
+    assert!(run.stdout.contains("- `--yes`: Assumes yes"));
+    assert!(commander
+        .run_with_args_result(["program", "delete", "--yes"])
+        .is_ok());
+    assert_eq!(deleted.get(), 3);
+
+    // Whatever its name
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program")
+                .arg(Arg::with_name("assume-yes").long("yes").global(true))
+        })
+        .add_cmd(Command::new("delete").confirm("Continue?").runner(
+            |_args, _matches| {
+                deleted.set(deleted.get() + 1);
+                Ok(())
+            },
+        ));
+
+    assert!(commander
+        .run_with_args_result(["program", "delete", "--yes"])
+        .is_ok());
+    assert_eq!(deleted.get(), 4);
+}
+
+#[test]
+fn typed_values() {
+    use std::cell::Cell;
+
+    let port = Cell::new(0);
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("serve")
+            .options(|app| {
+                app.arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .default_value("8080"),
+                )
+            })
+            .runner(|_args, matches| {
+                port.set(matches.get::<u16>("port")?.unwrap());
+                Ok(())
+            }),
+    );
+
+    assert!(commander.run_with_args_result(["program", "serve"]).is_ok());
+    assert_eq!(port.get(), 8080);
+
+    let err = commander
+        .run_with_args_result(["program", "serve", "--port", "http"])
+        .unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::ValueValidation);
+    assert_eq!(port.get(), 8080);
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn from_spec() {
+    use clap_nested::Spec;
+    use std::cell::RefCell;
+
+    let spec = Spec::from_yaml(
+        "name: program\n\
+         args:\n  \
+           - { name: env, long: env, default: dev, global: true }\n\
+         commands:\n  \
+           - name: cluster\n    \
+             commands:\n      \
+               - name: delete\n        \
+                 args:\n          \
+                   - { name: force, long: force }\n          \
+                   - { name: name, required: true, takes_value: true }\n      \
+               - name: create\n",
+    )
+    .unwrap();
+
+    let deleted = RefCell::new(Vec::new());
+
+    let commander = Commander::from_spec(&spec).bind("cluster.delete", |matches, ctx| {
+        deleted.borrow_mut().push(format!(
+            "{} {} {}",
+            matches.value_of("name").unwrap(),
+            matches.is_present("force"),
+            ctx.value_of("env").unwrap()
+        ));
+        Ok(())
+    });
+
+    assert!(commander
+        .run_with_args_result(["program", "cluster", "delete", "foo", "--force"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "--env", "prod", "cluster", "delete", "bar"])
+        .is_ok());
+    assert_eq!(*deleted.borrow(), ["foo true dev", "bar false prod"]);
+
+    let err = commander
+        .run_with_args_result(["program", "cluster"])
+        .unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::HelpDisplayed);
+    assert!(commander
+        .run_with_args_result(["program", "cluster", "create"])
+        .is_err());
+}
+
+#[test]
+fn validate() {
+    use std::cell::Cell;
+
+    let ran = Cell::new(false);
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("deploy")
+            .description("Deploys the app")
+            .options(|app| {
+                app.arg(Arg::with_name("canary").long("canary"))
+                    .arg(Arg::with_name("all").long("all"))
+            })
+            .validate(|_ctx, matches| {
+                if matches.is_present("canary") && matches.is_present("all") {
+                    return Err("--canary can't deploy --all".to_owned());
+                }
+
+                Ok(())
+            })
+            .runner(|_args, _matches| {
+                ran.set(true);
+                Ok(())
+            }),
+    );
+
+    let err = match commander.run_with_args_status(["program", "deploy", "--canary", "--all"]) {
+        ExitStatus::ParseError(err) => err,
+        status => panic!("unexpected status: {:?}", status),
+    };
+    assert_eq!(err.kind, clap::ErrorKind::ValueValidation);
+    assert!(err
+        .message
+        .starts_with("error: --canary can't deploy --all\n\n"));
+    assert!(err.message.contains("Deploys the app"));
+    assert!(err.message.contains("program deploy [FLAGS]"));
+    assert_eq!(commander.exit_code_for(&err), 1);
+    assert!(!ran.get());
+
+    assert!(commander
+        .run_with_args_result(["program", "deploy", "--canary"])
+        .is_ok());
+    assert!(ran.get());
+}
+
+#[test]
+fn require_arg() {
+    use std::cell::Cell;
+
+    let ran = Cell::new(false);
+
+    let commander =
+        Commander::new()
+            .options(|app| {
+                app.name("program").arg(
+                    Arg::with_name("environment")
+                        .long("environment")
+                        .value_name("ENV")
+                        .global(true),
+                )
+            })
+            .add_cmd(
+                Commander::new()
+                    .add_cmd(Command::new("foo").description("Shows foo").runner(
+                        |_args, _matches| {
+                            ran.set(true);
+                            Ok(())
+                        },
+                    ))
+                    .into_cmd("show"),
+            )
+            .require_arg("environment");
+
+    let err = match commander.run_with_args_status(["program", "show", "foo"]) {
+        ExitStatus::ParseError(err) => err,
+        status => panic!("unexpected status: {:?}", status),
+    };
+    assert_eq!(err.kind, clap::ErrorKind::MissingRequiredArgument);
+    assert!(err
+        .message
+        .contains("required arguments were not provided:\n    --environment <ENV>\n\n"));
+    assert!(err.message.contains("Shows foo"));
+    assert!(!ran.get());
+
+    assert!(commander
+        .run_with_args_result(["program", "show", "foo", "--environment", "prod"])
+        .is_ok());
+    assert!(ran.get());
+}
+
+#[test]
+fn global_groups() {
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program")
+                .arg(Arg::with_name("json").long("json").global(true))
+                .arg(Arg::with_name("yaml").long("yaml").global(true))
+                .arg(
+                    Arg::with_name("user")
+                        .long("user")
+                        .value_name("USER")
+                        .global(true),
+                )
+                .arg(
+                    Arg::with_name("password")
+                        .long("password")
+                        .value_name("PASSWORD")
+                        .global(true),
+                )
+        })
+        .add_cmd(Command::new("show").runner(|_args, _matches| Ok(())))
+        .exclusive_args(&["json", "yaml"])
+        .args_together(&["user", "password"]);
+
+    let error = |args: &[&str]| match commander.run_with_args_status(args.to_vec()) {
+        ExitStatus::ParseError(err) => err,
+        status => panic!("unexpected status: {:?}", status),
+    };
+
+    let err = error(&["program", "--json", "show", "--yaml"]);
+    assert_eq!(err.kind, clap::ErrorKind::ArgumentConflict);
+    assert!(err
+        .message
+        .contains("The argument '--json' cannot be used with '--yaml'"));
+
+    let err = error(&["program", "show", "--user", "foo"]);
+    assert_eq!(err.kind, clap::ErrorKind::MissingRequiredArgument);
+    assert!(err
+        .message
+        .contains("The argument '--user <USER>' requires '--password <PASSWORD>'"));
+
+    assert!(commander
+        .run_with_args_result([
+            "program",
+            "--yaml",
+            "show",
+            "--user",
+            "foo",
+            "--password",
+            "bar"
+        ])
+        .is_ok());
+}
+
+#[test]
+#[should_panic(expected = "The subcommands `show` and `display` are both named `show`")]
+fn duplicate_cmd() {
+    let _ = Commander::new()
+        .add_cmd(Command::<()>::new("show"))
+        .add_cmd(Command::new("display").options(|app| app.alias("show")));
+}
+
+#[test]
+#[should_panic(expected = "The subcommands `show` and `display` are both named `show`")]
+fn duplicate_replaced_cmd() {
+    let _ = Commander::new()
+        .add_cmd(Command::<()>::new("show"))
+        .add_cmd(Command::new("display"))
+        .replace_cmd(Command::new("display").options(|app| app.alias("show")));
+}
+
+#[test]
+fn add_cmds() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+
+    let cmds: Vec<_> = ["foo", "bar"]
+        .iter()
+        .map(|&name| {
+            let ran = &ran;
+            Command::new(name).runner(move |_args, _matches| {
+                ran.borrow_mut().push(name);
+                Ok(())
+            })
+        })
+        .collect();
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmds(cmds);
+
+    assert!(commander.run_with_args_result(["program", "bar"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "foo"]).is_ok());
+    assert_eq!(*ran.borrow(), ["bar", "foo"]);
+}
+
+#[test]
+fn extend() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+    let cmd = |name: &'static str, tag: &'static str| {
+        let ran = &ran;
+        Command::new(name).runner(move |_args, _matches| {
+            ran.borrow_mut().push(tag);
+            Ok(())
+        })
+    };
+
+    let base = || {
+        Commander::new()
+            .options(|app| app.name("program"))
+            .add_cmd(cmd("build", "base build"))
+    };
+    let other = || {
+        Commander::new()
+            .options(|app| app.arg(Arg::with_name("env").long("env").takes_value(true)))
+            .add_cmd(cmd("build", "other build"))
+            .add_cmd(cmd("deploy", "other deploy"))
+    };
+
+    let kept = base().extend_with(other(), Conflict::Keep);
+    assert!(kept
+        .run_with_args_result(["program", "--env", "prod", "deploy"])
+        .is_ok());
+    assert!(kept.run_with_args_result(["program", "build"]).is_ok());
+
+    let replaced = base().extend_with(other(), Conflict::Replace);
+    assert!(replaced.run_with_args_result(["program", "build"]).is_ok());
+
+    assert_eq!(*ran.borrow(), ["other deploy", "base build", "other build"]);
+
+    let all = base().extend(Commander::new().add_cmd(cmd("deploy", "deploy")));
+    assert!(all.run_with_args_result(["program", "deploy"]).is_ok());
+
+    let conflicting = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        base().extend(other());
+    }));
+    assert!(conflicting.is_err());
+}
+
+#[test]
+fn remove_and_replace_cmd() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+    let cmd = |name: &'static str, tag: &'static str| {
+        let ran = &ran;
+        Command::new(name).runner(move |_args, _matches| {
+            ran.borrow_mut().push(tag);
+            Ok(())
+        })
+    };
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(cmd("build", "build"))
+        .add_cmd(cmd("deploy", "deploy"))
+        .add_cmd(cmd("debug", "debug"))
+        .remove_cmd("debug")
+        .replace_cmd(cmd("deploy", "custom deploy"));
+
+    assert!(commander
+        .run_with_args_result(["program", "deploy"])
+        .is_ok());
+    assert!(commander.run_with_args_result(["program", "build"]).is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "debug"])
+        .is_err());
+    assert_eq!(*ran.borrow(), ["custom deploy", "build"]);
+}
+
+#[test]
+fn add_cmd_if() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd_if(true, Command::new("foo"))
+        .add_cmd_if(false, Command::new("bar"))
+        .add_cmd_when(|| true, || Command::new("baz"))
+        .add_cmd_when(
+            || false,
+            || -> Command<()> { panic!("built a disabled command") },
+        );
+
+    assert!(commander.run_with_args_result(["program", "foo"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "bar"]).is_err());
+    assert!(commander.run_with_args_result(["program", "baz"]).is_ok());
+}
+
+#[test]
+fn enabled() {
+    use std::cell::Cell;
+
+    let experimental = Cell::new(false);
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("stable"))
+        .add_cmd(
+            Command::new("preview")
+                .description("Previews the next release")
+                .enabled(|_env| experimental.get()),
+        );
+
+    let help = match commander.run_with_args_status(["program", "--help"]) {
+        ExitStatus::HelpShown(help) => help,
+        status => panic!("unexpected status: {:?}", status),
+    };
+    assert!(!help.contains("preview"));
+
+    let err = commander
+        .run_with_args_result(["program", "preview"])
+        .unwrap_err();
+    assert!(err
+        .message
+        .contains("The command `preview` is not enabled in this build or environment"));
+
+    experimental.set(true);
+
+    let help = match commander.run_with_args_status(["program", "--help"]) {
+        ExitStatus::HelpShown(help) => help,
+        status => panic!("unexpected status: {:?}", status),
+    };
+    assert!(help.contains("Previews the next release"));
+    assert!(commander
+        .run_with_args_result(["program", "preview"])
+        .is_ok());
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn enabled_from_config() {
+    let file = std::env::temp_dir().join(format!("clap-nested-flags-{}.toml", std::process::id()));
+    std::fs::write(&file, "[flags]\npreview = \"on\"\n").unwrap();
+
+    let commander = |file: &std::path::Path| {
+        Commander::new()
+            .options(|app| app.name("program"))
+            .add_cmd(
+                Command::new("preview")
+                    .description("Previews the next release")
+                    .enabled(|env| env.config().get("flags.preview").as_deref() == Some("on")),
+            )
+            .config_file(file)
+    };
+
+    assert!(commander(&file)
+        .run_capture(["program", "--help"])
+        .stdout
+        .contains("Previews the next release"));
+    assert_eq!(commander(&file).run_capture(["program", "preview"]).status, 0);
+
+    std::fs::write(&file, "[flags]\npreview = \"off\"\n").unwrap();
+
+    assert!(!commander(&file)
+        .run_capture(["program", "--help"])
+        .stdout
+        .contains("Previews the next release"));
+    assert!(commander(&file)
+        .run_capture(["program", "preview"])
+        .stderr
+        .contains("The command `preview` is not enabled in this build or environment"));
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn guard() {
+    use std::cell::Cell;
+
+    let admin = Cell::new(false);
+    let dropped = Cell::new(false);
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Commander::new()
+            .add_cmd(
+                Command::new("drop")
+                    .guard(|_ctx| match admin.get() {
+                        true => Ok(()),
+                        false => Err(DenyReason::from("only admins can drop databases")),
+                    })
+                    .runner(|_args, _matches| {
+                        dropped.set(true);
+                        Ok(())
+                    }),
+            )
+            .into_cmd("db"),
+    );
+
+    let err = commander
+        .run_with_args_result(["program", "db", "drop"])
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "error: Permission denied to run `db drop`: only admins can drop databases"
+    );
+    assert!(!dropped.get());
+
+    admin.set(true);
+    assert!(commander
+        .run_with_args_result(["program", "db", "drop"])
+        .is_ok());
+    assert!(dropped.get());
+}
+
+#[test]
+fn lazy_subcommands() {
+    use std::cell::Cell;
+
+    let built = Cell::new(0);
+    let ran = Cell::new(0);
+
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program")
+                .arg(Arg::with_name("env").long("env").takes_value(true))
+        })
+        .add_cmd(Command::new("foo"))
+        .add_cmd(
+            Commander::new()
+                .add_cmd(
+                    Command::new("deep")
+                        .options(|app| {
+                            built.set(built.get() + 1);
+                            app.arg(Arg::with_name("force").long("force"))
+                        })
+                        .runner(|_args, _matches| {
+                            ran.set(ran.get() + 1);
+                            Ok(())
+                        }),
+                )
+                .into_cmd("big")
+                .description("Has many subcommands"),
+        );
+
+    // Adding a command builds it once to check its name
+    let before = built.get();
+
+    assert!(commander.run_with_args_result(["program", "foo"]).is_ok());
+    assert_eq!(built.get(), before);
+
+    assert!(commander
+        .run_with_args_result(["program", "big", "deep", "--force"])
+        .is_ok());
+    assert_eq!(ran.get(), 1);
+
+    // The value of `--env` is mistaken for the subcommand at first
+    assert!(commander
+        .run_with_args_result(["program", "--env", "prod", "big", "deep", "--force"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "--env", "big", "big", "deep"])
+        .is_ok());
+    assert_eq!(ran.get(), 3);
+
+    let help = match commander.run_with_args_status(["program", "--env", "big", "big", "--help"]) {
+        ExitStatus::HelpShown(help) => help,
+        status => panic!("unexpected status: {:?}", status),
+    };
+    assert!(help.contains("deep"));
+}
+
+#[test]
+fn dispatch_after_remove() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+    let cmd = |name: &'static str| {
+        let ran = &ran;
+        Command::new(name)
+            .options(move |app| app.alias(&name[..1]))
+            .runner(move |_args, _matches| {
+                ran.borrow_mut().push(name);
+                Ok(())
+            })
+    };
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(cmd("apply"))
+        .add_cmd(cmd("build"))
+        .add_cmd(cmd("check"))
+        .remove_cmd("apply")
+        .no_cmd_policy(NoCmd::Default("c"));
+
+    assert!(commander.run_with_args_result(["program", "b"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "check"]).is_ok());
+    assert!(commander.run_with_args_result(["program"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "a"]).is_err());
+    assert_eq!(*ran.borrow(), ["build", "check", "check"]);
+}
+
+#[test]
+fn trimmed_help() {
+    use std::cell::Cell;
+
+    let built = Cell::new(0);
+    let commander = |trimmed: bool, lazy: bool| {
+        let built = &built;
+
+        Commander::new()
+            .options(|app| app.name("program"))
+            .trimmed_help(trimmed)
+            .lazy(lazy)
+            .add_cmd(Command::new("foo").description("Does foo"))
+            .add_cmd(
+                Command::new("bar")
+                    .description("Does bar")
+                    .options(move |app| {
+                        built.set(built.get() + 1);
+                        app.visible_alias("b")
+                    }),
+            )
+    };
+
+    let trimmed = commander(true, true);
+    let before = built.get();
+    assert!(trimmed.run_with_args_result(["program", "foo"]).is_ok());
+    assert_eq!(built.get(), before);
+    assert!(trimmed.run_with_args_result(["program", "b"]).is_ok());
+    assert!(built.get() > before);
+
+    let help = match trimmed.run_with_args_status(["program", "--help"]) {
+        ExitStatus::HelpShown(help) => help,
+        status => panic!("unexpected status: {:?}", status),
+    };
+    assert!(help.contains("Does bar"));
+    assert!(!help.contains("aliases"));
+
+    let eager = commander(false, false);
+    let before = built.get();
+    assert!(eager.run_with_args_result(["program", "foo"]).is_ok());
+    assert!(built.get() > before);
+}
+
+#[test]
+fn cloned_subtree() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+    let ran = &ran;
+    let deploy = Command::new("deploy").runner_with_context(move |_args, _matches, ctx| {
+        ran.borrow_mut().push(ctx.path().join(" "));
+        Ok(())
+    });
+    let leaves = Commander::new()
+        .add_cmd(deploy.clone().description("Deploys"))
+        .add_cmd(Command::new("status"));
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(leaves.clone().into_cmd("prod"))
+        .add_cmd(leaves.into_cmd("staging"))
+        .add_cmd(deploy);
+
+    assert!(commander
+        .run_with_args_result(["program", "prod", "deploy"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "staging", "deploy"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "deploy"])
+        .is_ok());
+    assert_eq!(*ran.borrow(), ["prod deploy", "staging deploy", "deploy"]);
+}
+
+#[test]
+fn debug_output() {
+    let commander = Commander::new()
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo").runner(|_args: &(), _matches| Ok(())))
+                .no_cmd_policy(NoCmd::Default("foo"))
+                .into_cmd("show")
+                .description("Shows things"),
+        )
+        .add_cmd(Command::new("what").description("Asks"));
+
+    assert_eq!(
+        format!("{:?}", commander),
+        "Commander { cmds: [\
+         MultiCommand { name: \"show\", description: Some(\"Shows things\"), \
+         cmds: [Command { name: \"foo\", description: None, .. }], \
+         no_cmd: Default(\"foo\"), .. }, \
+         Command { name: \"what\", description: Some(\"Asks\"), .. }\
+         ], no_cmd: Help, .. }"
+    );
+}
+
+#[test]
+fn collect_commands() {
+    use clap_nested::CommandLike;
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+    let ran = &ran;
+    let commander: Commander<(), ()> = ["apply", "build"]
+        .iter()
+        .map(|&name| -> Box<dyn CommandLike<()>> {
+            Box::new(Command::new(name).runner(move |_args, _matches| {
+                ran.borrow_mut().push(name);
+                Ok(())
+            }))
+        })
+        .collect();
+    let commander = commander.options(|app| app.name("program"));
+
+    assert!(commander.run_with_args_result(["program", "build"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "apply"]).is_ok());
+    assert_eq!(*ran.borrow(), ["build", "apply"]);
+
+    let commander: Commander<(), ()> = Default::default();
+    assert!(commander.run_with_args_result(["program"]).is_err());
+}
+
+#[test]
+fn runner_impl() {
+    use clap::ArgMatches;
+    use clap_nested::Runner;
+    use std::cell::RefCell;
+
+    struct Deploy<'a> {
+        region: String,
+        deployed: &'a RefCell<Vec<String>>,
+    }
+
+    impl<'a> Runner<str> for Deploy<'a> {
+        fn run(&self, env: &str, matches: &ArgMatches<'_>) -> Result<(), clap::Error> {
+            let service = matches.value_of("service").unwrap();
+
+            self.deployed
+                .borrow_mut()
+                .push(format!("{} to {} in {}", service, env, self.region));
+            Ok(())
+        }
+    }
+
+    let deployed = RefCell::new(Vec::new());
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .args(|_args, _matches| "prod")
+        .add_cmd(
+            Command::new("deploy")
+                .options(|app| app.arg(Arg::with_name("service").required(true)))
+                .runner_impl(Deploy {
+                    region: "eu-west-1".to_owned(),
+                    deployed: &deployed,
+                }),
+        );
+
+    assert!(commander
+        .run_with_args_result(["program", "deploy", "api"])
+        .is_ok());
+    assert_eq!(*deployed.borrow(), ["api to prod in eu-west-1"]);
+}
+
+#[test]
+fn runner_mut_and_once() {
+    use clap::{Error, ErrorKind};
+
+    let mut left = 2;
+    let mut unknown = 0;
+    let report = String::from("report");
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("spend").runner_mut(move |_args, _matches| {
+            if left == 0 {
+                return Err(Error::with_description("Nothing left", ErrorKind::Io));
+            }
+
+            left -= 1;
+            Ok(())
+        }))
+        .add_cmd(Command::new("send").runner_once(move |_args, _matches| {
+            let sent = report;
+
+            assert_eq!(sent, "report");
+            Ok(())
+        }))
+        .no_cmd_mut(move |_args, _matches| {
+            unknown += 1;
+            assert_eq!(unknown, 1);
+            Ok(())
+        });
+
+    assert!(commander.run_with_args_result(["program", "spend"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "spend"]).is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "spend"])
+        .is_err());
+
+    assert!(commander.run_with_args_result(["program", "send"]).is_ok());
+    let err = commander
+        .run_with_args_result(["program", "send"])
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::UnexpectedMultipleUsage);
+    assert!(err.message.contains("The command 'send' can only run once"));
+
+    assert!(commander.run_with_args_result(["program"]).is_ok());
+}
+
+#[test]
+fn runner_mut_reentrant() {
+    use clap::ErrorKind;
+    use std::cell::OnceCell;
+    use std::rc::Rc;
+
+    let cell: Rc<OnceCell<Commander<(), ()>>> = Rc::new(OnceCell::new());
+    let inner = cell.clone();
+    let mut runs = 0;
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("again").runner_mut(move |_args, _matches| {
+            runs += 1;
+            assert_eq!(runs, 1);
+            inner.get().unwrap().run_with_args_result(["program", "again"])
+        }),
+    );
+    let _ = cell.set(commander);
+
+    let err = cell
+        .get()
+        .unwrap()
+        .run_with_args_result(["program", "again"])
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::UnexpectedMultipleUsage);
+    assert!(err.message.contains("The command 'again' is already running"));
+}
+
+#[test]
+fn fallible_args() {
+    use clap::{ArgMatches, Error, ErrorKind};
+    use std::cell::RefCell;
+
+    fn env<'x>(matches: &'x ArgMatches<'_>) -> Result<&'x str, Error> {
+        match matches.value_of("env").unwrap_or("dev") {
+            env @ ("dev" | "prod") => Ok(env),
+            env => Err(Error::with_description(
+                &format!("Unknown environment '{}'", env),
+                ErrorKind::InvalidValue,
+            )),
+        }
+    }
+
+    fn env_arg<'x, 'y>(app: clap::App<'x, 'y>) -> clap::App<'x, 'y> {
+        app.name("program")
+            .arg(Arg::with_name("env").long("env").takes_value(true))
+    }
+
+    let seen = RefCell::new(Vec::new());
+    let seen = &seen;
+    let borrowed = Commander::new()
+        .options(env_arg)
+        .try_args(|_args, matches| env(matches))
+        .add_cmd(Command::new("show").runner(move |env: &str, _matches| {
+            seen.borrow_mut().push(env.to_owned());
+            Ok(())
+        }));
+    let owned = Commander::new()
+        .options(env_arg)
+        .try_args_owned(|_args, matches| Ok(format!("{}.example.com", env(matches)?)))
+        .add_cmd(Command::new("show").runner(move |host: &String, _matches| {
+            seen.borrow_mut().push(host.clone());
+            Ok(())
+        }));
+
+    assert!(borrowed
+        .run_with_args_result(["program", "--env", "prod", "show"])
+        .is_ok());
+    assert!(owned.run_with_args_result(["program", "show"]).is_ok());
+    assert_eq!(*seen.borrow(), ["prod", "dev.example.com"]);
+
+    let args = ["program", "--env", "qa", "show"];
+
+    for err in [
+        borrowed.run_with_args_result(args).unwrap_err(),
+        owned.run_with_args_result(args).unwrap_err(),
+    ] {
+        assert_eq!(err.kind, ErrorKind::InvalidValue);
+        assert!(err.message.contains("Unknown environment 'qa'"));
+    }
+}
+
+#[test]
+fn runner_with_help() {
+    use clap::{Error, ErrorKind, SubCommand};
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("deploy")
+            .description("Deploys services")
+            .options(|app| {
+                app.arg(Arg::with_name("all").long("all"))
+                    .arg(Arg::with_name("service"))
+                    .subcommand(SubCommand::with_name("status").about("Shows the status"))
+            })
+            .runner_with_help(|_args, matches, help| {
+                if matches.is_present("all") || matches.is_present("service") {
+                    return Ok(());
+                }
+
+                Err(Error::with_description(
+                    &format!("{}\n{}", help, help.cmd("status").unwrap()),
+                    ErrorKind::MissingRequiredArgument,
+                ))
+            }),
+    );
+
+    assert!(commander
+        .run_with_args_result(["program", "deploy", "--all"])
+        .is_ok());
+
+    let err = commander
+        .run_with_args_result(["program", "deploy"])
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MissingRequiredArgument);
+    assert!(err.message.contains("Deploys services"));
+    assert!(err.message.contains("Shows the status"));
+    assert!(err.message.contains("program deploy status"));
+}
+
+#[test]
+fn context_for() {
+    use std::cell::{Cell, RefCell};
+
+    let built = Cell::new(0);
+    let seen = RefCell::new(Vec::new());
+    let (built, seen) = (&built, &seen);
+    let record = move |client: &Option<String>, _matches: &clap::ArgMatches<'_>| {
+        seen.borrow_mut().push(client.clone());
+        Ok(())
+    };
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .context_for(move |path, _matches| {
+            built.set(built.get() + 1);
+
+            match path.segments()[0].as_str() {
+                "deploy" => Some(format!("client for {}", path)),
+                _ => None,
+            }
+        })
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("api").runner(record))
+                .into_cmd("deploy"),
+        )
+        .add_cmd(Command::new("status").runner(record));
+
+    assert!(commander.run_with_args_result(["program"]).is_err());
+    assert!(commander
+        .run_with_args_result(["program", "--help"])
+        .is_err());
+    assert_eq!(built.get(), 0);
+
+    assert!(commander
+        .run_with_args_result(["program", "status"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "deploy", "api"])
+        .is_ok());
+    assert_eq!(built.get(), 2);
+    assert_eq!(
+        *seen.borrow(),
+        [None, Some("client for deploy api".to_owned())]
+    );
+}
+
+#[test]
+fn services() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Config {
+        url: &'static str,
+    }
+
+    struct Pool {
+        url: &'static str,
+    }
+
+    let built = Cell::new(0);
+    let built = &built;
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .provide(move |_ctx| {
+            built.set(built.get() + 1);
+            Config {
+                url: "postgres://localhost",
+            }
+        })
+        .provide(move |ctx| Pool {
+            url: ctx.service::<Config>().url,
+        })
+        .add_cmd(
+            Command::new("migrate").runner_with_context(|_args, _matches, ctx| {
+                let pool = ctx.service::<Pool>();
+
+                assert_eq!(pool.url, "postgres://localhost");
+                assert!(Rc::ptr_eq(&pool, &ctx.service::<Pool>()));
+                assert_eq!(ctx.service::<Config>().url, pool.url);
+                Ok(())
+            }),
+        )
+        .add_cmd(Command::new("version"));
+
+    assert!(commander
+        .run_with_args_result(["program", "version"])
+        .is_ok());
+    assert_eq!(built.get(), 0);
+
+    assert!(commander
+        .run_with_args_result(["program", "migrate"])
+        .is_ok());
+    assert_eq!(built.get(), 1);
+
+    assert!(commander
+        .run_with_args_result(["program", "migrate"])
+        .is_ok());
+    assert_eq!(built.get(), 2);
+}
+
+#[test]
+fn with_env() {
+    use std::cell::RefCell;
+
+    let _env = lock_env();
+
+    let seen = RefCell::new(Vec::new());
+    let seen = &seen;
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("sync")
+            .with_env([("CLAP_NESTED_TEST_LOG", "debug")])
+            .runner(move |_args, _matches| {
+                seen.borrow_mut()
+                    .push(std::env::var("CLAP_NESTED_TEST_LOG").unwrap());
+                Ok(())
+            }),
+    );
+
+    std::env::set_var("CLAP_NESTED_TEST_LOG", "warn");
+
+    assert!(commander.run_with_args_result(["program", "sync"]).is_ok());
+    assert_eq!(*seen.borrow(), ["debug"]);
+    assert_eq!(std::env::var("CLAP_NESTED_TEST_LOG").unwrap(), "warn");
+}
+
+#[test]
+fn dry_run() {
+    use std::cell::RefCell;
+
+    let ran = RefCell::new(Vec::new());
+    let ran = &ran;
+    let commander =
+        Commander::new()
+            .options(|app| app.name("program"))
+            .with_dry_run()
+            .add_cmd(
+                Command::new("plan").runner_with_context(move |_args, _matches, ctx| {
+                    ran.borrow_mut().push(("plan", ctx.is_dry_run()));
+                    Ok(())
+                }),
+            )
+            .add_cmd(Command::new("apply").supports_dry_run(false).runner(
+                move |_args, _matches| {
+                    ran.borrow_mut().push(("apply", false));
+                    Ok(())
+                },
+            ));
+
+    assert!(commander
+        .run_with_args_result(["program", "--dry-run", "plan"])
+        .is_ok());
+    assert_eq!(
+        commander.run_capture(["program", "apply", "--dry-run"]).stderr,
+        "Skipping `apply`, which doesn't support --dry-run\n"
+    );
+    assert!(commander.run_with_args_result(["program", "plan"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "apply"]).is_ok());
+    assert_eq!(
+        *ran.borrow(),
+        [("plan", true), ("plan", false), ("apply", false)]
+    );
+}
+
+#[test]
+fn single_instance() {
+    use clap::ErrorKind;
+
+    let name = format!("clap-nested-instance-{}", std::process::id());
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .single_instance(name.as_str())
+        .add_cmd(Command::new("sync").runner(|_args, _matches| Ok(())))
+        .add_cmd(
+            Command::new("nested")
+                .single_instance(name.as_str())
+                .runner(|_args, _matches| Ok(())),
+        );
+
+    assert!(commander.run_with_args_result(["program", "sync"]).is_ok());
+    assert!(commander.run_with_args_result(["program", "sync"]).is_ok());
+
+    let err = commander
+        .run_with_args_result(["program", "nested"])
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Io);
+    assert!(err.message.contains("Another invocation of `"));
+
+    std::fs::remove_file(std::env::temp_dir().join(format!("{}.lock", name))).unwrap();
+}
+
+#[test]
+#[cfg(not(windows))]
+fn app_dirs() {
+    use std::cell::RefCell;
+
+    let _env = lock_env();
+
+    let dirs = RefCell::new(Vec::new());
+    let dirs = &dirs;
+    let cmd = || {
+        Command::new("dirs").runner_with_context(move |_args, _matches, ctx| {
+            dirs.borrow_mut()
+                .extend(vec![ctx.config_dir(), ctx.data_dir(), ctx.cache_dir()]);
+            Ok(())
+        })
+    };
+
+    // Restored at the end, the other tests share the environment
+    let xdg: Vec<_> = ["XDG_DATA_HOME", "XDG_CACHE_HOME"]
+        .iter()
+        .map(|&name| (name, std::env::var_os(name)))
+        .collect();
+
+    std::env::set_var("XDG_DATA_HOME", "/xdg/data");
+    std::env::set_var("XDG_CACHE_HOME", "/xdg/cache");
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(cmd());
+    assert!(commander.run_with_args_result(["program", "dirs"]).is_ok());
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .config("myapp")
+        .add_cmd(cmd());
+    assert!(commander.run_with_args_result(["program", "dirs"]).is_ok());
+
+    let dirs = dirs.borrow();
+    assert!(dirs[0].as_ref().unwrap().ends_with("program"));
+    assert_eq!(dirs[1].as_deref(), Some("/xdg/data/program".as_ref()));
+    assert_eq!(dirs[2].as_deref(), Some("/xdg/cache/program".as_ref()));
+    assert_eq!(dirs[4].as_deref(), Some("/xdg/data/myapp".as_ref()));
+
+    for (name, value) in xdg {
+        match value {
+            Some(value) => std::env::set_var(name, value),
+            None => std::env::remove_var(name),
+        }
+    }
+}
+
+#[test]
+fn sensitive_args() {
+    use clap::{Arg, ErrorKind};
+    use std::cell::RefCell;
+
+    let _env = lock_env();
+
+    // Pinned, the hook isn't called for users setting it
+    std::env::remove_var("DO_NOT_TRACK");
+
+    let seen = RefCell::new(Vec::new());
+    let seen = &seen;
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .sensitive("token")
+        .add_cmd(
+            Command::new("login")
+                .options(|app| {
+                    app.arg(
+                        Arg::with_name("token")
+                            .long("token")
+                            .short("t")
+                            .env("CLAP_NESTED_TEST_SENSITIVE_TOKEN")
+                            .possible_values(&["long-enough", "from-the-env"]),
+                    )
+                })
+                .runner(|_args, matches| {
+                    Err(clap::Error::with_description(
+                        &format!("Login failed with {}", matches.value_of("token").unwrap()),
+                        ErrorKind::Io,
+                    ))
+                }),
+        )
+        .on_invocation(move |_path, matches, _outcome, _duration| {
+            let login = matches.subcommand_matches("login").unwrap();
+            seen.borrow_mut()
+                .push(login.value_of("token").unwrap().to_owned());
+        });
+
+    std::env::set_var("CLAP_NESTED_TEST_SENSITIVE_TOKEN", "from-the-env");
+
+    let err = commander
+        .run_with_args_result(["program", "login", "--help"])
+        .unwrap_err();
+    assert!(!err.message.contains("from-the-env"));
+
+    let err = commander
+        .run_with_args_result(["program", "login", "--token=short"])
+        .unwrap_err();
+    assert!(err.message.contains("'***' isn't a valid value"));
+
+    let err = commander
+        .run_with_args_result(["program", "login", "-t", "long-enough"])
+        .unwrap_err();
+    assert!(err.message.ends_with("Login failed with ***"));
+    assert_eq!(*seen.borrow(), ["***"]);
+}
+
+#[test]
+fn doctor() {
+    use clap_nested::{Check, Diagnosis};
+    use std::cell::RefCell;
+
+    let paths = RefCell::new(Vec::new());
+    let paths = &paths;
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .with_doctor()
+        .check(Check::new("git", move |ctx| {
+            paths.borrow_mut().push(ctx.path().join(" "));
+            Diagnosis::pass()
+        }))
+        .add_cmd(Command::new("show"));
+
+    let run = commander.run_capture(["program", "doctor"]);
+    assert_eq!(run.status, 0);
+    assert!(run.stdout.starts_with("[ ok ] git"));
+    assert_eq!(*paths.borrow(), ["doctor"]);
+
+    let commander = commander.check(Check::new("network", |_ctx| {
+        Diagnosis::fail("Cannot reach the API").fix("Check the proxy settings")
+    }));
+    let run = commander.run_capture(["program", "doctor"]);
+    assert!(run.stdout.contains("[FAIL] network: Cannot reach the API"));
+    assert!(run.stderr.contains("1 of 2 checks failed"));
+
+    let err = commander.run_with_args_result(["program"]).unwrap_err();
+    assert!(err.message.contains("Checks the environment for problems"));
+}
+
+#[test]
+fn docs() {
+    use clap::ErrorKind;
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .with_docs()
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo"))
+                .into_cmd("show"),
+        );
+
+    let run = commander.run_capture(["program", "docs", "show", "foo", "--format", "man"]);
+    assert_eq!(run.status, 0);
+    assert!(run.stdout.starts_with(".TH PROGRAM\\-SHOW\\-FOO 1"));
+
+    let err = commander
+        .run_with_args_result(["program", "docs", "show", "bar"])
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidSubcommand);
+    assert!(err
+        .message
+        .contains("No subcommand named 'bar' in `program show`"));
+}
+
+#[test]
+#[cfg(feature = "self-update")]
+fn self_update() {
+    use clap_nested::SelfUpdate;
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .with_self_update(SelfUpdate::new(
+            "https://example.com/{version}/program-{target}",
+            |url| Ok(url.as_bytes().to_vec()),
+            |_binary| Err("bad signature".to_owned()),
+        ));
+
+    let err = commander
+        .run_with_args_result(["program", "self", "update", "1.2.0"])
+        .unwrap_err();
+    assert!(err
+        .message
+        .contains("The download from https://example.com/1.2.0/program-"));
+    assert!(err.message.contains("failed verification: bad signature"));
+}
+
+#[test]
+fn completions() {
+    let path = std::env::temp_dir().join(format!("clap-nested-bash-{}", std::process::id()));
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .with_completions()
+        .add_cmd(Command::new("show"));
+
+    let run = commander.run_capture([
+        "program",
+        "completions",
+        "install",
+        "bash",
+        "--path",
+        path.to_str().unwrap(),
+    ]);
+    assert_eq!(run.status, 0);
+    assert!(run.stdout.starts_with("Installed the bash completions"));
+
+    let script = std::fs::read_to_string(&path).unwrap();
+    assert!(script.contains("complete -F _program"));
+    assert!(script.contains("show"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn completion_backend() {
+    struct Xonsh;
+
+    impl CompletionBackend for Xonsh {
+        fn shell(&self) -> &'static str {
+            "xonsh"
+        }
+
+        fn generate(&self, app: &clap::App<'_, '_>, bin_name: &str) -> String {
+            let names: Vec<_> = app
+                .p
+                .subcommands
+                .iter()
+                .map(|cmd| &*cmd.p.meta.name)
+                .collect();
+            format!("{}: {}\n", bin_name, names.join(" "))
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!("clap-nested-xonsh-{}", std::process::id()));
+    let commander = || {
+        Commander::new()
+            .options(|app| app.name("program"))
+            .with_completions()
+            .completion_backend(Xonsh)
+            .add_cmd(Command::new("show"))
+    };
+    let install = |shell: &str| {
+        commander().run_capture([
+            "program",
+            "completions",
+            "install",
+            shell,
+            "--path",
+            path.to_str().unwrap(),
+        ])
+    };
+
+    assert!(install("xonsh").stdout.starts_with("Installed the xonsh completions"));
+    assert!(std::fs::read_to_string(&path)
+        .unwrap()
+        .starts_with("program: show"));
+    assert_eq!(install("nushell").status, 0);
+    assert!(std::fs::read_to_string(&path)
+        .unwrap()
+        .contains("export extern \"program show\""));
+    assert_eq!(install("tcsh").status, 1);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn error_codes() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .explain("E042", "The API cannot be reached, check your network.\n")
+        .add_cmd(Command::new("fetch").runner(|_, _| {
+            Err(clap::Error::with_code(
+                "E042",
+                "Cannot reach the API",
+                clap::ErrorKind::Io,
+            ))
+        }));
+
+    let err = commander
+        .run_with_args_result(["program", "fetch"])
+        .unwrap_err();
+    assert_eq!(err.code().as_deref(), Some("E042"));
+    assert!(err.message.contains("Cannot reach the API"));
+    assert!(commander
+        .run_capture(["program", "fetch"])
+        .stderr
+        .ends_with("\nFor more information about this error, try `program explain E042`.\n"));
+
+    assert_eq!(
+        commander.run_capture(["program", "explain", "E042"]).stdout,
+        "The API cannot be reached, check your network.\n"
+    );
+    assert!(commander
+        .run_with_args_result(["program", "explain", "E001"])
+        .is_err());
+}
+
+#[test]
+#[cfg(feature = "i18n")]
+fn translations() {
+    use clap_nested::Translations;
+
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .translations(
+            Translations::new()
+                .add("fr", "Shows things", "Affiche des choses")
+                .add("fr", "USAGE:", "UTILISATION :")
+                .add("fr", "Prints help information", "Affiche l'aide")
+                .add("fr", "error:", "erreur :")
+                .locale("fr"),
+        )
+        .add_cmd(Command::new("show").description("Shows things"));
+
+    let help = commander
+        .run_with_args_result(["program", "show", "--help"])
+        .unwrap_err()
+        .message;
+    assert!(help.contains("Affiche des choses"));
+    assert!(help.contains("UTILISATION :"));
+    assert!(help.contains("Affiche l'aide"));
+
+    let err = commander
+        .run_with_args_result(["program", "show", "--missing"])
+        .unwrap_err();
+    assert!(err.message.contains("erreur :"));
+    assert!(err.message.contains("UTILISATION :"));
+}
+
+#[test]
+fn run_capture() {
+    use std::io::Write;
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Command::new("show").runner_with_context(|_, _, ctx| {
+            writeln!(ctx.stdout(), "shown").unwrap();
+            writeln!(ctx.stderr(), "warning: nothing to show").unwrap();
+            Ok(())
+        }),
+    );
+
+    let run = commander.run_capture(["program", "show"]);
+    assert_eq!(run.stdout, "shown\n");
+    assert_eq!(run.stderr, "warning: nothing to show\n");
+    assert_eq!(run.status, 0);
+
+    let run = commander.run_capture(["program", "show", "--missing"]);
+    assert!(run.stdout.is_empty());
+    assert!(run.stderr.contains("Found argument '--missing'"));
+    assert_eq!(run.status, 1);
+
+    let run = commander.run_capture(["program", "--help"]);
+    assert!(run.stdout.contains("USAGE:"));
+    assert_eq!(run.status, 0);
+}
+
+#[test]
+fn fuzz() {
+    let commander = Commander::new()
+        .options(|app| {
+            app.name("program").arg(
+                Arg::with_name("env")
+                    .long("env")
+                    .takes_value(true)
+                    .possible_values(&["dev", "prod"]),
+            )
+        })
+        .add_cmd(
+            Commander::new()
+                .add_cmd(
+                    Command::new("foo")
+                        .options(|app| {
+                            app.arg(Arg::with_name("name").required(true))
+                                .arg(Arg::with_name("all").short("a").long("all"))
+                        })
+                        .runner(|_, _| panic!("fuzzing doesn't run commands")),
+                )
+                .into_cmd("show"),
+        )
+        .add_cmd(Command::new("what").options(|app| {
+            app.arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .takes_value(true)
+                    .multiple(true),
+            )
+        }));
+
+    clap_nested::testing::fuzz(&commander, 500, 42);
+}
+
+#[test]
+fn validator_error() {
+    let commander =
+        Commander::new()
+            .options(|app| app.name("program"))
+            .add_cmd(Command::new("what").options(|app| {
+                app.arg(
+                    Arg::with_name("count")
+                        .long("count")
+                        .takes_value(true)
+                        .validator(|value| {
+                            value.parse::<u8>().map(drop).map_err(|err| err.to_string())
+                        }),
+                )
+            }));
+
+    let err = commander
+        .run_with_args_result(["program", "what", "--count", "many"])
+        .unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::HelpDisplayed);
+    assert!(err.message.contains("Invalid value for '--count <count>'"));
+    assert!(err.message.ends_with("For more information try --help"));
+
+    clap_nested::testing::fuzz(&commander, 200, 7);
+}
+
+#[test]
+#[cfg(feature = "snapshot")]
+fn snapshots() {
+    use clap_nested::testing;
+
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Commander::new()
+            .add_cmd(Command::new("foo").description("Shows foo"))
+            .into_cmd("show")
+            .description("Shows things"),
+    );
+
+    assert_eq!(
+        testing::help_snapshot(&commander, &["show", "foo"]),
+        "program-show-foo __VERSION__
+__AUTHOR__
+Shows foo
+
+USAGE:
+    program show foo
+
+FLAGS:
+    -h, --help       Prints help information
+    -V, --version    Prints version information"
+    );
+    assert_eq!(
+        testing::snapshot(&commander, ["program", "show", "bar"]),
+        "error: error: Found argument 'bar' which wasn't expected, or isn't valid in this context
+
+program-show __VERSION__
+__AUTHOR__
+Shows things
+
+USAGE:
+    program show [SUBCOMMAND]
+
+FLAGS:
+    -h, --help       Prints help information
+    -V, --version    Prints version information
+
+SUBCOMMANDS:
+    foo     Shows foo
+    help    Prints this message or the help of the given subcommand(s)
+[exit status: 1]"
+    );
+}
+
+#[test]
+fn ancestor_matches() {
+    use std::cell::RefCell;
+
+    let seen = RefCell::new(Vec::new());
+    let seen = &seen;
+    let commander = Commander::new().options(|app| app.name("program")).add_cmd(
+        Commander::new()
+            .options(|app| app.arg(Arg::with_name("format").long("format").takes_value(true)))
+            .add_cmd(Command::new("foo").runner_with_context(move |_, _, ctx| {
+                let show = ctx.matches_for("show").unwrap();
+
+                seen.borrow_mut()
+                    .push(show.value_of("format").map(str::to_owned));
+                seen.borrow_mut()
+                    .push(ctx.matches_for("foo").map(|_| "foo".to_owned()));
+                assert!(ctx.matches_for("bar").is_none());
+                assert_eq!(ctx.matches_stack().len(), 3);
+                Ok(())
+            }))
+            .into_cmd("show"),
+    );
+
+    assert!(commander
+        .run_with_args_result(["program", "show", "--format", "json", "foo"])
+        .is_ok());
+    assert_eq!(
+        *seen.borrow(),
+        [Some("json".to_owned()), Some("foo".to_owned())]
+    );
+}
+
+#[test]
+fn command_path() {
+    use std::cell::RefCell;
+
+    let invoked = RefCell::new(Vec::new());
+    let invoked = &invoked;
+    let remove = move || {
+        Command::new("remove").runner_with_context(move |_, _, ctx| {
+            invoked
+                .borrow_mut()
+                .push(format!("[{}] removing", ctx.command_path()));
+            Ok(())
+        })
+    };
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Commander::new().add_cmd(remove()).into_cmd("user"))
+        .add_cmd(Commander::new().add_cmd(remove()).into_cmd("group"));
+
+    assert!(commander
+        .run_with_args_result(["program", "user", "remove"])
+        .is_ok());
+    assert!(commander
+        .run_with_args_result(["program", "group", "remove"])
+        .is_ok());
+    assert_eq!(
+        *invoked.borrow(),
+        ["[user remove] removing", "[group remove] removing"]
+    );
+}
+
+#[test]
+fn misplaced_subcommand() {
+    // Only the subcommands built for the parse are suggested, so all of them
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .lazy(false)
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo"))
+                .into_cmd("show"),
+        )
+        .add_cmd(Command::new("what"));
+
+    let err = commander
+        .run_with_args_result(["program", "foo"])
+        .unwrap_err();
+    let message = clap_nested::testing::strip_ansi(&err.message);
+
+    assert!(
+        message.starts_with(
+            "error: error: Found argument 'foo' which wasn't expected, \
+             or isn't valid in this context\n\tDid you mean 'program show foo'?\n\n"
+        ),
+        "{}",
+        message
+    );
+
+    let err = commander
+        .run_with_args_result(["program", "show", "what"])
+        .unwrap_err();
+
+    assert!(err.message.contains("Did you mean 'program what'?"));
+
+    let err = commander
+        .run_with_args_result(["program", "bar"])
+        .unwrap_err();
+
+    assert!(!err.message.contains("Did you mean"));
+}
+
+#[test]
+fn help_search() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(
+            Commander::new()
+                .add_cmd(Command::new("foo").description("Shows foo"))
+                .add_cmd(Command::new("bar").description("Shows bar"))
+                .into_cmd("show")
+                .description("Shows things"),
+        )
+        .add_cmd(Command::new("what").description("Prints FOO"));
+
+    assert_eq!(
+        commander
+            .search("foo")
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        ["show foo", "what"]
+    );
+    assert!(commander.search("nothing").is_empty());
+
+    assert_output(
+        &commander,
+        ["program", "help", "--search", "foo"],
+        "    program show foo    Shows foo
+    program what        Prints FOO",
+        false,
+    );
+    assert_output(
+        &commander,
+        ["program", "help", "--search=nothing"],
+        "error: No command matches 'nothing'",
+        false,
+    );
+}
@@ -0,0 +1,42 @@
+// The logger is set up once for the whole process, so its test runs in its own
+
+extern crate clap_nested;
+#[cfg(feature = "env_logger")]
+#[macro_use]
+extern crate log;
+
+use clap_nested::{Command, Commander, LogConfig};
+
+#[test]
+fn init_logging() {
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .add_cmd(Command::new("foo").runner(|_args, _matches| {
+            #[cfg(feature = "env_logger")]
+            {
+                debug!("details");
+                trace!("more details");
+            }
+            Ok(())
+        }))
+        .init_logging(
+            LogConfig::new()
+                .format_flag()
+                .env_var("CLAP_NESTED_TEST_LOG"),
+        );
+
+    let run = commander.run_capture(["program", "-vv", "foo", "--log-format", "json"]);
+    assert_eq!(run.status, 0);
+
+    // The same with `tracing-subscriber`, which takes over with all the features
+    #[cfg(feature = "env_logger")]
+    {
+        assert!(run.stderr.contains(r#""level":"DEBUG""#));
+        assert!(run.stderr.contains(r#""message":"details""#));
+        assert!(!run.stderr.contains("more details"));
+    }
+
+    let run = commander.run_capture(["program", "--log-format", "xml", "foo"]);
+    assert_eq!(run.status, 1);
+    assert!(run.stderr.contains("'xml' isn't a valid value"));
+}
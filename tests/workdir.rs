@@ -0,0 +1,46 @@
+// The working directory is shared by the whole process, which this test changes
+// and checks, so it runs in its own
+
+extern crate clap;
+extern crate clap_nested;
+
+use clap_nested::{Command, Commander};
+
+#[test]
+fn chdir_flag() {
+    use std::cell::Cell;
+
+    let dir = std::env::temp_dir().join(format!("clap-nested-chdir-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("marker"), "").unwrap();
+
+    let found = Cell::new(0);
+    let found = &found;
+    let commander = Commander::new()
+        .options(|app| app.name("program"))
+        .with_chdir_flag()
+        .chain("+")
+        .add_cmd(Command::new("check").runner(move |_args, _matches| {
+            if std::path::Path::new("marker").exists() {
+                found.set(found.get() + 1);
+            }
+            Ok(())
+        }));
+    let before = std::env::current_dir().unwrap();
+    let dir_arg = dir.to_str().unwrap();
+
+    assert!(commander
+        .run_with_args_result(["program", "-C", dir_arg, "check", "+", "check"])
+        .is_ok());
+    assert_eq!(found.get(), 2);
+    assert_eq!(std::env::current_dir().unwrap(), before);
+
+    let missing = dir.join("missing");
+    let err = commander
+        .run_with_args_result(["program", "-C", missing.to_str().unwrap(), "check"])
+        .unwrap_err();
+    assert_eq!(err.kind, clap::ErrorKind::Io);
+    assert!(err.message.contains("Cannot change the working directory"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}